@@ -17,6 +17,12 @@ pub struct Config {
     pub user_rpc: UserRpc,
     pub oauth_rpc: OauthRpc,
     pub kubernetes: Kubernetes,
+    pub stream: Stream,
+    pub discovery: Discovery,
+    pub gateway: Gateway,
+    pub grpc_web: GrpcWeb,
+    pub auth: Auth,
+    pub internal: Internal,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -41,6 +47,12 @@ pub struct Redis {
     pub redis_max_size: u32,
     pub redis_min_idle: u32,
     pub redis_connection_timeout: u64,
+    #[serde(default = "default_redis_notify_bus")]
+    pub redis_notify_bus: bool, // 以redis pub/sub轉發站內信到其他pod 關閉時改用grpc直接連線
+}
+
+fn default_redis_notify_bus() -> bool {
+    true
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -62,6 +74,14 @@ pub struct RabbitMQ {
     pub rabbitmq_max_connection: usize,
     pub rabbitmq_min_connection: usize,
     pub rabbitmq_connection_timeout: usize,
+    #[serde(default)]
+    pub rabbitmq_in_memory: bool, // 使用in-memory queue取代rabbit_mq 僅用於開發與測試
+    #[serde(default = "default_rabbitmq_schema_version")]
+    pub rabbitmq_schema_version: u32, // producer發送的訊息版本 0.舊版 1.envelope 所有consumer都更新後再改為1
+}
+
+fn default_rabbitmq_schema_version() -> u32 {
+    crate::mq_manager::envelope::LEGACY_SCHEMA_VERSION
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -88,6 +108,162 @@ pub struct OauthRpc {
     pub oauth_server_port: u32,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Stream {
+    #[serde(default = "default_stream_buffer_size")]
+    pub stream_frontend_buffer_size: usize, // 前台每個連線可暫存的訊息數量
+    #[serde(default = "default_stream_buffer_size")]
+    pub stream_backstage_buffer_size: usize, // 後台每個連線可暫存的訊息數量
+    #[serde(default)]
+    pub stream_overflow_policy: StreamOverflowPolicy, // 連線暫存已滿時的處理方式
+}
+
+fn default_stream_buffer_size() -> usize {
+    16
+}
+
+/// 連線暫存已滿時的處理方式
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamOverflowPolicy {
+    #[default]
+    Drop, // 丟棄這則訊息 保留連線
+    Evict, // 關閉這個連線
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Discovery {
+    #[serde(default)]
+    pub discovery_mode: DiscoveryMode, // 取得其他pod的方式
+    #[serde(default)]
+    pub discovery_static_peers: Vec<String>, // static模式的所有pod位址 以逗號分隔
+    #[serde(default)]
+    pub discovery_dns_name: String, // dns模式解析的名稱 dns_srv模式為SRV記錄名稱
+    #[serde(default = "default_discovery_cache_secs")]
+    pub discovery_cache_secs: u64, // 查詢結果的快取時間
+}
+
+fn default_discovery_cache_secs() -> u64 {
+    10
+}
+
+/// 取得其他pod的方式
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscoveryMode {
+    #[default]
+    Kubernetes, // 透過k8s api查詢
+    Static, // 設定檔指定
+    Dns,    // 解析DNS A記錄
+    DnsSrv, // 解析DNS SRV記錄
+    Redis,  // 各pod註冊到redis
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Gateway {
+    #[serde(default)]
+    pub gateway_enabled: bool, // 啟用websocket與sse的http服務 供瀏覽器直接連線
+    #[serde(default = "default_gateway_host")]
+    pub gateway_host: String,
+    #[serde(default = "default_gateway_port")]
+    pub gateway_port: u32,
+    #[serde(default)]
+    pub gateway_auth_mode: GatewayAuthMode, // 驗證連線用戶的方式
+    #[serde(default)]
+    pub gateway_allow_insecure_auth: bool, // 允許不驗證token的trusted_header與query模式 需明確開啟
+    #[serde(default = "default_gateway_keep_alive_secs")]
+    pub gateway_keep_alive_secs: u64, // 沒有訊息時送出keep alive的間隔
+}
+
+fn default_gateway_host() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_gateway_port() -> u32 {
+    8080
+}
+
+fn default_gateway_keep_alive_secs() -> u64 {
+    15
+}
+
+/// 驗證http連線用戶的方式
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GatewayAuthMode {
+    TrustedHeader, // 由前方的api gateway驗證後 以header帶入client_id與user_id
+    Query,         // 直接使用query string中的client_id與user_id 僅用於開發與測試
+    #[default]
+    Bearer, // 與grpc相同以bearer token驗證 瀏覽器無法帶header時可用access_token query
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GrpcWeb {
+    #[serde(default)]
+    pub grpc_web_enabled: bool, // grpc服務同時接受http/1.1的grpc-web請求
+    #[serde(default)]
+    pub grpc_web_allowed_origins: Vec<String>, // 允許跨域的來源 以逗號分隔 *為允許所有來源
+    #[serde(default = "default_grpc_web_cors_max_age_secs")]
+    pub grpc_web_cors_max_age_secs: u64, // 瀏覽器快取preflight結果的時間
+}
+
+fn default_grpc_web_cors_max_age_secs() -> u64 {
+    24 * 60 * 60
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Auth {
+    #[serde(default = "default_auth_enabled")]
+    pub auth_enabled: bool, // 驗證bearer token 關閉時信任請求中的client_id與user_id
+    #[serde(default)]
+    pub auth_mode: AuthMode, // 驗證token的方式
+    #[serde(default)]
+    pub auth_jwks_url: String, // jwks模式的公鑰位址
+    #[serde(default)]
+    pub auth_jwt_issuer: Option<String>, // jwks模式檢查token的iss
+    #[serde(default)]
+    pub auth_jwt_audience: Option<String>, // jwks模式檢查token的aud
+    #[serde(default)]
+    pub auth_jwt_algorithm: Option<String>, // jwks模式公鑰未指定alg時使用的演算法 例如RS256
+    #[serde(default = "default_auth_cache_secs")]
+    pub auth_cache_secs: u64, // 驗證結果的快取時間
+}
+
+fn default_auth_enabled() -> bool {
+    true
+}
+
+fn default_auth_cache_secs() -> u64 {
+    30
+}
+
+/// 驗證token的方式
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMode {
+    #[default]
+    Oauth, // 透過oauth服務驗證
+    Jwks, // 以jwks公鑰在本地驗證jwt
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Internal {
+    #[serde(default = "default_internal_host")]
+    pub internal_host: String, // pod之間轉發使用的grpc服務 不對外開放
+    #[serde(default = "default_internal_port")]
+    pub internal_port: u32,
+    #[serde(default)]
+    pub internal_secret: String, // pod之間呼叫時帶入的共用密鑰 未設定時不啟動內部grpc服務
+}
+
+fn default_internal_host() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_internal_port() -> u32 {
+    50052
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Kubernetes {
     pub pod_ip: String,
@@ -119,6 +295,12 @@ impl Config {
             pod_namespace: std::env::var("POD_NAMESPACE").unwrap_or_default(),
             deployment_name: std::env::var("DEPLOYMENT_NAME").unwrap_or_default(),
         };
+        let stream = envy::from_env::<Stream>().expect("load stream config error");
+        let discovery = envy::from_env::<Discovery>().expect("load discovery config error");
+        let gateway = envy::from_env::<Gateway>().expect("load gateway config error");
+        let grpc_web = envy::from_env::<GrpcWeb>().expect("load grpc web config error");
+        let auth = envy::from_env::<Auth>().expect("load auth config error");
+        let internal = envy::from_env::<Internal>().expect("load internal config error");
         let config = Config {
             host,
             telemetry,
@@ -130,6 +312,12 @@ impl Config {
             user_rpc,
             oauth_rpc,
             kubernetes,
+            stream,
+            discovery,
+            gateway,
+            grpc_web,
+            auth,
+            internal,
         };
         Arc::new(config)
     }
@@ -174,3 +362,27 @@ pub fn get_oauth_rpc() -> &'static OauthRpc {
 pub fn get_kubernetes() -> &'static Kubernetes {
     &CONFIG.kubernetes
 }
+
+pub fn get_stream() -> &'static Stream {
+    &CONFIG.stream
+}
+
+pub fn get_discovery() -> &'static Discovery {
+    &CONFIG.discovery
+}
+
+pub fn get_gateway() -> &'static Gateway {
+    &CONFIG.gateway
+}
+
+pub fn get_grpc_web() -> &'static GrpcWeb {
+    &CONFIG.grpc_web
+}
+
+pub fn get_auth() -> &'static Auth {
+    &CONFIG.auth
+}
+
+pub fn get_internal() -> &'static Internal {
+    &CONFIG.internal
+}
@@ -1,14 +1,19 @@
 use crate::consumers::consumer::Job;
+use crate::consumers::delivery_progress;
 
 use crate::consumers::error::JobError;
-use crate::mq_manager::{BatchNotifyModel, SingleNotifyModel};
+use crate::consumers::quarantine;
+use crate::consumers::retry;
+use crate::helper;
+use crate::mq_manager::{
+    envelope, trace_context, BatchNotifyModel, MessageQueue, QueueConsumer, QueueMessage,
+    QueueName, SingleNotifyModel,
+};
 use crate::notify_server::application::user_rpc;
 use crate::repository;
 use crate::{enums, notify_server};
-use crate::{helper, mq_manager};
-use futures::StreamExt;
+use kgs_tracing::tracing::{self, Instrument};
 use kgs_tracing::{info, warn};
-use lapin::Consumer;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::Arc;
@@ -19,16 +24,20 @@ use super::error::ConsumerError;
 #[derive(Debug)]
 pub struct BatchNotifyJob {
     job_name: String,
-    rabbit_consumer: Option<Consumer>,
+    message_queue: Arc<dyn MessageQueue>,
+    queue_consumer: Option<QueueConsumer>,
     message: Option<Arc<BatchNotifyModel>>,
+    delivery: Option<QueueMessage>,
 }
 
 impl BatchNotifyJob {
-    pub fn new(job_name: &str) -> Self {
+    pub fn new(job_name: &str, message_queue: Arc<dyn MessageQueue>) -> Self {
         BatchNotifyJob {
             job_name: job_name.to_string(),
-            rabbit_consumer: None,
+            message_queue,
+            queue_consumer: None,
             message: None,
+            delivery: None,
         }
     }
 }
@@ -39,113 +48,85 @@ impl Job for BatchNotifyJob {
         &self.job_name
     }
 
-    async fn start(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    async fn start(&mut self) -> Result<(), JobError> {
         info!("{} start", self.job_name.as_str());
 
-        // get rabbit_mq consumer
-        match mq_manager::consume_batch_notify(self.job_name()).await {
-            Ok(r) => self.rabbit_consumer = Some(r),
+        // get queue consumer
+        match self
+            .message_queue
+            .consume(QueueName::BatchNotify, self.job_name())
+            .await
+        {
+            Ok(r) => self.queue_consumer = Some(r),
             Err(err) => {
-                warn!("獲取rabbit_mq consumer錯誤 {}", err);
-                return Err(Box::new(err));
+                warn!("獲取queue consumer錯誤 {}", err);
+                return Err(err.into());
             }
         };
 
         Ok(())
     }
 
-    async fn update(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    async fn update(&mut self) -> Result<(), JobError> {
         info!("{} update", self.job_name.as_str());
         // reset message
         if self.message.is_some() {
             self.message = None;
         }
+        self.delivery = None;
 
-        // early return if rabbit_consumer is none
-        if self.rabbit_consumer.as_ref().is_none() {
-            return Err(Box::new(JobError::new(
-                "rabbit_consumer is none".to_string(),
-                None,
-            )));
+        // early return if queue_consumer is none
+        if self.queue_consumer.as_ref().is_none() {
+            return Err(JobError::Internal("queue_consumer is none".to_string()));
         }
 
-        // get delivery form rabbit_mq
-        let delivery = match self.rabbit_consumer.as_mut().unwrap().next().await {
+        // get delivery form queue
+        let delivery = match self.queue_consumer.as_mut().unwrap().next().await {
             Some(r) => r.map_err(|err| {
                 warn!("獲取Delivery錯誤 {}", err);
-                Box::new(err)
+                JobError::from(err)
             })?,
             None => {
                 return Ok(());
             } // if no message, wait for next message
         };
 
-        // ack
-        mq_manager::rabbit_consumer_ack(&delivery)
-            .await
-            .map_err(|err| {
-                warn!("ack錯誤 {}", err);
-                Box::new(err)
-            })?;
-
-        // get trans from database_manager
-        let trans = database_manager::sea_orm::get_trans()
-            .await
-            .map_err(|err| {
-                warn!("獲取db trans錯誤 {}", err);
-                Box::new(err)
-            })?;
-
-        // parse ConsumerModelNotify from delivery
-        let batch_notify_model: BatchNotifyModel = match std::str::from_utf8(&delivery.data) {
-            Ok(msg_str) => serde_json::from_str(msg_str).map_err(|err| {
-                warn!(" deserialize from binary 錯誤{}", err);
-                Box::new(err)
-            })?,
-            Err(err) => {
-                warn!("binary轉成str錯誤 {} ", err);
-                return Err(Box::new(err));
-            }
-        };
-
-        // stone received_model to self.message
-        let batch_notify_model_arc = Arc::new(batch_notify_model);
-        self.message = Some(Arc::clone(&batch_notify_model_arc));
-
-        // publish each message to user
-        publish_each_message_to_user(batch_notify_model_arc.clone()).await?;
-
-        // update task status
-        repository::backstage_send_task::update_task_status(
-            &trans,
-            batch_notify_model_arc.task_id,
-            enums::TaskStatus::Success,
-            None,
+        // parse BatchNotifyModel from delivery, 無法解析的訊息存入隔離表後略過
+        let message_envelope = match quarantine::decode_or_quarantine::<BatchNotifyModel>(
+            &*self.message_queue,
+            &delivery,
         )
-        .await
-        .map_err(|err| {
-            warn!("update task status錯誤 {}", err);
-            Box::new(JobError::new("更新task status時發生錯誤".to_string(), None))
-        })?;
+        .await?
+        {
+            Some(message_envelope) => message_envelope,
+            None => return Ok(()),
+        };
+        info!(
+            "收到訊息 schema_version: {}, idempotency_key: {}",
+            message_envelope.schema_version, message_envelope.idempotency_key
+        );
 
-        // commit
-        trans.commit().await.map_err(|err| {
-            warn!("commit trans錯誤 {}", err);
-            Box::new(err)
+        // ack
+        self.message_queue.ack(&delivery).await.map_err(|err| {
+            warn!("ack錯誤 {}", err);
+            JobError::from(err)
         })?;
+        self.delivery = Some(delivery.clone());
 
-        Ok(())
+        // 以producer帶來的trace context作為parent 串接同一條trace
+        let span = tracing::info_span!("handle_delivery", job_name = self.job_name.as_str());
+        trace_context::set_parent(&span, &delivery.headers);
+        self.handle_delivery(message_envelope.payload)
+            .instrument(span)
+            .await
     }
 
-    async fn end(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    async fn end(&mut self) -> Result<(), JobError> {
         info!("{} end", self.job_name.as_str());
         Ok(())
     }
 
-    async fn error_handler(
-        &mut self,
-        err: ConsumerError,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    async fn error_handler(&mut self, err: ConsumerError) -> Result<(), JobError> {
         warn!("{} error_handler {}", self.job_name.as_str(), err);
 
         match err {
@@ -155,16 +136,28 @@ impl Job for BatchNotifyJob {
                 self.start().await?;
             }
             ConsumerError::UpdateStateError(err) => {
-                // 如果在update階段發生錯誤 我會檢查是否有拿到message 如果有 將 task狀態改回fail 並記錄 err message
-                // 然後繼續下一個任務
-                warn!("UpdateStateError {}", err);
+                // 如果在update階段發生錯誤 可重試的錯誤放回queue
+                // 永久性錯誤或超過重試次數時 將 task狀態改回fail 並記錄 err message 然後繼續下一個任務
+                warn!("UpdateStateError {} class: {}", err, err.error_class());
+
+                if let Some(delivery) = self.delivery.take() {
+                    if err.is_retryable() {
+                        if retry::requeue(&*self.message_queue, &delivery).await? {
+                            self.message = None;
+                            return Ok(());
+                        }
+
+                        // 超過重試次數 移至dead letter queue
+                        retry::dead_letter(&*self.message_queue, &delivery, &err).await?;
+                    }
+                }
 
                 // get trans from database_manager
                 let trans = database_manager::sea_orm::get_trans()
                     .await
                     .map_err(|err| {
                         warn!("獲取db trans錯誤 {}", err);
-                        Box::new(err)
+                        JobError::from(err)
                     })?;
 
                 if let Some(model_arc) = &self.message {
@@ -177,13 +170,13 @@ impl Job for BatchNotifyJob {
                     .await
                     .map_err(|err| {
                         warn!("update task status錯誤 {}", err);
-                        Box::new(JobError::new("更新task status時發生錯誤".to_string(), None))
+                        JobError::Database(format!("更新task status時發生錯誤: {}", err))
                     })?;
 
                     // commit
                     trans.commit().await.map_err(|err| {
                         warn!("commit錯誤 {}", err);
-                        Box::new(err)
+                        JobError::from(err)
                     })?;
                 }
             }
@@ -201,22 +194,30 @@ impl Job for BatchNotifyJob {
 }
 
 async fn publish_each_message_to_user(
+    message_queue: &dyn MessageQueue,
     model: Arc<BatchNotifyModel>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+) -> Result<(), JobError> {
     // get each user address
     let user_addresses =
         user_rpc::get_email_and_phone_by_user_ids(model.frontend_client_id, &model.receiver_ids)
             .await
-            .map_err(|_err| {
+            .map_err(|err| {
                 let msg: String = format!("獲取user address錯誤 client_id: {}", model.client_id);
                 warn!("{}", msg);
-                Box::new(JobError::new(msg, None))
+                JobError::rpc(msg, err)
             })?;
 
+    // 重試時略過已發布的通知 避免用戶重複收到
+    let mut progress = delivery_progress::BatchProgress::load(model.task_id)?;
+
     // publish each message
     for user_address in user_addresses.email_and_phone.iter() {
         // send each template to user
-        for template in &model.templates {
+        for (template_index, template) in model.templates.iter().enumerate() {
+            if progress.is_published(user_address.user_id, template_index) {
+                continue;
+            }
+
             let notify_id = helper::generate_snowflake_id().await;
             let receive_address =
                 notify_server::application::notify_handler::get_receive_address_opt(
@@ -238,12 +239,62 @@ async fn publish_each_message_to_user(
                 content: template.content.clone(),
                 receive_address,
                 key_map: HashMap::new(),
+                expires_at: None,
             };
 
             // publish message
-            mq_manager::publish_single_notify(&single_model).await?;
+            let trace_context = trace_context::inject_current_context();
+            let payload = envelope::encode(&single_model, &trace_context)?;
+            message_queue
+                .publish(QueueName::SingleNotify, payload, trace_context)
+                .await?;
+            progress.save_published(user_address.user_id, template_index)?;
         }
     }
 
     Ok(())
 }
+
+impl BatchNotifyJob {
+    /// 處理從queue收到的訊息
+    async fn handle_delivery(
+        &mut self,
+        batch_notify_model: BatchNotifyModel,
+    ) -> Result<(), JobError> {
+        // get trans from database_manager
+        let trans = database_manager::sea_orm::get_trans()
+            .await
+            .map_err(|err| {
+                warn!("獲取db trans錯誤 {}", err);
+                JobError::from(err)
+            })?;
+
+        // stone received_model to self.message
+        let batch_notify_model_arc = Arc::new(batch_notify_model);
+        self.message = Some(Arc::clone(&batch_notify_model_arc));
+
+        // publish each message to user
+        publish_each_message_to_user(&*self.message_queue, batch_notify_model_arc.clone()).await?;
+
+        // update task status
+        repository::backstage_send_task::update_task_status(
+            &trans,
+            batch_notify_model_arc.task_id,
+            enums::TaskStatus::Success,
+            None,
+        )
+        .await
+        .map_err(|err| {
+            warn!("update task status錯誤 {}", err);
+            JobError::Database(format!("更新task status時發生錯誤: {}", err))
+        })?;
+
+        // commit
+        trans.commit().await.map_err(|err| {
+            warn!("commit trans錯誤 {}", err);
+            JobError::from(err)
+        })?;
+
+        Ok(())
+    }
+}
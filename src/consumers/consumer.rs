@@ -3,18 +3,15 @@ use std::sync::Arc;
 use tokio::{sync::Mutex, task::JoinHandle};
 use tonic::async_trait;
 
-use super::error::ConsumerError;
+use super::error::{ConsumerError, JobError};
 
 #[async_trait]
 pub trait Job: Send + Sync + 'static {
     fn job_name(&self) -> &str;
-    async fn start(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
-    async fn update(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
-    async fn end(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
-    async fn error_handler(
-        &mut self,
-        err: ConsumerError,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+    async fn start(&mut self) -> Result<(), JobError>;
+    async fn update(&mut self) -> Result<(), JobError>;
+    async fn end(&mut self) -> Result<(), JobError>;
+    async fn error_handler(&mut self, err: ConsumerError) -> Result<(), JobError>;
     fn is_continue(&self) -> bool;
 }
 
@@ -0,0 +1,142 @@
+use crate::consumers::consumer::Job;
+use crate::consumers::error::JobError;
+use crate::entity;
+use crate::enums;
+use crate::notify_server::controller::frontend_notify::FrontendNotifyServer;
+use crate::repository;
+use kgs_err::models::status::Status as KgsStatus;
+use kgs_tracing::{info, tracing, warn};
+use std::fmt::Debug;
+use tonic::async_trait;
+
+use super::error::ConsumerError;
+
+const SCHEDULE_INTERVAL_SECS: u64 = 10; // 每10秒掃描一次到期的延遲通知
+const BATCH_SIZE: u64 = 100; // 每次最多處理的通知數量
+
+/// 延遲通知排程 到達發送時間時發送通知
+#[derive(Debug)]
+pub struct DelayNotifyJob {
+    job_name: String,
+}
+
+impl DelayNotifyJob {
+    pub fn new(job_name: &str) -> Self {
+        DelayNotifyJob {
+            job_name: job_name.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Job for DelayNotifyJob {
+    fn job_name(&self) -> &str {
+        &self.job_name
+    }
+
+    async fn start(&mut self) -> Result<(), JobError> {
+        info!("{} start", self.job_name.as_str());
+        Ok(())
+    }
+
+    async fn update(&mut self) -> Result<(), JobError> {
+        tokio::time::sleep(std::time::Duration::from_secs(SCHEDULE_INTERVAL_SECS)).await;
+
+        let db = database_manager::sea_orm::get_db();
+        let now = chrono::Utc::now().naive_utc();
+
+        // get due delay notifies
+        let delay_notifies = repository::delay_notify::find_due_list(&*db, now, BATCH_SIZE)
+            .await
+            .map_err(|err| {
+                let msg = format!("獲取到期的延遲通知錯誤: {}", err);
+                warn!("{}", msg);
+                JobError::Database(msg)
+            })?;
+
+        for delay_notify in delay_notifies {
+            let delay_notify_id = delay_notify.id;
+            if let Err(err) = send_delay_notify(delay_notify).await {
+                warn!(
+                    "延遲通知發送錯誤 delay_notify_id: {} err: {}",
+                    delay_notify_id, err
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn end(&mut self) -> Result<(), JobError> {
+        info!("{} end", self.job_name.as_str());
+        Ok(())
+    }
+
+    async fn error_handler(&mut self, err: ConsumerError) -> Result<(), JobError> {
+        warn!("{} error_handler {}", self.job_name.as_str(), err);
+
+        match err {
+            ConsumerError::StartStateError(err) => {
+                warn!("StartStateError {}", err);
+                self.start().await?;
+            }
+            ConsumerError::UpdateStateError(err) => {
+                // 掃描失敗時等待下一次掃描即可
+                warn!("UpdateStateError {}", err);
+            }
+            ConsumerError::EndStateError(err) => {
+                warn!("EndStateError {}", err);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_continue(&self) -> bool {
+        true
+    }
+}
+
+/// 發送單筆延遲通知 發送失敗時將狀態改為失敗並記錄錯誤訊息
+#[tracing::instrument]
+async fn send_delay_notify(delay_notify: entity::delay_notify::Model) -> Result<(), KgsStatus> {
+    let db = database_manager::sea_orm::get_db();
+
+    // claim the delay_notify, skip if it was canceled or taken by another pod
+    if !repository::delay_notify::update_status(
+        &*db,
+        delay_notify.id,
+        enums::DelayNotifyStatus::Pending,
+        enums::DelayNotifyStatus::Sent,
+        None,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+
+    // publish notify
+    if let Err(err) = FrontendNotifyServer::publish_event_notify(
+        delay_notify.client_id,
+        delay_notify.user_id,
+        &delay_notify.notify_event,
+        &delay_notify.get_key_map(),
+        delay_notify
+            .expires_at
+            .map(|expires_at| expires_at.and_utc().timestamp_millis()),
+    )
+    .await
+    {
+        repository::delay_notify::update_status(
+            &*db,
+            delay_notify.id,
+            enums::DelayNotifyStatus::Sent,
+            enums::DelayNotifyStatus::Fail,
+            Some(err.to_string()),
+        )
+        .await?;
+        return Err(err);
+    }
+
+    Ok(())
+}
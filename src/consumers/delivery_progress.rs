@@ -0,0 +1,100 @@
+use crate::consumers::error::JobError;
+use kgs_tracing::{tracing, warn};
+use redis::Commands;
+use std::collections::HashSet;
+
+const REDIS_KEY: &str = "notify_server";
+const PROGRESS_EXPIRE_SECS: u64 = 60 * 60 * 24; // 需大於訊息重試的總時間
+
+/// 已發送的單筆通知 value為寫入notify_record的notify_id
+fn get_sent_key(idempotency_key: &str) -> String {
+    format!("{}:sent:{}", REDIS_KEY, idempotency_key)
+}
+
+/// 批次任務中已發布給consumer的通知 以set保存
+fn get_batch_progress_key(task_id: i64) -> String {
+    format!("{}:batch_progress:{}", REDIS_KEY, task_id)
+}
+
+/// 取得已發送時使用的notify_id 重試時據此略過已發送的email sms與站內信
+#[tracing::instrument]
+pub fn get_sent_notify_id(idempotency_key: &str) -> Result<Option<i64>, JobError> {
+    let mut redis_conn = database_manager::redis::RedisManager::get_conn();
+    redis_conn.get(get_sent_key(idempotency_key)).map_err(|e| {
+        let msg = format!("get sent notify from redis failed: {}", e);
+        warn!("{}", msg);
+        JobError::Cache(msg)
+    })
+}
+
+/// 記錄通知已發送
+#[tracing::instrument]
+pub fn save_sent_notify_id(idempotency_key: &str, notify_id: i64) -> Result<(), JobError> {
+    let mut redis_conn = database_manager::redis::RedisManager::get_conn();
+    redis_conn
+        .set_ex(
+            get_sent_key(idempotency_key),
+            notify_id,
+            PROGRESS_EXPIRE_SECS,
+        )
+        .map_err(|e| {
+            let msg = format!("save sent notify to redis failed: {}", e);
+            warn!("{}", msg);
+            JobError::Cache(msg)
+        })
+}
+
+/// 批次任務中每位用戶的每個模板各自記錄
+fn get_batch_progress_member(user_id: i64, template_index: usize) -> String {
+    format!("{}:{}", user_id, template_index)
+}
+
+/// 批次任務中已發布給consumer的通知 重試時只發布剩下的通知
+#[derive(Debug)]
+pub struct BatchProgress {
+    task_id: i64,
+    published: HashSet<String>,
+}
+
+impl BatchProgress {
+    #[tracing::instrument]
+    pub fn load(task_id: i64) -> Result<Self, JobError> {
+        let mut redis_conn = database_manager::redis::RedisManager::get_conn();
+        let published = redis_conn
+            .smembers(get_batch_progress_key(task_id))
+            .map_err(|e| {
+                let msg = format!("get batch progress from redis failed: {}", e);
+                warn!("{}", msg);
+                JobError::Cache(msg)
+            })?;
+
+        Ok(BatchProgress { task_id, published })
+    }
+
+    pub fn is_published(&self, user_id: i64, template_index: usize) -> bool {
+        self.published
+            .contains(&get_batch_progress_member(user_id, template_index))
+    }
+
+    /// 記錄通知已發布
+    #[tracing::instrument(skip(self))]
+    pub fn save_published(&mut self, user_id: i64, template_index: usize) -> Result<(), JobError> {
+        let key = get_batch_progress_key(self.task_id);
+        let member = get_batch_progress_member(user_id, template_index);
+        let mut redis_conn = database_manager::redis::RedisManager::get_conn();
+        redis::pipe()
+            .sadd(&key, &member)
+            .ignore()
+            .expire(&key, PROGRESS_EXPIRE_SECS as i64)
+            .ignore()
+            .query::<()>(&mut *redis_conn)
+            .map_err(|e| {
+                let msg = format!("save batch progress to redis failed: {}", e);
+                warn!("{}", msg);
+                JobError::Cache(msg)
+            })?;
+
+        self.published.insert(member);
+        Ok(())
+    }
+}
@@ -1,11 +1,12 @@
+use super::JobError;
 use std::fmt;
 
 #[derive(Debug)]
 /// 錯誤類別 for Consumer
 pub enum ConsumerError {
-    StartStateError(Box<dyn std::error::Error + Send + Sync>),
-    UpdateStateError(Box<dyn std::error::Error + Send + Sync>),
-    EndStateError(Box<dyn std::error::Error + Send + Sync>),
+    StartStateError(JobError),
+    UpdateStateError(JobError),
+    EndStateError(JobError),
 }
 
 impl std::error::Error for ConsumerError {}
@@ -1,28 +1,173 @@
+use super::SendRequestError;
+use crate::mq_manager::QueueError;
+use kgs_err::models::status::Status as KgsStatus;
 use std::error::Error;
 use std::fmt;
 
+/// 錯誤分類 決定consumer要重試還是直接記錄失敗
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    Retryable, // 暫時性錯誤 例如連線逾時 資料庫斷線 重試後可能成功
+    Permanent, // 永久性錯誤 例如電話號碼錯誤 訊息格式錯誤 重試也不會成功
+}
+
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorClass::Retryable => write!(f, "retryable"),
+            ErrorClass::Permanent => write!(f, "permanent"),
+        }
+    }
+}
+
 #[derive(Debug)]
-pub struct JobError {
-    message: String,
-    source: Option<Box<dyn Error + 'static + Send + Sync>>,
+pub enum JobError {
+    SendRequest(SendRequestError),              // 呼叫email sms服務錯誤
+    Database(String),                           // 資料庫錯誤
+    Cache(String),                              // redis錯誤
+    Rpc { message: String, status: KgsStatus }, // 呼叫其他服務錯誤
+    Parse(String),                              // 訊息解析錯誤
+    Queue(QueueError),                          // queue連線 發送 ack錯誤
+    Internal(String),                           // 其他無法重試的錯誤
 }
 
 impl Error for JobError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        self.source
-            .as_deref()
-            .map(|err| err as &(dyn Error + 'static))
+        match self {
+            JobError::SendRequest(err) => Some(err),
+            JobError::Queue(err) => Some(err.as_ref()),
+            _ => None,
+        }
     }
 }
 
 impl fmt::Display for JobError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.message)
+        match self {
+            JobError::SendRequest(err) => write!(f, "Send Request Error: {}", err),
+            JobError::Database(msg) => write!(f, "Database Error: {}", msg),
+            JobError::Cache(msg) => write!(f, "Cache Error: {}", msg),
+            JobError::Rpc { message, status } => write!(f, "Rpc Error: {} {}", message, status),
+            JobError::Parse(msg) => write!(f, "Parse Error: {}", msg),
+            JobError::Queue(err) => write!(f, "Queue Error: {}", err),
+            JobError::Internal(msg) => write!(f, "Internal Error: {}", msg),
+        }
     }
 }
 
 impl JobError {
-    pub fn new(message: String, source: Option<Box<dyn Error + 'static + Send + Sync>>) -> Self {
-        Self { message, source }
+    pub fn rpc(message: String, status: KgsStatus) -> Self {
+        JobError::Rpc { message, status }
+    }
+
+    pub fn error_class(&self) -> ErrorClass {
+        match self {
+            JobError::SendRequest(err) if err.kind().is_retryable() => ErrorClass::Retryable,
+            JobError::SendRequest(_) => ErrorClass::Permanent,
+            JobError::Database(_) => ErrorClass::Retryable,
+            JobError::Cache(_) => ErrorClass::Retryable,
+            JobError::Rpc { status, .. } => match status {
+                KgsStatus::DataNotFound | KgsStatus::InvalidArgument => ErrorClass::Permanent,
+                _ => ErrorClass::Retryable,
+            },
+            JobError::Parse(_) => ErrorClass::Permanent,
+            JobError::Queue(_) => ErrorClass::Retryable,
+            JobError::Internal(_) => ErrorClass::Permanent,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.error_class() == ErrorClass::Retryable
+    }
+}
+
+impl From<SendRequestError> for JobError {
+    fn from(err: SendRequestError) -> Self {
+        JobError::SendRequest(err)
+    }
+}
+
+impl From<sea_orm::DbErr> for JobError {
+    fn from(err: sea_orm::DbErr) -> Self {
+        JobError::Database(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for JobError {
+    fn from(err: serde_json::Error) -> Self {
+        JobError::Parse(err.to_string())
+    }
+}
+
+impl From<QueueError> for JobError {
+    fn from(err: QueueError) -> Self {
+        JobError::Queue(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consumers::error::ErrorKind;
+
+    fn send_request_error(kind: ErrorKind) -> JobError {
+        JobError::SendRequest(SendRequestError::new(kind, "error".to_string(), None))
+    }
+
+    #[test]
+    fn send_request_error_class() {
+        assert_eq!(
+            send_request_error(ErrorKind::Timeout).error_class(),
+            ErrorClass::Retryable
+        );
+        assert_eq!(
+            send_request_error(ErrorKind::ServerError).error_class(),
+            ErrorClass::Retryable
+        );
+
+        // the provider may have sent the message, retrying could send it twice
+        assert_eq!(
+            send_request_error(ErrorKind::ConnectionError).error_class(),
+            ErrorClass::Permanent
+        );
+        assert_eq!(
+            send_request_error(ErrorKind::StatusError).error_class(),
+            ErrorClass::Permanent
+        );
+        assert_eq!(
+            send_request_error(ErrorKind::InvalidPhoneNumber).error_class(),
+            ErrorClass::Permanent
+        );
+    }
+
+    #[test]
+    fn rpc_error_class() {
+        assert_eq!(
+            JobError::rpc("error".to_string(), KgsStatus::DataNotFound).error_class(),
+            ErrorClass::Permanent
+        );
+        assert_eq!(
+            JobError::rpc("error".to_string(), KgsStatus::InvalidArgument).error_class(),
+            ErrorClass::Permanent
+        );
+        assert_eq!(
+            JobError::rpc("error".to_string(), KgsStatus::InternalServerError).error_class(),
+            ErrorClass::Retryable
+        );
+    }
+
+    #[test]
+    fn other_error_class() {
+        assert!(JobError::Database("error".to_string()).is_retryable());
+        assert!(JobError::Cache("error".to_string()).is_retryable());
+        assert!(JobError::Queue("error".into()).is_retryable());
+        assert!(!JobError::Parse("error".to_string()).is_retryable());
+        assert!(!JobError::Internal("error".to_string()).is_retryable());
+    }
+
+    #[test]
+    fn parse_error_from_serde_is_permanent() {
+        let err = serde_json::from_str::<i64>("invalid").unwrap_err();
+        assert_eq!(JobError::from(err).error_class(), ErrorClass::Permanent);
     }
 }
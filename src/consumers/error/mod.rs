@@ -3,6 +3,7 @@ mod job_error;
 mod request_error;
 
 pub use consumer_error::ConsumerError;
+pub use job_error::ErrorClass;
 pub use job_error::JobError;
 pub use request_error::ErrorKind;
 pub use request_error::SendRequestError;
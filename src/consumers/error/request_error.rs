@@ -9,8 +9,10 @@ pub struct SendRequestError {
 
 #[derive(Debug)]
 pub enum ErrorKind {
-    ConnectionError,
-    StatusError,
+    ConnectionError, // 連線失敗或讀取回應失敗 無法確定服務商是否已發送
+    Timeout,         // 呼叫逾時
+    ServerError,     // 服務商回傳5xx
+    StatusError,     // 服務商回傳其他失敗狀態 ex: 4xx
     InvalidPhoneNumber,
 }
 
@@ -32,12 +34,45 @@ impl fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ErrorKind::ConnectionError => write!(f, "Connection Error"),
+            ErrorKind::Timeout => write!(f, "Timeout"),
+            ErrorKind::ServerError => write!(f, "Server Error"),
             ErrorKind::StatusError => write!(f, "Status Error"),
             ErrorKind::InvalidPhoneNumber => write!(f, "Invalid Phone Number"),
         }
     }
 }
 
+impl ErrorKind {
+    /// 只有逾時與服務商5xx可以重試 4xx與電話號碼錯誤重試也不會成功
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ErrorKind::Timeout => true,
+            ErrorKind::ServerError => true,
+            ErrorKind::ConnectionError => false,
+            ErrorKind::StatusError => false,
+            ErrorKind::InvalidPhoneNumber => false,
+        }
+    }
+
+    /// 依呼叫服務商的錯誤分類
+    pub fn from_reqwest(err: &reqwest::Error) -> Self {
+        if err.is_timeout() {
+            ErrorKind::Timeout
+        } else {
+            ErrorKind::ConnectionError
+        }
+    }
+
+    /// 依服務商回傳的http狀態分類
+    pub fn from_status(status: reqwest::StatusCode) -> Self {
+        if status.is_server_error() {
+            ErrorKind::ServerError
+        } else {
+            ErrorKind::StatusError
+        }
+    }
+}
+
 impl SendRequestError {
     pub fn new(
         kind: ErrorKind,
@@ -50,4 +85,8 @@ impl SendRequestError {
             source,
         }
     }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
 }
@@ -1,24 +1,52 @@
-use super::{batch_notify_job::BatchNotifyJob, consumer};
+use super::{
+    batch_notify_job::BatchNotifyJob, consumer, delay_notify_job::DelayNotifyJob,
+    presence_heartbeat_job::PresenceHeartbeatJob, recurring_campaign_job::RecurringCampaignJob,
+    redeliver_notify_job::RedeliverNotifyJob,
+};
 use crate::consumers::single_notify_job::SingleNotifyJob;
+use crate::mq_manager::MessageQueue;
 use kgs_tracing::tracing;
+use std::sync::Arc;
 
 #[tracing::instrument]
-pub fn start() -> Vec<tokio::task::JoinHandle<()>> {
+pub fn start(message_queue: Arc<dyn MessageQueue>) -> Vec<tokio::task::JoinHandle<()>> {
     // 單一通知消費者
-    let single_notify_job_1 = SingleNotifyJob::new("single_notify_consumer_1");
-    let single_notify_job_2 = SingleNotifyJob::new("single_notify_consumer_2");
-    let single_notify_job_3 = SingleNotifyJob::new("single_notify_consumer_3");
-    let single_notify_job_4 = SingleNotifyJob::new("single_notify_consumer_4");
-    let single_notify_job_5 = SingleNotifyJob::new("single_notify_consumer_5");
-    let single_notify_job_6 = SingleNotifyJob::new("single_notify_consumer_6");
-    let single_notify_job_7 = SingleNotifyJob::new("single_notify_consumer_7");
-    let single_notify_job_8 = SingleNotifyJob::new("single_notify_consumer_8");
-    let single_notify_job_9 = SingleNotifyJob::new("single_notify_consumer_9");
-    let single_notify_job_10 = SingleNotifyJob::new("single_notify_consumer_10");
+    let single_notify_job_1 =
+        SingleNotifyJob::new("single_notify_consumer_1", message_queue.clone());
+    let single_notify_job_2 =
+        SingleNotifyJob::new("single_notify_consumer_2", message_queue.clone());
+    let single_notify_job_3 =
+        SingleNotifyJob::new("single_notify_consumer_3", message_queue.clone());
+    let single_notify_job_4 =
+        SingleNotifyJob::new("single_notify_consumer_4", message_queue.clone());
+    let single_notify_job_5 =
+        SingleNotifyJob::new("single_notify_consumer_5", message_queue.clone());
+    let single_notify_job_6 =
+        SingleNotifyJob::new("single_notify_consumer_6", message_queue.clone());
+    let single_notify_job_7 =
+        SingleNotifyJob::new("single_notify_consumer_7", message_queue.clone());
+    let single_notify_job_8 =
+        SingleNotifyJob::new("single_notify_consumer_8", message_queue.clone());
+    let single_notify_job_9 =
+        SingleNotifyJob::new("single_notify_consumer_9", message_queue.clone());
+    let single_notify_job_10 =
+        SingleNotifyJob::new("single_notify_consumer_10", message_queue.clone());
 
     // 批次通知消費者
-    let batch_notify_job_1 = BatchNotifyJob::new("batch_notify_consumer_1");
-    let batch_notify_job_2 = BatchNotifyJob::new("batch_notify_consumer_2");
+    let batch_notify_job_1 = BatchNotifyJob::new("batch_notify_consumer_1", message_queue.clone());
+    let batch_notify_job_2 = BatchNotifyJob::new("batch_notify_consumer_2", message_queue.clone());
+
+    // 週期性活動排程
+    let recurring_campaign_job = RecurringCampaignJob::new("recurring_campaign_scheduler");
+
+    // 延遲通知排程
+    let delay_notify_job = DelayNotifyJob::new("delay_notify_scheduler");
+
+    // pod心跳與在線用戶位置
+    let presence_heartbeat_job = PresenceHeartbeatJob::new("presence_heartbeat_scheduler");
+
+    // 重新推送未確認收到的站內信
+    let redeliver_notify_job = RedeliverNotifyJob::new("redeliver_notify_scheduler");
 
     let consumers = vec![
         consumer::Consumer::new(single_notify_job_1, 3),
@@ -33,6 +61,10 @@ pub fn start() -> Vec<tokio::task::JoinHandle<()>> {
         consumer::Consumer::new(single_notify_job_10, 3),
         consumer::Consumer::new(batch_notify_job_1, 3),
         consumer::Consumer::new(batch_notify_job_2, 3),
+        consumer::Consumer::new(recurring_campaign_job, 3),
+        consumer::Consumer::new(delay_notify_job, 3),
+        consumer::Consumer::new(presence_heartbeat_job, 3),
+        consumer::Consumer::new(redeliver_notify_job, 3),
     ];
 
     consumers
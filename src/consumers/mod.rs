@@ -1,7 +1,14 @@
 pub mod batch_notify_job;
 pub mod consumer;
+pub mod delay_notify_job;
+pub mod delivery_progress;
 pub mod error;
 mod initializer;
+pub mod presence_heartbeat_job;
+pub mod quarantine;
+pub mod recurring_campaign_job;
+pub mod redeliver_notify_job;
+pub mod retry;
 pub mod send_request_handler;
 pub mod single_notify_job;
 
@@ -0,0 +1,99 @@
+use crate::consumers::consumer::Job;
+use crate::consumers::error::JobError;
+use crate::enums;
+use crate::notify_server::application::notify_handler;
+use crate::notify_server::{BACKSTAGE_NOTIFY_SERVER, FRONTEND_NOTIFY_SERVER};
+use kgs_tracing::{info, warn};
+use std::fmt::Debug;
+use tonic::async_trait;
+
+use super::error::ConsumerError;
+
+const HEARTBEAT_INTERVAL_SECS: u64 = 10; // 每10秒更新一次心跳 需小於redis中心跳與用戶位置的過期時間
+
+/// pod心跳排程 更新pod心跳與此pod上在線用戶的位置及在線狀態 並清除已關閉的連線
+#[derive(Debug)]
+pub struct PresenceHeartbeatJob {
+    job_name: String,
+}
+
+impl PresenceHeartbeatJob {
+    pub fn new(job_name: &str) -> Self {
+        PresenceHeartbeatJob {
+            job_name: job_name.to_string(),
+        }
+    }
+
+    fn heartbeat(&self) -> Result<(), JobError> {
+        notify_handler::save_pod_heartbeat()
+            .map_err(|err| JobError::Database(format!("更新pod心跳錯誤: {}", err)))
+    }
+}
+
+#[async_trait]
+impl Job for PresenceHeartbeatJob {
+    fn job_name(&self) -> &str {
+        &self.job_name
+    }
+
+    async fn start(&mut self) -> Result<(), JobError> {
+        info!("{} start", self.job_name.as_str());
+
+        // 啟動時立即送出心跳 讓其他pod可以轉發通知過來
+        self.heartbeat()
+    }
+
+    async fn update(&mut self) -> Result<(), JobError> {
+        tokio::time::sleep(std::time::Duration::from_secs(HEARTBEAT_INTERVAL_SECS)).await;
+
+        self.heartbeat()?;
+
+        // 清除已關閉的連線 並延長仍在線用戶的位置
+        let online_users = FRONTEND_NOTIFY_SERVER.refresh_sessions().await;
+        notify_handler::refresh_user_located_servers(&online_users)
+            .map_err(|err| JobError::Database(format!("更新在線用戶位置錯誤: {}", err)))?;
+
+        // 清除後台已關閉的連線 並更新仍在線的後台使用者
+        let (online_users, offline_users) = BACKSTAGE_NOTIFY_SERVER.connections.refresh_streams();
+        for user in offline_users {
+            if let Err(err) =
+                notify_handler::remove_online_user_from_redis(&enums::Platform::Backstage, &user)
+            {
+                warn!("remove online user {} failed: {}", user.user_id, err);
+            }
+        }
+        notify_handler::save_online_users_to_redis(&enums::Platform::Backstage, &online_users)
+            .map_err(|err| JobError::Database(format!("更新後台在線使用者錯誤: {}", err)))?;
+
+        Ok(())
+    }
+
+    async fn end(&mut self) -> Result<(), JobError> {
+        info!("{} end", self.job_name.as_str());
+        Ok(())
+    }
+
+    async fn error_handler(&mut self, err: ConsumerError) -> Result<(), JobError> {
+        warn!("{} error_handler {}", self.job_name.as_str(), err);
+
+        match err {
+            ConsumerError::StartStateError(err) => {
+                warn!("StartStateError {}", err);
+                self.start().await?;
+            }
+            ConsumerError::UpdateStateError(err) => {
+                // 心跳失敗時等待下一次心跳即可
+                warn!("UpdateStateError {}", err);
+            }
+            ConsumerError::EndStateError(err) => {
+                warn!("EndStateError {}", err);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_continue(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,70 @@
+use crate::consumers::error::JobError;
+use crate::consumers::retry;
+use crate::mq_manager::envelope::{self, EnvelopeMessage, MessageEnvelope};
+use crate::mq_manager::{MessageQueue, QueueMessage};
+use crate::repository;
+use kgs_err::models::status::Status as KgsStatus;
+use kgs_tracing::{tracing, warn};
+
+/// 解析queue中的訊息 無法解析的訊息存入隔離表後才ack
+/// 回傳None代表訊息已處理 隔離失敗時移至dead letter queue 避免訊息遺失也避免不斷重送
+#[tracing::instrument(skip(message_queue, message))]
+pub async fn decode_or_quarantine<T: EnvelopeMessage>(
+    message_queue: &dyn MessageQueue,
+    message: &QueueMessage,
+) -> Result<Option<MessageEnvelope<T>>, JobError> {
+    let decode_err = match envelope::decode::<T>(&message.data) {
+        Ok(message_envelope) => return Ok(Some(message_envelope)),
+        Err(err) => err,
+    };
+    warn!(" deserialize from binary 錯誤{}", decode_err);
+
+    if let Err(err) = quarantine_message(message, decode_err.to_string()).await {
+        let msg = format!("隔離無法解析的訊息錯誤: {}", err);
+        warn!("{}", msg);
+
+        // move to dead letter queue, requeue only if it fails
+        let err = JobError::Database(msg);
+        if let Err(err) = retry::dead_letter(message_queue, message, &err).await {
+            message_queue.nack(message, true).await.map_err(|err| {
+                warn!("nack錯誤 {}", err);
+                JobError::from(err)
+            })?;
+            return Err(err);
+        }
+    }
+
+    // ack
+    message_queue.ack(message).await.map_err(|err| {
+        warn!("ack錯誤 {}", err);
+        JobError::from(err)
+    })?;
+
+    Ok(None)
+}
+
+/// 將無法解析的訊息原封不動存入隔離表 之後可由後台檢查並重新發送
+#[tracing::instrument(skip(message))]
+pub async fn quarantine_message(
+    message: &QueueMessage,
+    error_message: String,
+) -> Result<(), KgsStatus> {
+    let db = database_manager::sea_orm::get_db();
+
+    let record = repository::mq_quarantine_record::insert(
+        &*db,
+        message.queue.as_str(),
+        message.data.clone(),
+        &message.headers,
+        error_message,
+    )
+    .await?;
+
+    warn!(
+        "無法解析的訊息已隔離 queue: {} quarantine_id: {}",
+        message.queue.as_str(),
+        record.id
+    );
+
+    Ok(())
+}
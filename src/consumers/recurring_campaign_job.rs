@@ -0,0 +1,185 @@
+use crate::consumers::consumer::Job;
+use crate::consumers::error::JobError;
+use crate::entity;
+use crate::notify_server::application::oauth_rpc;
+use crate::notify_server::controller::backstage_notify::BackstageNotifyServer;
+use crate::repository;
+use chrono::NaiveDateTime;
+use kgs_err::models::status::Status as KgsStatus;
+use kgs_tracing::{info, tracing, warn};
+use std::fmt::Debug;
+use tonic::async_trait;
+
+use super::error::ConsumerError;
+
+const SCHEDULE_INTERVAL_SECS: u64 = 30; // 每30秒掃描一次到期的活動
+
+/// 週期性發送活動排程 到期時為活動產生一筆backstage_send_task
+#[derive(Debug)]
+pub struct RecurringCampaignJob {
+    job_name: String,
+}
+
+impl RecurringCampaignJob {
+    pub fn new(job_name: &str) -> Self {
+        RecurringCampaignJob {
+            job_name: job_name.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Job for RecurringCampaignJob {
+    fn job_name(&self) -> &str {
+        &self.job_name
+    }
+
+    async fn start(&mut self) -> Result<(), JobError> {
+        info!("{} start", self.job_name.as_str());
+        Ok(())
+    }
+
+    async fn update(&mut self) -> Result<(), JobError> {
+        tokio::time::sleep(std::time::Duration::from_secs(SCHEDULE_INTERVAL_SECS)).await;
+
+        let db = database_manager::sea_orm::get_db();
+        let now = chrono::Utc::now().naive_utc();
+
+        // get due campaigns
+        let campaigns = repository::recurring_campaign::find_due_list(&*db, now)
+            .await
+            .map_err(|err| {
+                let msg = format!("獲取到期的週期性活動錯誤: {}", err);
+                warn!("{}", msg);
+                JobError::Database(msg)
+            })?;
+
+        // 單一活動失敗不影響其他活動 失敗的活動下次掃描時會重新執行
+        for campaign in campaigns {
+            let campaign_id = campaign.id;
+            if let Err(err) = materialize_occurrence(campaign, now).await {
+                warn!(
+                    "週期性活動產生任務錯誤 campaign_id: {} err: {}",
+                    campaign_id, err
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn end(&mut self) -> Result<(), JobError> {
+        info!("{} end", self.job_name.as_str());
+        Ok(())
+    }
+
+    async fn error_handler(&mut self, err: ConsumerError) -> Result<(), JobError> {
+        warn!("{} error_handler {}", self.job_name.as_str(), err);
+
+        match err {
+            ConsumerError::StartStateError(err) => {
+                warn!("StartStateError {}", err);
+                self.start().await?;
+            }
+            ConsumerError::UpdateStateError(err) => {
+                // 掃描失敗時等待下一次掃描即可
+                warn!("UpdateStateError {}", err);
+            }
+            ConsumerError::EndStateError(err) => {
+                warn!("EndStateError {}", err);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_continue(&self) -> bool {
+        true
+    }
+}
+
+/// 為到期的活動產生一筆發送任務
+#[tracing::instrument]
+async fn materialize_occurrence(
+    campaign: entity::recurring_campaign::Model,
+    now: NaiveDateTime,
+) -> Result<(), KgsStatus> {
+    let expected_next_run_at = match campaign.next_run_at {
+        Some(next_run_at) => next_run_at,
+        None => return Ok(()),
+    };
+
+    // 以目前時間計算下次執行時間 停機期間錯過的多次執行只會補發一次
+    let next_run_at = campaign.get_next_run_at(now)?;
+
+    // get receivers (在開啟transaction前呼叫rpc 避免外部服務緩慢時長時間佔用連線與鎖)
+    let frontend_client_id = oauth_rpc::get_frontend_client(campaign.client_id).await?;
+    let receivers = BackstageNotifyServer::get_receivers(
+        frontend_client_id,
+        campaign.is_all,
+        campaign.receiver_ids.clone(),
+        campaign.vip_levels.clone(),
+    )
+    .await?;
+
+    // get templates
+    let db = database_manager::sea_orm::get_db();
+    let templates =
+        repository::recurring_campaign_detail::find_list_by_campaign_id(&*db, campaign.id)
+            .await?
+            .into_iter()
+            .map(|detail| detail.to_publish_model(campaign.notify_level))
+            .collect();
+
+    // get transaction
+    let txn = database_manager::sea_orm::get_trans().await.map_err(|e| {
+        warn!("get db failed: {:?}", e);
+        KgsStatus::InternalServerError
+    })?;
+
+    // claim this occurrence, skip if another pod already took it
+    if !repository::recurring_campaign::claim_occurrence(
+        &txn,
+        campaign.id,
+        expected_next_run_at,
+        next_run_at,
+        now,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+
+    // insert backstage_send_task
+    let (task_id, mq_task) = BackstageNotifyServer::create_send_task(
+        &txn,
+        frontend_client_id,
+        campaign.client_id,
+        campaign.client_event_id,
+        campaign.sender_id,
+        campaign.sender_account.clone(),
+        campaign.sender_ip.clone(),
+        campaign.campaign_name.clone(),
+        campaign.notify_level,
+        &receivers,
+        templates,
+        Some(campaign.id),
+    )
+    .await?;
+
+    // commit
+    txn.commit().await.map_err(|e| {
+        warn!("commit failed: {:?}", e);
+        KgsStatus::InternalServerError
+    })?;
+
+    // push to notify_queue (commit之後才推送 避免rollback後仍發送沒有任務的通知)
+    BackstageNotifyServer::publish_send_task(&mq_task).await?;
+
+    info!(
+        "週期性活動產生任務 campaign_id: {} task_id: {}",
+        campaign.id, task_id
+    );
+
+    Ok(())
+}
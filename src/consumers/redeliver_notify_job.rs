@@ -0,0 +1,80 @@
+use crate::consumers::consumer::Job;
+use crate::consumers::error::JobError;
+use crate::notify_server::FRONTEND_NOTIFY_SERVER;
+use kgs_tracing::{info, warn};
+use std::fmt::Debug;
+use tonic::async_trait;
+
+use super::error::ConsumerError;
+
+const SCHEDULE_INTERVAL_SECS: u64 = 10; // 每10秒檢查一次未確認收到的通知
+
+/// 重新推送排程 推送後超時未確認收到的通知重新推送給用戶
+#[derive(Debug)]
+pub struct RedeliverNotifyJob {
+    job_name: String,
+}
+
+impl RedeliverNotifyJob {
+    pub fn new(job_name: &str) -> Self {
+        RedeliverNotifyJob {
+            job_name: job_name.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Job for RedeliverNotifyJob {
+    fn job_name(&self) -> &str {
+        &self.job_name
+    }
+
+    async fn start(&mut self) -> Result<(), JobError> {
+        info!("{} start", self.job_name.as_str());
+        Ok(())
+    }
+
+    async fn update(&mut self) -> Result<(), JobError> {
+        tokio::time::sleep(std::time::Duration::from_secs(SCHEDULE_INTERVAL_SECS)).await;
+
+        FRONTEND_NOTIFY_SERVER
+            .redeliver_unacked_notify()
+            .await
+            .map_err(|err| {
+                let msg = format!("重新推送未確認收到的通知錯誤: {}", err);
+                warn!("{}", msg);
+                JobError::Database(msg)
+            })?;
+
+        Ok(())
+    }
+
+    async fn end(&mut self) -> Result<(), JobError> {
+        info!("{} end", self.job_name.as_str());
+        Ok(())
+    }
+
+    async fn error_handler(&mut self, err: ConsumerError) -> Result<(), JobError> {
+        warn!("{} error_handler {}", self.job_name.as_str(), err);
+
+        match err {
+            ConsumerError::StartStateError(err) => {
+                warn!("StartStateError {}", err);
+                self.start().await?;
+            }
+            ConsumerError::UpdateStateError(err) => {
+                // 重新推送失敗時等待下一次檢查即可
+                warn!("UpdateStateError {}", err);
+            }
+            ConsumerError::EndStateError(err) => {
+                warn!("EndStateError {}", err);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_continue(&self) -> bool {
+        true
+    }
+}
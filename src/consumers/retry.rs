@@ -0,0 +1,91 @@
+use crate::consumers::error::JobError;
+use crate::mq_manager::{
+    MessageQueue, QueueMessage, QueueName, ERROR_CLASS_HEADER, ERROR_MESSAGE_HEADER,
+    ORIGINAL_QUEUE_HEADER, RETRY_COUNT_HEADER,
+};
+use kgs_tracing::{tracing, warn};
+use std::time::Duration;
+
+const MAX_RETRY_TIMES: u32 = 3; // 可重試錯誤的最大重試次數
+const RETRY_BASE_DELAY_SECS: u64 = 5; // 第一次重試前等待的秒數 之後每次加倍
+
+/// 依已重試的次數計算下次重試前的等待時間
+fn get_retry_delay(retry_count: u32) -> Duration {
+    Duration::from_secs(RETRY_BASE_DELAY_SECS << retry_count.min(MAX_RETRY_TIMES))
+}
+
+/// 將訊息延遲後重新放回原本的queue並累加重試次數
+/// 回傳false代表已超過重試次數 呼叫端應記錄失敗
+#[tracing::instrument(skip(message_queue, message))]
+pub async fn requeue(
+    message_queue: &dyn MessageQueue,
+    message: &QueueMessage,
+) -> Result<bool, JobError> {
+    let retry_count = message.retry_count();
+    if retry_count >= MAX_RETRY_TIMES {
+        warn!(
+            "訊息已超過重試次數 queue: {} retry_count: {}",
+            message.queue.as_str(),
+            retry_count
+        );
+        return Ok(false);
+    }
+
+    let mut headers = message.headers.clone();
+    headers.insert(
+        RETRY_COUNT_HEADER.to_string(),
+        (retry_count + 1).to_string(),
+    );
+    message_queue
+        .publish_delayed(
+            message.queue,
+            message.data.clone(),
+            headers,
+            get_retry_delay(retry_count),
+        )
+        .await?;
+
+    Ok(true)
+}
+
+/// 將超過重試次數的訊息移至dead letter queue 並保留原本的queue與錯誤原因
+#[tracing::instrument(skip(message_queue, message))]
+pub async fn dead_letter(
+    message_queue: &dyn MessageQueue,
+    message: &QueueMessage,
+    err: &JobError,
+) -> Result<(), JobError> {
+    let mut headers = message.headers.clone();
+    headers.insert(
+        ORIGINAL_QUEUE_HEADER.to_string(),
+        message.queue.as_str().to_string(),
+    );
+    headers.insert(ERROR_MESSAGE_HEADER.to_string(), err.to_string());
+    headers.insert(
+        ERROR_CLASS_HEADER.to_string(),
+        err.error_class().to_string(),
+    );
+    message_queue
+        .publish(QueueName::DeadLetter, message.data.clone(), headers)
+        .await?;
+
+    warn!(
+        "訊息已移至dead letter queue queue: {} retry_count: {}",
+        message.queue.as_str(),
+        message.retry_count()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_doubles() {
+        assert_eq!(get_retry_delay(0), Duration::from_secs(5));
+        assert_eq!(get_retry_delay(1), Duration::from_secs(10));
+        assert_eq!(get_retry_delay(2), Duration::from_secs(20));
+    }
+}
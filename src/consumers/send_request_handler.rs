@@ -49,16 +49,21 @@ pub async fn send_email(title: &str, content: &str, email: &str) -> Result<(), S
         .map_err(|err| {
             warn!("Failed to send email: {:?}", err);
             SendRequestError::new(
-                ErrorKind::ConnectionError,
+                ErrorKind::from_reqwest(&err),
                 err.to_string(),
                 Some(Box::new(err)),
             )
         })?;
 
-    if !response.status().is_success() {
+    let status = response.status();
+    if !status.is_success() {
         let msg = format!("Failed to send email: {:#?}", response.text().await);
         warn!(msg);
-        return Err(SendRequestError::new(ErrorKind::StatusError, msg, None));
+        return Err(SendRequestError::new(
+            ErrorKind::from_status(status),
+            msg,
+            None,
+        ));
     }
 
     Ok(())
@@ -86,17 +91,22 @@ pub async fn send_sms(content: &str, address: &str) -> Result<(), SendRequestErr
     let response = client.get(&full_url).send().await.map_err(|err| {
         warn!("Failed to send sms: {:?}", err);
         SendRequestError::new(
-            ErrorKind::ConnectionError,
+            ErrorKind::from_reqwest(&err),
             err.to_string(),
             Some(Box::new(err)),
         )
     })?;
 
     // Check if the request was successful
-    if !response.status().is_success() {
+    let status = response.status();
+    if !status.is_success() {
         let msg = format!("Failed to send sms: {:#?}", response.text().await);
         warn!(msg);
-        return Err(SendRequestError::new(ErrorKind::StatusError, msg, None));
+        return Err(SendRequestError::new(
+            ErrorKind::from_status(status),
+            msg,
+            None,
+        ));
     }
 
     Ok(())
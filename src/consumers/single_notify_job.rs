@@ -1,18 +1,21 @@
 use crate::consumers::consumer::Job;
+use crate::consumers::delivery_progress;
 use crate::consumers::error::JobError;
+use crate::consumers::quarantine;
+use crate::consumers::retry;
 use crate::consumers::send_request_handler;
 use crate::entity;
 use crate::enums;
 use crate::helper;
-use crate::mq_manager;
-use crate::mq_manager::SingleNotifyModel;
+use crate::mq_manager::envelope::EnvelopeMessage;
+use crate::mq_manager::{
+    trace_context, MessageQueue, QueueConsumer, QueueMessage, QueueName, SingleNotifyModel,
+};
 use crate::notify_server::application::user_rpc;
 use crate::notify_server::FRONTEND_NOTIFY_SERVER;
 use crate::repository;
-use futures::StreamExt;
-use kgs_tracing::tracing;
+use kgs_tracing::tracing::{self, Instrument};
 use kgs_tracing::{info, warn};
-use lapin::Consumer;
 use sea_orm::ActiveModelTrait;
 use sea_orm::ActiveValue::NotSet;
 use sea_orm::Set;
@@ -25,16 +28,20 @@ use super::error::ConsumerError;
 #[derive(Debug)]
 pub struct SingleNotifyJob {
     job_name: String,
-    rabbit_consumer: Option<Consumer>,
+    message_queue: Arc<dyn MessageQueue>,
+    queue_consumer: Option<QueueConsumer>,
     message: Option<Arc<SingleNotifyModel>>,
+    delivery: Option<QueueMessage>,
 }
 
 impl SingleNotifyJob {
-    pub fn new(job_name: &str) -> Self {
+    pub fn new(job_name: &str, message_queue: Arc<dyn MessageQueue>) -> Self {
         SingleNotifyJob {
             job_name: job_name.to_string(),
-            rabbit_consumer: None,
+            message_queue,
+            queue_consumer: None,
             message: None,
+            delivery: None,
         }
     }
 }
@@ -45,76 +52,181 @@ impl Job for SingleNotifyJob {
         &self.job_name
     }
 
-    async fn start(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    async fn start(&mut self) -> Result<(), JobError> {
         info!("{} start", self.job_name.as_str());
 
-        // get rabbit_mq consumer
-        match mq_manager::consume_single_notify(self.job_name()).await {
-            Ok(r) => self.rabbit_consumer = Some(r),
+        // get queue consumer
+        match self
+            .message_queue
+            .consume(QueueName::SingleNotify, self.job_name())
+            .await
+        {
+            Ok(r) => self.queue_consumer = Some(r),
             Err(err) => {
-                warn!("獲取rabbit_mq consumer錯誤 {}", err);
-                return Err(Box::new(err));
+                warn!("獲取queue consumer錯誤 {}", err);
+                return Err(err.into());
             }
         };
 
         Ok(())
     }
 
-    #[tracing::instrument]
-    async fn update(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    async fn update(&mut self) -> Result<(), JobError> {
         info!("{} update", self.job_name.as_str());
         // reset message
         if self.message.is_some() {
             self.message = None;
         }
+        self.delivery = None;
 
-        // early return if rabbit_consumer is none
-        if self.rabbit_consumer.as_ref().is_none() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "rabbit_consumer is none",
-            )));
+        // early return if queue_consumer is none
+        if self.queue_consumer.as_ref().is_none() {
+            return Err(JobError::Internal("queue_consumer is none".to_string()));
         }
 
-        // get delivery form rabbit_mq
-        let delivery = match self.rabbit_consumer.as_mut().unwrap().next().await {
+        // get delivery form queue
+        let delivery = match self.queue_consumer.as_mut().unwrap().next().await {
             Some(r) => r.map_err(|err| {
                 warn!("獲取Delivery錯誤 {}", err);
-                Box::new(err)
+                JobError::from(err)
             })?,
             None => {
                 return Ok(());
             } // if no message, wait for next message
         };
 
+        // parse SingleNotifyModel from delivery, 無法解析的訊息存入隔離表後略過
+        let message_envelope = match quarantine::decode_or_quarantine::<SingleNotifyModel>(
+            &*self.message_queue,
+            &delivery,
+        )
+        .await?
+        {
+            Some(message_envelope) => message_envelope,
+            None => return Ok(()),
+        };
+        info!(
+            "收到訊息 schema_version: {}, idempotency_key: {}",
+            message_envelope.schema_version, message_envelope.idempotency_key
+        );
+
         // ack first
-        mq_manager::rabbit_consumer_ack(&delivery)
+        self.message_queue.ack(&delivery).await.map_err(|err| {
+            warn!("ack錯誤 {}", err);
+            JobError::from(err)
+        })?;
+        self.delivery = Some(delivery.clone());
+
+        // 以producer帶來的trace context作為parent 串接同一條trace
+        let span = tracing::info_span!("handle_delivery", job_name = self.job_name.as_str());
+        trace_context::set_parent(&span, &delivery.headers);
+        self.handle_delivery(message_envelope.payload)
+            .instrument(span)
             .await
-            .map_err(|err| {
-                warn!("ack錯誤 {}", err);
-                Box::new(err)
-            })?;
+    }
+
+    async fn end(&mut self) -> Result<(), JobError> {
+        info!("{} end", self.job_name.as_str());
+        Ok(())
+    }
+
+    async fn error_handler(&mut self, err: ConsumerError) -> Result<(), JobError> {
+        warn!("{} error: message:{}", self.job_name.as_str(), err);
+        match err {
+            ConsumerError::StartStateError(err) => {
+                warn!("StartStateError {}", err);
+                // 如果在start階段發生錯誤 我選擇在重新執行一次 ,也可以直接return err
+                self.start().await?;
+            }
+            ConsumerError::UpdateStateError(err) => {
+                warn!("UpdateStateError {} class: {}", err, err.error_class());
+
+                // 可重試的錯誤放回queue 永久性錯誤或超過重試次數才記錄失敗
+                if let Some(delivery) = self.delivery.take() {
+                    if err.is_retryable() {
+                        if retry::requeue(&*self.message_queue, &delivery).await? {
+                            self.message = None;
+                            return Ok(());
+                        }
+
+                        // 超過重試次數 移至dead letter queue
+                        retry::dead_letter(&*self.message_queue, &delivery, &err).await?;
+                    }
+                    self.delivery = Some(delivery);
+                }
+
+                // get trans from database_manager
+                let trans = database_manager::sea_orm::get_trans()
+                    .await
+                    .map_err(|err| {
+                        warn!("獲取db trans錯誤 {}", err);
+                        JobError::from(err)
+                    })?;
+
+                // get received_model from self.message
+                if let Some(received_model_arc) = &self.message {
+                    let received_model = Arc::clone(&received_model_arc);
+                    let retry_count = self
+                        .delivery
+                        .as_ref()
+                        .map(|delivery| delivery.retry_count())
+                        .unwrap_or(0);
+
+                    // insert failed message to database
+                    repository::mq_failed_record::create(
+                        &trans,
+                        Some(received_model.notify_id),
+                        Some(received_model.client_id),
+                        Some(received_model.user_id),
+                        Some(received_model.sender_id),
+                        Some(received_model.title.clone()),
+                        Some(received_model.notify_type.clone()),
+                        Some(received_model.content.clone()),
+                        Some(err.to_string()),
+                        Some(err.error_class().to_string()),
+                        retry_count as i32,
+                    )
+                    .await
+                    .map_err(|err| {
+                        warn!("插入records錯誤 {}", err);
+                        JobError::from(err)
+                    })?;
+
+                    // clear self.message
+                    self.message = None;
+                    self.delivery = None;
+
+                    // commit
+                    trans.commit().await.map_err(|err| {
+                        warn!("commit錯誤 {}", err);
+                        JobError::from(err)
+                    })?;
+                }
+            }
+            ConsumerError::EndStateError(err) => {
+                warn!("EndStateError {}", err);
+            }
+        }
 
+        Ok(())
+    }
+
+    fn is_continue(&self) -> bool {
+        true
+    }
+}
+
+impl SingleNotifyJob {
+    /// 處理從queue收到的訊息
+    async fn handle_delivery(&mut self, received_model: SingleNotifyModel) -> Result<(), JobError> {
         // get trans from database_manager
         let trans = database_manager::sea_orm::get_trans()
             .await
             .map_err(|err| {
                 warn!("獲取db trans錯誤 {}", err);
-                Box::new(err)
+                JobError::from(err)
             })?;
 
-        // parse ConsumerModelNotify from delivery
-        let received_model: SingleNotifyModel = match std::str::from_utf8(&delivery.data) {
-            Ok(msg_str) => serde_json::from_str(msg_str).map_err(|err| {
-                warn!(" deserialize from binary 錯誤{}", err);
-                Box::new(err)
-            })?,
-            Err(err) => {
-                warn!("binary轉成str錯誤 {} ", err);
-                return Err(Box::new(err));
-            }
-        };
-
         // stone received_model to self.message
         let received_model_arc = Arc::new(received_model);
         self.message = Some(Arc::clone(&received_model_arc));
@@ -123,13 +235,13 @@ impl Job for SingleNotifyJob {
         let user_profile =
             user_rpc::get_user_profile(received_model_arc.client_id, received_model_arc.user_id)
                 .await
-                .map_err(|_err| {
+                .map_err(|err| {
                     let msg = format!(
                         "獲取user_profile錯誤: client_id: {}, user_id: {}",
                         received_model_arc.client_id, received_model_arc.user_id
                     );
                     warn!("{}", msg);
-                    Box::new(JobError::new(msg, None))
+                    JobError::rpc(msg, err)
                 })?;
 
         // replace the template
@@ -141,38 +253,54 @@ impl Job for SingleNotifyJob {
                 &received_model_arc.key_map,
             );
 
-        let notify_id = helper::generate_snowflake_id().await;
+        // 重試時若已發送過 沿用當時的notify_id並略過發送 避免重複發送
+        let idempotency_key = received_model_arc.idempotency_key();
+        let sent_notify_id = delivery_progress::get_sent_notify_id(&idempotency_key)?;
+        let notify_id = match sent_notify_id {
+            Some(notify_id) => notify_id,
+            None => helper::generate_snowflake_id().await,
+        };
 
-        // send message
-        match received_model_arc.notify_type {
-            enums::NotifyType::Email => {
-                send_request_handler::send_email(
-                    &title,
-                    &content,
-                    &received_model_arc.receive_address,
-                )
-                .await?;
-            }
-            enums::NotifyType::SMS => {
-                send_request_handler::send_sms(&content, &received_model_arc.receive_address)
-                    .await?;
-            }
-            enums::NotifyType::InApp => {
-                if let Err(e) = FRONTEND_NOTIFY_SERVER
-                    .send_message_in_app(
-                        received_model_arc.client_id,
-                        received_model_arc.user_id,
-                        notify_id,
-                        received_model_arc.notify_level,
+        // 過期的通知不發送 只記錄為過期
+        let is_expired = received_model_arc.is_expired();
+        if is_expired {
+            warn!(
+                "通知已過期不發送 notify_id: {}, expires_at: {:?}",
+                received_model_arc.notify_id, received_model_arc.expires_at
+            );
+        } else if sent_notify_id.is_some() {
+            info!("通知已發送過 略過發送 idempotency_key: {}", idempotency_key);
+        } else {
+            // send message
+            match received_model_arc.notify_type {
+                enums::NotifyType::Email => {
+                    send_request_handler::send_email(
                         &title,
                         &content,
+                        &received_model_arc.receive_address,
                     )
-                    .await
+                    .await?;
+                }
+                enums::NotifyType::SMS => {
+                    send_request_handler::send_sms(&content, &received_model_arc.receive_address)
+                        .await?;
+                }
+                // 站內信在notify_record寫入後才推送 裝置確認收到時才找得到紀錄
+                enums::NotifyType::InApp => {}
+            }
+
+            // 記錄失敗時仍繼續寫入notify_record 只有寫入也失敗重試時才會重複發送
+            if received_model_arc.notify_type != enums::NotifyType::InApp {
+                if let Err(err) =
+                    delivery_progress::save_sent_notify_id(&idempotency_key, notify_id)
                 {
-                    warn!("send_message_in_app錯誤 {}", e);
+                    warn!("記錄已發送錯誤 {}", err);
                 }
             }
         }
+        let is_push_in_app = !is_expired
+            && sent_notify_id.is_none()
+            && received_model_arc.notify_type == enums::NotifyType::InApp;
 
         // insert notify_record to database
         entity::notify_record::ActiveModel {
@@ -184,11 +312,19 @@ impl Job for SingleNotifyJob {
             sender_id: Set(received_model_arc.sender_id),
             sender_account: Set(received_model_arc.sender_account.clone()),
             sender_ip: Set(received_model_arc.sender_ip.clone()),
-            title: Set(title),
-            content: Set(content),
+            title: Set(title.clone()),
+            content: Set(content.clone()),
             notify_type: Set(received_model_arc.notify_type),
             notify_level: Set(received_model_arc.notify_level),
-            notify_status: Set(enums::NotifyStatus::Unread),
+            notify_status: Set(if is_expired {
+                enums::NotifyStatus::Expired
+            } else {
+                enums::NotifyStatus::Unread
+            }),
+            expires_at: Set(received_model_arc
+                .expires_at
+                .and_then(chrono::DateTime::from_timestamp_millis)
+                .map(|date_time| date_time.naive_utc())),
             create_at: NotSet,
             update_at: NotSet,
         }
@@ -196,103 +332,51 @@ impl Job for SingleNotifyJob {
         .await
         .map_err(|err| {
             warn!("插入records錯誤 {}", err);
-            Box::new(err)
+            JobError::from(err)
         })?;
 
         // insert success message to database
-        repository::mq_success_record::create(
-            &trans,
-            received_model_arc.notify_id,
-            received_model_arc.client_id,
-            received_model_arc.user_id,
-            received_model_arc.sender_id,
-            &received_model_arc.title.clone(),
-            &received_model_arc.content.clone(),
-            &received_model_arc.notify_type.clone(),
-        )
-        .await
-        .map_err(|err| {
-            warn!("插入records錯誤 {}", err);
-            Box::new(err)
-        })?;
+        if !is_expired {
+            repository::mq_success_record::create(
+                &trans,
+                received_model_arc.notify_id,
+                received_model_arc.client_id,
+                received_model_arc.user_id,
+                received_model_arc.sender_id,
+                &received_model_arc.title.clone(),
+                &received_model_arc.content.clone(),
+                &received_model_arc.notify_type.clone(),
+            )
+            .await
+            .map_err(|err| {
+                warn!("插入records錯誤 {}", err);
+                JobError::from(err)
+            })?;
+        }
 
         // commit
         trans.commit().await.map_err(|err| {
             warn!("commit錯誤 {}", err);
-            Box::new(err)
+            JobError::from(err)
         })?;
 
-        Ok(())
-    }
-
-    async fn end(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        info!("{} end", self.job_name.as_str());
-        Ok(())
-    }
-
-    async fn error_handler(
-        &mut self,
-        err: ConsumerError,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        warn!("{} error: message:{}", self.job_name.as_str(), err);
-        match err {
-            ConsumerError::StartStateError(err) => {
-                warn!("StartStateError {}", err);
-                // 如果在start階段發生錯誤 我選擇在重新執行一次 ,也可以直接return err
-                self.start().await?;
-            }
-            ConsumerError::UpdateStateError(err) => {
-                warn!("UpdateStateError {}", err);
-
-                // get trans from database_manager
-                let trans = database_manager::sea_orm::get_trans()
-                    .await
-                    .map_err(|err| {
-                        warn!("獲取db trans錯誤 {}", err);
-                        Box::new(err)
-                    })?;
-
-                // get received_model from self.message
-                if let Some(received_model_arc) = &self.message {
-                    let received_model = Arc::clone(&received_model_arc);
-
-                    // insert failed message to database
-                    repository::mq_failed_record::create(
-                        &trans,
-                        Some(received_model.notify_id),
-                        Some(received_model.client_id),
-                        Some(received_model.user_id),
-                        Some(received_model.sender_id),
-                        Some(received_model.title.clone()),
-                        Some(received_model.notify_type.clone()),
-                        Some(received_model.content.clone()),
-                        Some(err.to_string()),
-                    )
-                    .await
-                    .map_err(|err| {
-                        warn!("插入records錯誤 {}", err);
-                        Box::new(err)
-                    })?;
-
-                    // clear self.message
-                    self.message = None;
-
-                    // commit
-                    trans.commit().await.map_err(|err| {
-                        warn!("commit錯誤 {}", err);
-                        Box::new(err)
-                    })?;
-                }
-            }
-            ConsumerError::EndStateError(err) => {
-                warn!("EndStateError {}", err);
+        // push in-app notify after commit
+        if is_push_in_app {
+            if let Err(e) = FRONTEND_NOTIFY_SERVER
+                .send_message_in_app(
+                    received_model_arc.client_id,
+                    received_model_arc.user_id,
+                    notify_id,
+                    received_model_arc.notify_level,
+                    &title,
+                    &content,
+                )
+                .await
+            {
+                warn!("send_message_in_app錯誤 {}", e);
             }
         }
 
         Ok(())
     }
-
-    fn is_continue(&self) -> bool {
-        true
-    }
 }
@@ -7,20 +7,21 @@ use sea_orm::entity::prelude::*;
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: i64,
-    pub client_id: i64,                   // 發送目標的client id(前台client id)
-    pub client_event_id: i64,             // client的事件id
-    pub sender_id: i64,                   // 通知發送者id
-    pub sender_account: String,           // 通知發送者帳號
-    pub sender_ip: Option<String>,        // 通知發送者ip
-    pub receiver_count: i32,              // 通知對象數量
-    pub receiver_account: Vec<String>,    // 通知對象帳號
-    pub receiver_id: Vec<i64>,            // 通知對象id
-    pub task_name: String,                // 任務名稱
-    pub notify_level: enums::NotifyLevel, // 通知等級 1.一般 2.系統 3.重要
-    pub task_status: enums::TaskStatus,   // 任務狀態 1.待處理 2.成功 3.失敗
-    pub error_message: Option<String>,    // 錯誤訊息
-    pub create_at: NaiveDateTime,         // 建立時間
-    pub update_at: NaiveDateTime,         // 更新時間
+    pub client_id: i64,                     // 發送目標的client id(前台client id)
+    pub client_event_id: i64,               // client的事件id
+    pub sender_id: i64,                     // 通知發送者id
+    pub sender_account: String,             // 通知發送者帳號
+    pub sender_ip: Option<String>,          // 通知發送者ip
+    pub receiver_count: i32,                // 通知對象數量
+    pub receiver_account: Vec<String>,      // 通知對象帳號
+    pub receiver_id: Vec<i64>,              // 通知對象id
+    pub task_name: String,                  // 任務名稱
+    pub notify_level: enums::NotifyLevel,   // 通知等級 1.一般 2.系統 3.重要
+    pub task_status: enums::TaskStatus,     // 任務狀態 1.待處理 2.成功 3.失敗
+    pub error_message: Option<String>,      // 錯誤訊息
+    pub recurring_campaign_id: Option<i64>, // 週期性發送活動id 由排程產生的任務才有值
+    pub create_at: NaiveDateTime,           // 建立時間
+    pub update_at: NaiveDateTime,           // 更新時間
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
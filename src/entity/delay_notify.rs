@@ -0,0 +1,33 @@
+use crate::enums;
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "delay_notify")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: i64,
+    pub client_id: i64,                         // 前台client id
+    pub user_id: i64,                           // 通知對象id
+    pub notify_event: enums::NotifyEvent,       // 通知事件
+    pub delay_key: Option<String>,              // 呼叫端自定義的key 用於取消通知
+    pub key_map: Json,                          // 模板替換參數
+    pub send_at: NaiveDateTime,                 // 預計發送時間
+    pub delay_status: enums::DelayNotifyStatus, // 狀態 1.待發送 2.已發送 3.已取消 4.發送失敗
+    pub error_message: Option<String>,          // 錯誤訊息
+    pub expires_at: Option<NaiveDateTime>,      // 通知過期時間
+    pub create_at: NaiveDateTime,               // 建立時間
+    pub update_at: NaiveDateTime,               // 更新時間
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    pub fn get_key_map(&self) -> HashMap<String, String> {
+        serde_json::from_value(self.key_map.clone()).unwrap_or_default()
+    }
+}
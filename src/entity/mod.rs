@@ -2,8 +2,11 @@ pub mod backstage_send_task;
 pub mod backstage_send_task_detail;
 pub mod client_notify_event;
 pub mod client_notify_template;
+pub mod delay_notify;
+pub mod delay_notify_status;
 pub mod language;
 pub mod mq_failed_record;
+pub mod mq_quarantine_record;
 pub mod mq_success_record;
 pub mod notify_event;
 pub mod notify_level;
@@ -12,4 +15,6 @@ pub mod notify_status;
 pub mod notify_template;
 pub mod notify_type;
 pub mod platform;
+pub mod recurring_campaign;
+pub mod recurring_campaign_detail;
 pub mod task_status;
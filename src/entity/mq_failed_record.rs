@@ -15,6 +15,8 @@ pub struct Model {
     pub title: Option<String>,
     pub content: Option<String>,
     pub error_message: Option<String>,
+    pub error_class: Option<String>,
+    pub retry_count: i32,
     pub create_at: NaiveDateTime,
 }
 
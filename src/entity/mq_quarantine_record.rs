@@ -0,0 +1,42 @@
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "mq_quarantine_record")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: i64,
+    pub queue_name: String,                 // 來源queue
+    pub payload: Vec<u8>,                   // 原始訊息內容
+    pub headers: Json,                      // 原始訊息headers
+    pub error_message: String,              // 解析錯誤訊息
+    pub replay_count: i32,                  // 重新發送次數
+    pub replayed_at: Option<NaiveDateTime>, // 最後重新發送時間
+    pub create_at: NaiveDateTime,           // 建立時間
+    pub update_at: NaiveDateTime,           // 更新時間
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    pub fn get_headers(&self) -> HashMap<String, String> {
+        serde_json::from_value(self.headers.clone()).unwrap_or_default()
+    }
+
+    pub fn to_proto(self) -> protos::backstage_notify::QuarantineRecord {
+        protos::backstage_notify::QuarantineRecord {
+            id: self.id,
+            headers: self.get_headers(),
+            queue_name: self.queue_name,
+            payload: String::from_utf8_lossy(&self.payload).to_string(),
+            error_message: self.error_message,
+            replay_count: self.replay_count,
+            replayed_at: self.replayed_at.map(|v| v.and_utc().timestamp_millis()),
+            create_at: self.create_at.and_utc().timestamp_millis(),
+        }
+    }
+}
@@ -19,6 +19,7 @@ pub struct Model {
     pub notify_status: enums::NotifyStatus, // 通知狀態 1.未讀 2.已讀 3.已刪除
     pub title: String,                      // 通知標題
     pub content: String,                    // 通知內容
+    pub expires_at: Option<NaiveDateTime>,  // 過期時間 None為不過期
     pub create_at: NaiveDateTime,           // 通知建立時間
     pub update_at: NaiveDateTime,           // 更新時間
 }
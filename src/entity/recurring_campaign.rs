@@ -0,0 +1,104 @@
+use crate::{enums, helper};
+use chrono::NaiveDateTime;
+use kgs_err::models::status::Status as KgsStatus;
+use sea_orm::entity::prelude::*;
+
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "recurring_campaign")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: i64,
+    pub client_id: i64,                     // 建立活動的client id(後台client id)
+    pub client_event_id: i64,               // client的事件id
+    pub sender_id: i64,                     // 建立者id
+    pub sender_account: String,             // 建立者帳號
+    pub sender_ip: Option<String>,          // 建立者ip
+    pub campaign_name: String,              // 活動名稱
+    pub cron_expression: String,            // cron表達式
+    pub timezone: String,                   // cron表達式所使用的時區 ex: Asia/Tokyo
+    pub notify_level: enums::NotifyLevel,   // 通知等級 1.一般 2.系統 3.重要
+    pub is_all: bool,                       // 是否發送給全部用戶
+    pub receiver_ids: Vec<i64>,             // 指定接收者id
+    pub vip_levels: Vec<i64>,               // 指定vip等級
+    pub is_active: bool,                    // 是否啟用
+    pub start_at: Option<NaiveDateTime>,    // 活動開始時間
+    pub end_at: Option<NaiveDateTime>,      // 活動結束時間
+    pub next_run_at: Option<NaiveDateTime>, // 下次執行時間 None表示已無下次執行
+    pub last_run_at: Option<NaiveDateTime>, // 上次執行時間
+    pub create_at: NaiveDateTime,           // 建立時間
+    pub update_at: NaiveDateTime,           // 更新時間
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    /// 計算 after 之後的下次執行時間, 超出活動期間則回傳None
+    pub fn get_next_run_at(
+        &self,
+        after: NaiveDateTime,
+    ) -> Result<Option<NaiveDateTime>, KgsStatus> {
+        get_next_run_at(
+            &self.cron_expression,
+            &self.timezone,
+            self.start_at,
+            self.end_at,
+            after,
+        )
+    }
+}
+
+/// 計算活動期間內 after 之後的下次執行時間
+pub fn get_next_run_at(
+    cron_expression: &str,
+    timezone: &str,
+    start_at: Option<NaiveDateTime>,
+    end_at: Option<NaiveDateTime>,
+    after: NaiveDateTime,
+) -> Result<Option<NaiveDateTime>, KgsStatus> {
+    // 活動尚未開始 從開始時間起算 剛好在開始時間的執行也要發送
+    let next_run_at = match start_at {
+        Some(start_at) if start_at > after => {
+            helper::cron::get_first_run_at(cron_expression, timezone, start_at)?
+        }
+        _ => helper::cron::get_next_run_at(cron_expression, timezone, after)?,
+    };
+
+    Ok(next_run_at.filter(|next_run_at| end_at.map_or(true, |end_at| *next_run_at <= end_at)))
+}
+
+impl Model {
+    pub fn to_proto<T: ProtoTrait>(self) -> T {
+        T::to_proto(self)
+    }
+}
+
+pub trait ProtoTrait {
+    fn to_proto(model: Model) -> Self;
+}
+
+impl ProtoTrait for protos::backstage_notify::RecurringCampaign {
+    fn to_proto(model: Model) -> Self {
+        protos::backstage_notify::RecurringCampaign {
+            campaign_id: model.id,
+            campaign_name: model.campaign_name,
+            client_event_id: model.client_event_id,
+            sender_account: model.sender_account,
+            cron_expression: model.cron_expression,
+            timezone: model.timezone,
+            notify_level: model.notify_level as i32,
+            is_all: model.is_all,
+            receiver_ids: model.receiver_ids,
+            vip_levels: model.vip_levels,
+            is_active: model.is_active,
+            start_at: model.start_at.map(|v| v.and_utc().timestamp_millis()),
+            end_at: model.end_at.map(|v| v.and_utc().timestamp_millis()),
+            next_run_at: model.next_run_at.map(|v| v.and_utc().timestamp_millis()),
+            last_run_at: model.last_run_at.map(|v| v.and_utc().timestamp_millis()),
+            create_at: model.create_at.and_utc().timestamp_millis(),
+            update_at: model.update_at.and_utc().timestamp_millis(),
+        }
+    }
+}
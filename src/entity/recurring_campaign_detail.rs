@@ -0,0 +1,53 @@
+use crate::enums;
+use sea_orm::entity::prelude::*;
+
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "recurring_campaign_detail")]
+
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: i64,
+    pub recurring_campaign_id: i64,     // 活動id
+    pub notify_type: enums::NotifyType, // 通知管道 1.站內信 2.信箱 3.簡訊
+    pub title: String,                  // 模板標題
+    pub content: String,                // 模板內容
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    pub fn to_publish_model(
+        self,
+        notify_level: enums::NotifyLevel,
+    ) -> crate::mq_manager::TemplateModel {
+        crate::mq_manager::TemplateModel {
+            notify_type: self.notify_type,
+            notify_level,
+            title: self.title,
+            content: self.content,
+        }
+    }
+}
+
+impl Model {
+    pub fn to_proto<T: ProtoTrait>(self) -> T {
+        T::to_proto(self)
+    }
+}
+
+pub trait ProtoTrait {
+    fn to_proto(model: Model) -> Self;
+}
+
+impl ProtoTrait for protos::backstage_notify::NotifyTaskDetail {
+    fn to_proto(model: Model) -> Self {
+        protos::backstage_notify::NotifyTaskDetail {
+            notify_type: model.notify_type as i32,
+            title: model.title,
+            content: model.content,
+        }
+    }
+}
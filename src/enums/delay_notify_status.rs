@@ -0,0 +1,52 @@
+use kgs_err::models::status::Status as KgsStatus;
+use sea_orm::{entity::prelude::*, strum::Display};
+
+/// 延遲通知狀態
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Display)]
+#[sea_orm(rs_type = "i32", db_type = "Integer")]
+pub enum DelayNotifyStatus {
+    #[strum(to_string = "Pending")]
+    Pending = 1, // 待發送
+    #[strum(to_string = "Sent")]
+    Sent = 2, // 已發送
+    #[strum(to_string = "Cancel")]
+    Cancel = 3, // 已取消
+    #[strum(to_string = "Fail")]
+    Fail = 4, // 發送失敗
+}
+
+impl From<DelayNotifyStatus> for i32 {
+    fn from(status: DelayNotifyStatus) -> Self {
+        status as i32
+    }
+}
+
+impl TryFrom<i32> for DelayNotifyStatus {
+    type Error = KgsStatus;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(DelayNotifyStatus::Pending),
+            2 => Ok(DelayNotifyStatus::Sent),
+            3 => Ok(DelayNotifyStatus::Cancel),
+            4 => Ok(DelayNotifyStatus::Fail),
+            _ => Err(KgsStatus::InvalidArgument),
+        }
+    }
+}
+
+impl DelayNotifyStatus {
+    pub fn to_id(&self) -> i32 {
+        self.clone().into()
+    }
+
+    pub fn get_comment(&self) -> String {
+        match self {
+            DelayNotifyStatus::Pending => "待發送",
+            DelayNotifyStatus::Sent => "已發送",
+            DelayNotifyStatus::Cancel => "已取消",
+            DelayNotifyStatus::Fail => "發送失敗",
+        }
+        .to_string()
+    }
+}
@@ -1,4 +1,5 @@
 mod client_notify_template_common_key;
+mod delay_notify_status;
 mod language;
 mod notify_event;
 mod notify_level;
@@ -8,6 +9,7 @@ pub mod platform;
 mod task_status;
 
 pub use client_notify_template_common_key::CommonKey;
+pub use delay_notify_status::DelayNotifyStatus;
 pub use language::Language;
 pub use notify_event::NotifyEvent;
 pub use notify_level::NotifyLevel;
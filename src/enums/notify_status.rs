@@ -11,6 +11,10 @@ pub enum NotifyStatus {
     Read = 2, // 已讀
     #[strum(to_string = "Delete")]
     Delete = 3, // 已刪除
+    #[strum(to_string = "Expired")]
+    Expired = 4, // 已過期
+    #[strum(to_string = "Delivered")]
+    Delivered = 5, // 已送達裝置 尚未讀取
 }
 
 impl From<NotifyStatus> for i32 {
@@ -27,6 +31,8 @@ impl TryFrom<i32> for NotifyStatus {
             1 => Ok(NotifyStatus::Unread),
             2 => Ok(NotifyStatus::Read),
             3 => Ok(NotifyStatus::Delete),
+            4 => Ok(NotifyStatus::Expired),
+            5 => Ok(NotifyStatus::Delivered),
             _ => Err(KgsStatus::InvalidArgument),
         }
     }
@@ -42,6 +48,8 @@ impl NotifyStatus {
             Self::Unread => "未讀",
             Self::Read => "已讀",
             Self::Delete => "刪除",
+            Self::Expired => "過期",
+            Self::Delivered => "已送達",
         }
         .to_string()
     }
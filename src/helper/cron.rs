@@ -0,0 +1,155 @@
+use chrono::{NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use cron::Schedule;
+use kgs_err::models::status::Status as KgsStatus;
+use kgs_tracing::{tracing, warn};
+use std::str::FromStr;
+
+/// 解析cron表達式 支援5欄位(分 時 日 月 週)與含秒數的6/7欄位格式
+#[tracing::instrument]
+pub fn parse_schedule(cron_expression: &str) -> Result<Schedule, KgsStatus> {
+    let cron_expression = cron_expression.trim();
+    let expression = if cron_expression.split_whitespace().count() == 5 {
+        format!("0 {}", cron_expression) // 補上秒數欄位
+    } else {
+        cron_expression.to_string()
+    };
+
+    Schedule::from_str(&expression).map_err(|err| {
+        warn!("invalid cron expression {}: {}", cron_expression, err);
+        KgsStatus::InvalidArgument
+    })
+}
+
+/// 解析時區 ex: Asia/Tokyo
+#[tracing::instrument]
+pub fn parse_timezone(timezone: &str) -> Result<Tz, KgsStatus> {
+    timezone.parse::<Tz>().map_err(|err| {
+        warn!("invalid timezone {}: {}", timezone, err);
+        KgsStatus::InvalidArgument
+    })
+}
+
+/// 以指定時區計算 after(UTC) 之後的下次執行時間(UTC) 不含after本身
+#[tracing::instrument]
+pub fn get_next_run_at(
+    cron_expression: &str,
+    timezone: &str,
+    after: NaiveDateTime,
+) -> Result<Option<NaiveDateTime>, KgsStatus> {
+    let schedule = parse_schedule(cron_expression)?;
+    let tz = parse_timezone(timezone)?;
+
+    let after = tz.from_utc_datetime(&after);
+    Ok(schedule
+        .after(&after)
+        .next()
+        .map(|next_run_at| next_run_at.naive_utc()))
+}
+
+/// 以指定時區計算 from(UTC) 起的第一次執行時間(UTC) 剛好在from的執行時間也算在內
+#[tracing::instrument]
+pub fn get_first_run_at(
+    cron_expression: &str,
+    timezone: &str,
+    from: NaiveDateTime,
+) -> Result<Option<NaiveDateTime>, KgsStatus> {
+    let schedule = parse_schedule(cron_expression)?;
+    let tz = parse_timezone(timezone)?;
+
+    // cron的最小單位為秒 往前一秒查詢後再排除早於from的時間
+    let after = tz.from_utc_datetime(&(from - chrono::Duration::seconds(1)));
+    Ok(schedule
+        .after(&after)
+        .map(|run_at| run_at.naive_utc())
+        .find(|run_at| *run_at >= from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, min, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn parse_five_and_six_field_expressions() {
+        assert!(parse_schedule("0 9 * * *").is_ok());
+        assert!(parse_schedule("30 0 9 * * *").is_ok());
+        assert!(matches!(
+            parse_schedule("invalid"),
+            Err(KgsStatus::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    fn parse_invalid_timezone() {
+        assert!(parse_timezone("Asia/Taipei").is_ok());
+        assert!(matches!(
+            parse_timezone("Invalid/Zone"),
+            Err(KgsStatus::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    fn next_run_at_uses_timezone() {
+        // 09:00 in Taipei is 01:00 UTC
+        let next_run_at = get_next_run_at("0 9 * * *", "Asia/Taipei", utc(2024, 1, 1, 0, 0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(next_run_at, utc(2024, 1, 1, 1, 0));
+
+        let next_run_at = get_next_run_at("0 9 * * *", "Asia/Taipei", utc(2024, 1, 1, 2, 0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(next_run_at, utc(2024, 1, 2, 1, 0));
+    }
+
+    #[test]
+    fn next_run_at_excludes_after() {
+        let next_run_at = get_next_run_at("0 9 * * *", "UTC", utc(2024, 1, 1, 9, 0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(next_run_at, utc(2024, 1, 2, 9, 0));
+    }
+
+    #[test]
+    fn next_run_at_follows_daylight_saving_time() {
+        // New York switches to daylight saving time on 2024-03-10
+        let next_run_at = get_next_run_at("0 9 * * *", "America/New_York", utc(2024, 3, 9, 15, 0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(next_run_at, utc(2024, 3, 10, 13, 0));
+    }
+
+    #[test]
+    fn first_run_at_includes_from() {
+        let first_run_at = get_first_run_at("0 9 * * *", "UTC", utc(2024, 1, 1, 9, 0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(first_run_at, utc(2024, 1, 1, 9, 0));
+
+        let first_run_at = get_first_run_at("0 9 * * *", "Asia/Taipei", utc(2024, 1, 1, 1, 0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(first_run_at, utc(2024, 1, 1, 1, 0));
+    }
+
+    #[test]
+    fn first_run_at_skips_earlier_runs() {
+        let from = utc(2024, 1, 1, 9, 0) + chrono::Duration::milliseconds(500);
+        let first_run_at = get_first_run_at("0 9 * * *", "UTC", from).unwrap().unwrap();
+        assert_eq!(first_run_at, utc(2024, 1, 2, 9, 0));
+
+        // the run at from itself is returned rather than the next second
+        let first_run_at = get_first_run_at("* * * * * *", "UTC", utc(2024, 1, 1, 9, 0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(first_run_at, utc(2024, 1, 1, 9, 0));
+    }
+}
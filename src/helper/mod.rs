@@ -1,3 +1,4 @@
+pub mod cron;
 pub mod snowflake;
 
 pub use snowflake::generate_id as generate_snowflake_id;
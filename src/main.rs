@@ -1,5 +1,5 @@
 use database_manager::redis;
-use kgs_tracing::{info, tracing};
+use kgs_tracing::{info, tracing, warn};
 use sea_orm_migration::MigratorTrait;
 mod config;
 mod consumers;
@@ -18,9 +18,43 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     init_redis();
     init_rabbit_mq().await;
 
-    consumers::start();
+    // 訂閱其他pod轉發的站內信
+    if notify_server::application::notify_bus::is_enabled() {
+        notify_server::application::notify_bus::start();
+    }
+
+    consumers::start(mq_manager::get_message_queue());
+
+    // 供瀏覽器使用的websocket與sse
+    if config::config::get_gateway().gateway_enabled {
+        notify_server::gateway::check_auth_mode()?;
+        notify_server::gateway::start();
+    }
+
+    // pod之間轉發使用的內部grpc服務 未使用redis pub/sub時必須設定共用密鑰 否則無法轉發到其他pod
+    let internal_enabled = notify_server::application::auth::is_internal_enabled();
+    if !internal_enabled {
+        if !notify_server::application::notify_bus::is_enabled() {
+            return Err("internal_secret is required when notify bus is disabled".into());
+        }
+        warn!("internal secret is not configured, internal grpc server disabled");
+    }
+    let internal_server = async {
+        if internal_enabled {
+            internal_grpc_server().await
+        } else {
+            std::future::pending().await
+        }
+    };
 
-    notify_grpc_server().await?;
+    // 內部服務停止後無法再轉發到其他pod 視為服務異常結束
+    tokio::select! {
+        result = notify_grpc_server() => result?,
+        result = internal_server => {
+            result?;
+            return Err("internal grpc server stopped".into());
+        }
+    }
 
     tokio::signal::ctrl_c().await.unwrap();
     Ok(())
@@ -48,6 +82,15 @@ fn init_redis() {
 #[tracing::instrument]
 async fn init_rabbit_mq() {
     let config = config::config::get_rabbit();
+    mq_manager::envelope::set_produce_schema_version(config.rabbitmq_schema_version);
+
+    // 單機開發模式 使用in-memory queue 不需要連線rabbit_mq
+    if config.rabbitmq_in_memory {
+        mq_manager::set_message_queue(std::sync::Arc::new(mq_manager::InMemoryMessageQueue::new()));
+        info!("in-memory message queue init success");
+        return;
+    }
+
     let _ = mq_manager::Builder::new()
         .host(&config.rabbitmq_host)
         .port(config.rabbitmq_port)
@@ -110,9 +153,55 @@ async fn notify_grpc_server() -> Result<(), tonic::transport::Error> {
         .unwrap();
     info!("start notify grpc server on {}", addr);
 
+    // 瀏覽器可直接以grpc-web呼叫 不需透過envoy轉換
+    let grpc_web_enabled = config::config::get_grpc_web().grpc_web_enabled;
+    if grpc_web_enabled {
+        info!("grpc-web enabled");
+    }
+
+    tonic::transport::Server::builder()
+        .accept_http1(grpc_web_enabled)
+        .layer(kgs_tracing::middlewares::tonic::root_span_builder())
+        .layer(kgs_tracing::middlewares::tonic::TracingRecord::default())
+        .layer(tower::util::option_layer(
+            grpc_web_enabled.then(grpc_web_cors),
+        ))
+        .layer(tower::util::option_layer(
+            grpc_web_enabled.then(tonic_web::GrpcWebLayer::new),
+        ))
+        .layer(notify_server::application::auth::InternalMethodLayer::public())
+        .layer(tower::util::option_layer(
+            notify_server::application::auth::is_enabled()
+                .then(notify_server::application::auth::AuthLayer::default),
+        ))
+        .add_service(FrontendNotifyServiceServer::from_arc(
+            notify_server::FRONTEND_NOTIFY_SERVER.clone(),
+        ))
+        .add_service(BackStageNotifyServiceServer::from_arc(
+            notify_server::BACKSTAGE_NOTIFY_SERVER.clone(),
+        ))
+        .serve(addr)
+        .await
+}
+
+/// 只接受其他pod轉發的請求 需帶入共用密鑰
+#[tracing::instrument]
+async fn internal_grpc_server() -> Result<(), tonic::transport::Error> {
+    use notify_server::application::auth::{check_internal_secret, InternalMethodLayer};
+    use protos::backstage_notify::back_stage_notify_service_server::BackStageNotifyServiceServer;
+    use protos::frontend_notify::frontend_notify_service_server::FrontendNotifyServiceServer;
+
+    let config = config::config::get_internal();
+    let addr = format!("{}:{}", config.internal_host, config.internal_port)
+        .parse()
+        .unwrap();
+    info!("start internal grpc server on {}", addr);
+
     tonic::transport::Server::builder()
         .layer(kgs_tracing::middlewares::tonic::root_span_builder())
         .layer(kgs_tracing::middlewares::tonic::TracingRecord::default())
+        .layer(InternalMethodLayer::internal())
+        .layer(tonic::service::interceptor(check_internal_secret))
         .add_service(FrontendNotifyServiceServer::from_arc(
             notify_server::FRONTEND_NOTIFY_SERVER.clone(),
         ))
@@ -121,4 +210,46 @@ async fn notify_grpc_server() -> Result<(), tonic::transport::Error> {
         ))
         .serve(addr)
         .await
+        .inspect_err(|e| warn!("internal grpc server stopped: {}", e))
+}
+
+/// grpc-web的跨域設定 未設定允許的來源時不允許跨域
+fn grpc_web_cors() -> tower_http::cors::CorsLayer {
+    use tonic::codegen::http::{header::HeaderName, HeaderValue, Method};
+    use tower_http::cors::{AllowOrigin, CorsLayer};
+
+    let config = config::config::get_grpc_web();
+    let allow_origin = if config
+        .grpc_web_allowed_origins
+        .iter()
+        .any(|origin| origin == "*")
+    {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            config
+                .grpc_web_allowed_origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin.trim()).ok()),
+        )
+    };
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::POST, Method::OPTIONS])
+        .allow_headers([
+            HeaderName::from_static("content-type"),
+            HeaderName::from_static("authorization"),
+            HeaderName::from_static("x-grpc-web"),
+            HeaderName::from_static("x-user-agent"),
+            HeaderName::from_static("grpc-timeout"),
+        ])
+        .expose_headers([
+            HeaderName::from_static("grpc-status"),
+            HeaderName::from_static("grpc-message"),
+            HeaderName::from_static("grpc-status-details-bin"),
+        ])
+        .max_age(std::time::Duration::from_secs(
+            config.grpc_web_cors_max_age_secs,
+        ))
 }
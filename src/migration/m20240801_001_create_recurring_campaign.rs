@@ -0,0 +1,137 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        self.create_table(manager).await?;
+
+        self.add_update_trigger(manager).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RecurringCampaign::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum RecurringCampaign {
+    Table,
+    Id,
+    ClientId,
+    ClientEventId,
+    SenderId,
+    SenderAccount,
+    SenderIp,
+    CampaignName,
+    CronExpression,
+    Timezone,
+    NotifyLevel,
+    IsAll,
+    ReceiverIds,
+    VipLevels,
+    IsActive,
+    StartAt,
+    EndAt,
+    NextRunAt,
+    LastRunAt,
+    CreateAt,
+    UpdateAt,
+}
+
+impl Migration {
+    async fn create_table(&self, manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RecurringCampaign::Table)
+                    .if_not_exists()
+                    .col(big_integer(RecurringCampaign::Id).not_null().primary_key())
+                    .col(big_integer(RecurringCampaign::ClientId).not_null())
+                    .col(big_integer(RecurringCampaign::ClientEventId).not_null())
+                    .col(big_integer(RecurringCampaign::SenderId).not_null())
+                    .col(string(RecurringCampaign::SenderAccount).not_null())
+                    .col(string_null(RecurringCampaign::SenderIp))
+                    .col(string(RecurringCampaign::CampaignName).not_null())
+                    .col(string(RecurringCampaign::CronExpression).not_null())
+                    .col(string(RecurringCampaign::Timezone).not_null())
+                    .col(integer(RecurringCampaign::NotifyLevel).not_null())
+                    .col(boolean(RecurringCampaign::IsAll).not_null())
+                    .col(array_null(
+                        RecurringCampaign::ReceiverIds,
+                        ColumnType::BigInteger,
+                    ))
+                    .col(array_null(
+                        RecurringCampaign::VipLevels,
+                        ColumnType::BigInteger,
+                    ))
+                    .col(boolean(RecurringCampaign::IsActive).not_null())
+                    .col(timestamp_null(RecurringCampaign::StartAt))
+                    .col(timestamp_null(RecurringCampaign::EndAt))
+                    .col(timestamp_null(RecurringCampaign::NextRunAt))
+                    .col(timestamp_null(RecurringCampaign::LastRunAt))
+                    .col(timestamp(RecurringCampaign::CreateAt).default(Expr::current_timestamp()))
+                    .col(timestamp(RecurringCampaign::UpdateAt).default(Expr::current_timestamp()))
+                    .to_owned(),
+            )
+            .await?;
+
+        // 排程器依 next_run_at 掃描到期的活動
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_recurring_campaign_next_run_at")
+                    .table(RecurringCampaign::Table)
+                    .col(RecurringCampaign::IsActive)
+                    .col(RecurringCampaign::NextRunAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        let sql = r#"
+            Comment on table recurring_campaign is '週期性發送活動表';
+            Comment on column recurring_campaign.id is 'ID';
+            Comment on column recurring_campaign.client_id is '客戶ID';
+            Comment on column recurring_campaign.client_event_id is '客戶事件ID';
+            Comment on column recurring_campaign.sender_id is '建立者ID';
+            Comment on column recurring_campaign.sender_account is '建立者帳號';
+            Comment on column recurring_campaign.sender_ip is '建立者IP';
+            Comment on column recurring_campaign.campaign_name is '活動名稱';
+            Comment on column recurring_campaign.cron_expression is 'cron表達式';
+            Comment on column recurring_campaign.timezone is '時區';
+            Comment on column recurring_campaign.notify_level is '通知等級';
+            Comment on column recurring_campaign.is_all is '是否發送給全部用戶';
+            Comment on column recurring_campaign.receiver_ids is '指定接收者ID';
+            Comment on column recurring_campaign.vip_levels is '指定VIP等級';
+            Comment on column recurring_campaign.is_active is '是否啟用';
+            Comment on column recurring_campaign.start_at is '活動開始時間';
+            Comment on column recurring_campaign.end_at is '活動結束時間';
+            Comment on column recurring_campaign.next_run_at is '下次執行時間';
+            Comment on column recurring_campaign.last_run_at is '上次執行時間';
+            Comment on column recurring_campaign.create_at is '創建時間';
+            Comment on column recurring_campaign.update_at is '更新時間';
+        "#;
+
+        manager.get_connection().execute_unprepared(sql).await?;
+        Ok(())
+    }
+
+    async fn add_update_trigger(&self, manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+        let sql = r#"
+            CREATE TRIGGER trigger_update_timestamp
+            BEFORE UPDATE ON recurring_campaign
+            FOR EACH ROW
+            EXECUTE FUNCTION update_timestamp();
+        "#;
+
+        manager.get_connection().execute_unprepared(sql).await?;
+
+        Ok(())
+    }
+}
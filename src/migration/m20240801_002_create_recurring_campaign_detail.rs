@@ -0,0 +1,68 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        self.create_table(manager).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(RecurringCampaignDetail::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum RecurringCampaignDetail {
+    Table,
+    Id,
+    RecurringCampaignId,
+    NotifyType,
+    Title,
+    Content,
+}
+
+impl Migration {
+    async fn create_table(&self, manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RecurringCampaignDetail::Table)
+                    .if_not_exists()
+                    .col(
+                        big_integer(RecurringCampaignDetail::Id)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(big_integer(RecurringCampaignDetail::RecurringCampaignId).not_null())
+                    .col(integer(RecurringCampaignDetail::NotifyType).not_null())
+                    .col(string(RecurringCampaignDetail::Title).not_null())
+                    .col(text(RecurringCampaignDetail::Content).not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        let sql = r#"
+            Comment on table recurring_campaign_detail is '週期性發送活動模板表';
+            Comment on column recurring_campaign_detail.id is 'ID';
+            Comment on column recurring_campaign_detail.recurring_campaign_id is '週期性發送活動ID';
+            Comment on column recurring_campaign_detail.notify_type is '通知類型';
+            Comment on column recurring_campaign_detail.title is '標題';
+            Comment on column recurring_campaign_detail.content is '內容';
+        "#;
+
+        manager.get_connection().execute_unprepared(sql).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(BackstageSendTask::Table)
+                    .add_column_if_not_exists(big_integer_null(
+                        BackstageSendTask::RecurringCampaignId,
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        let sql = r#"
+            Comment on column backstage_send_task.recurring_campaign_id is '週期性發送活動ID';
+        "#;
+
+        manager.get_connection().execute_unprepared(sql).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(BackstageSendTask::Table)
+                    .drop_column(BackstageSendTask::RecurringCampaignId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum BackstageSendTask {
+    Table,
+    RecurringCampaignId,
+}
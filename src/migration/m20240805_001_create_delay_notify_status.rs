@@ -0,0 +1,85 @@
+use crate::entity::delay_notify_status;
+use crate::enums;
+use sea_orm::{EntityTrait, Iterable, Set};
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        self.create_table(manager).await?;
+
+        self.create_initial_data(manager).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DelayNotifyStatus::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum DelayNotifyStatus {
+    Table,
+    Id,
+    Name,
+    Memo,
+}
+
+impl Migration {
+    async fn create_table(&self, manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(DelayNotifyStatus::Table)
+                    .if_not_exists()
+                    .col(integer(DelayNotifyStatus::Id).primary_key())
+                    .col(string(DelayNotifyStatus::Name).not_null())
+                    .col(string(DelayNotifyStatus::Memo))
+                    .to_owned(),
+            )
+            .await?;
+
+        let sql = r#"
+            Comment on table delay_notify_status is '延遲通知狀態表';
+            Comment on column delay_notify_status.id is 'ID';
+            Comment on column delay_notify_status.name is '狀態名稱';
+            Comment on column delay_notify_status.memo is '備註';
+        "#;
+
+        manager.get_connection().execute_unprepared(sql).await?;
+
+        Ok(())
+    }
+
+    async fn create_initial_data(&self, manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        for delay_notify_status in enums::DelayNotifyStatus::iter() {
+            // check if the delay_notify_status exists
+            if delay_notify_status::Entity::find_by_id(delay_notify_status.to_id())
+                .one(db)
+                .await?
+                .is_some()
+            {
+                continue;
+            }
+
+            // insert the delay_notify_status
+            delay_notify_status::Entity::insert(delay_notify_status::ActiveModel {
+                id: Set(delay_notify_status.to_id()),
+                name: Set(delay_notify_status.to_string()),
+                memo: Set(delay_notify_status.get_comment()),
+            })
+            .exec(db)
+            .await?;
+        }
+
+        Ok(())
+    }
+}
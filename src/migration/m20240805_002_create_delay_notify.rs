@@ -0,0 +1,117 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        self.create_table(manager).await?;
+
+        self.add_update_trigger(manager).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DelayNotify::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum DelayNotify {
+    Table,
+    Id,
+    ClientId,
+    UserId,
+    NotifyEvent,
+    DelayKey,
+    KeyMap,
+    SendAt,
+    DelayStatus,
+    ErrorMessage,
+    CreateAt,
+    UpdateAt,
+}
+
+impl Migration {
+    async fn create_table(&self, manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(DelayNotify::Table)
+                    .if_not_exists()
+                    .col(big_integer(DelayNotify::Id).not_null().primary_key())
+                    .col(big_integer(DelayNotify::ClientId).not_null())
+                    .col(big_integer(DelayNotify::UserId).not_null())
+                    .col(big_integer(DelayNotify::NotifyEvent).not_null())
+                    .col(string_null(DelayNotify::DelayKey))
+                    .col(json_binary(DelayNotify::KeyMap).not_null())
+                    .col(timestamp(DelayNotify::SendAt).not_null())
+                    .col(integer(DelayNotify::DelayStatus).not_null())
+                    .col(text_null(DelayNotify::ErrorMessage))
+                    .col(timestamp(DelayNotify::CreateAt).default(Expr::current_timestamp()))
+                    .col(timestamp(DelayNotify::UpdateAt).default(Expr::current_timestamp()))
+                    .to_owned(),
+            )
+            .await?;
+
+        // 排程器依 send_at 掃描待發送的通知
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_delay_notify_status_send_at")
+                    .table(DelayNotify::Table)
+                    .col(DelayNotify::DelayStatus)
+                    .col(DelayNotify::SendAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        // 以呼叫端key取消通知
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_delay_notify_delay_key")
+                    .table(DelayNotify::Table)
+                    .col(DelayNotify::ClientId)
+                    .col(DelayNotify::UserId)
+                    .col(DelayNotify::DelayKey)
+                    .to_owned(),
+            )
+            .await?;
+
+        let sql = r#"
+            Comment on table delay_notify is '延遲通知表';
+            Comment on column delay_notify.id is 'ID';
+            Comment on column delay_notify.client_id is '客戶端ID';
+            Comment on column delay_notify.user_id is '用戶ID';
+            Comment on column delay_notify.notify_event is '通知事件';
+            Comment on column delay_notify.delay_key is '呼叫端自定義key';
+            Comment on column delay_notify.key_map is '模板替換參數';
+            Comment on column delay_notify.send_at is '預計發送時間';
+            Comment on column delay_notify.delay_status is '延遲通知狀態';
+            Comment on column delay_notify.error_message is '錯誤訊息';
+            Comment on column delay_notify.create_at is '建立時間';
+            Comment on column delay_notify.update_at is '更新時間';
+        "#;
+
+        manager.get_connection().execute_unprepared(sql).await?;
+        Ok(())
+    }
+
+    async fn add_update_trigger(&self, manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+        let sql = r#"
+            CREATE TRIGGER trigger_update_timestamp
+            BEFORE UPDATE ON delay_notify
+            FOR EACH ROW
+            EXECUTE FUNCTION update_timestamp();
+        "#;
+
+        manager.get_connection().execute_unprepared(sql).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NotifyRecord::Table)
+                    .add_column_if_not_exists(timestamp_null(NotifyRecord::ExpiresAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DelayNotify::Table)
+                    .add_column_if_not_exists(timestamp_null(DelayNotify::ExpiresAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        let sql = r#"
+            Comment on column notify_record.expires_at is '過期時間';
+            Comment on column delay_notify.expires_at is '過期時間';
+        "#;
+
+        manager.get_connection().execute_unprepared(sql).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NotifyRecord::Table)
+                    .drop_column(NotifyRecord::ExpiresAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DelayNotify::Table)
+                    .drop_column(DelayNotify::ExpiresAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum NotifyRecord {
+    Table,
+    ExpiresAt,
+}
+
+#[derive(DeriveIden)]
+enum DelayNotify {
+    Table,
+    ExpiresAt,
+}
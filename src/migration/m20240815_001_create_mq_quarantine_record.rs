@@ -0,0 +1,86 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        self.create_table(manager).await?;
+
+        self.add_update_trigger(manager).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MqQuarantineRecord::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MqQuarantineRecord {
+    Table,
+    Id,
+    QueueName,
+    Payload,
+    Headers,
+    ErrorMessage,
+    ReplayCount,
+    ReplayedAt,
+    CreateAt,
+    UpdateAt,
+}
+
+impl Migration {
+    async fn create_table(&self, manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MqQuarantineRecord::Table)
+                    .if_not_exists()
+                    .col(big_integer(MqQuarantineRecord::Id).not_null().primary_key())
+                    .col(string(MqQuarantineRecord::QueueName).not_null())
+                    .col(blob(MqQuarantineRecord::Payload).not_null())
+                    .col(json_binary(MqQuarantineRecord::Headers).not_null())
+                    .col(text(MqQuarantineRecord::ErrorMessage).not_null())
+                    .col(integer(MqQuarantineRecord::ReplayCount).default(0))
+                    .col(timestamp_null(MqQuarantineRecord::ReplayedAt))
+                    .col(timestamp(MqQuarantineRecord::CreateAt).default(Expr::current_timestamp()))
+                    .col(timestamp(MqQuarantineRecord::UpdateAt).default(Expr::current_timestamp()))
+                    .to_owned(),
+            )
+            .await?;
+
+        let sql = r#"
+            Comment on table mq_quarantine_record is '無法解析的mq訊息隔離表';
+            Comment on column mq_quarantine_record.id is 'ID';
+            Comment on column mq_quarantine_record.queue_name is '來源queue';
+            Comment on column mq_quarantine_record.payload is '原始訊息內容';
+            Comment on column mq_quarantine_record.headers is '原始訊息headers';
+            Comment on column mq_quarantine_record.error_message is '解析錯誤訊息';
+            Comment on column mq_quarantine_record.replay_count is '重新發送次數';
+            Comment on column mq_quarantine_record.replayed_at is '最後重新發送時間';
+            Comment on column mq_quarantine_record.create_at is '建立時間';
+            Comment on column mq_quarantine_record.update_at is '更新時間';
+        "#;
+
+        manager.get_connection().execute_unprepared(sql).await?;
+        Ok(())
+    }
+
+    async fn add_update_trigger(&self, manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+        let sql = r#"
+            CREATE TRIGGER trigger_update_timestamp
+            BEFORE UPDATE ON mq_quarantine_record
+            FOR EACH ROW
+            EXECUTE FUNCTION update_timestamp();
+        "#;
+
+        manager.get_connection().execute_unprepared(sql).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MqFailedRecord::Table)
+                    .add_column_if_not_exists(string_null(MqFailedRecord::ErrorClass))
+                    .add_column_if_not_exists(integer(MqFailedRecord::RetryCount).default(0))
+                    .to_owned(),
+            )
+            .await?;
+
+        let sql = r#"
+            Comment on column mq_failed_record.error_class is '錯誤分類 retryable.可重試 permanent.永久性錯誤';
+            Comment on column mq_failed_record.retry_count is '記錄失敗前已重試的次數';
+        "#;
+
+        manager.get_connection().execute_unprepared(sql).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MqFailedRecord::Table)
+                    .drop_column(MqFailedRecord::ErrorClass)
+                    .drop_column(MqFailedRecord::RetryCount)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MqFailedRecord {
+    Table,
+    ErrorClass,
+    RetryCount,
+}
@@ -0,0 +1,43 @@
+use crate::entity::notify_status;
+use crate::enums;
+use sea_orm::{EntityTrait, Set};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let notify_status = enums::NotifyStatus::Delivered;
+
+        // check if the notify_status exists
+        if notify_status::Entity::find_by_id(notify_status.to_id())
+            .one(db)
+            .await?
+            .is_some()
+        {
+            return Ok(());
+        }
+
+        // insert notify_status
+        notify_status::Entity::insert(notify_status::ActiveModel {
+            id: Set(notify_status.to_id()),
+            name: Set(notify_status.to_string()),
+            memo: Set(notify_status.get_comment()),
+        })
+        .exec(db)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        notify_status::Entity::delete_by_id(enums::NotifyStatus::Delivered.to_id())
+            .exec(manager.get_connection())
+            .await?;
+
+        Ok(())
+    }
+}
@@ -14,6 +14,16 @@ mod m20240708_001_create_platform;
 mod m20240710_001_create_task_status;
 mod m20240710_002_create_backstage_send_task;
 mod m20240710_003_create_backstage_send_task_detail;
+mod m20240801_001_create_recurring_campaign;
+mod m20240801_002_create_recurring_campaign_detail;
+mod m20240801_003_alter_backstage_send_task_add_campaign_id;
+mod m20240805_001_create_delay_notify_status;
+mod m20240805_002_create_delay_notify;
+mod m20240810_001_add_expires_at;
+mod m20240810_002_insert_notify_status_expired;
+mod m20240815_001_create_mq_quarantine_record;
+mod m20240820_001_alter_mq_failed_record_add_error_class;
+mod m20240825_001_insert_notify_status_delivered;
 
 pub use sea_orm_migration::prelude::*;
 
@@ -39,6 +49,16 @@ impl MigratorTrait for Migrator {
             Box::new(m20240710_001_create_task_status::Migration),             // 新增任務狀態表
             Box::new(m20240710_002_create_backstage_send_task::Migration),     // 新增後台發送任務表
             Box::new(m20240710_003_create_backstage_send_task_detail::Migration), // 新增後台發送任務詳情表
+            Box::new(m20240801_001_create_recurring_campaign::Migration), // 新增週期性發送活動表
+            Box::new(m20240801_002_create_recurring_campaign_detail::Migration), // 新增週期性發送活動模板表
+            Box::new(m20240801_003_alter_backstage_send_task_add_campaign_id::Migration), // 後台發送任務表新增活動ID
+            Box::new(m20240805_001_create_delay_notify_status::Migration), // 新增延遲通知狀態表
+            Box::new(m20240805_002_create_delay_notify::Migration),        // 新增延遲通知表
+            Box::new(m20240810_001_add_expires_at::Migration), // 通知紀錄表與延遲通知表新增過期時間
+            Box::new(m20240810_002_insert_notify_status_expired::Migration), // 新增過期通知狀態
+            Box::new(m20240815_001_create_mq_quarantine_record::Migration), // 新增無法解析的mq訊息隔離表
+            Box::new(m20240820_001_alter_mq_failed_record_add_error_class::Migration), // 錯誤紀錄表新增錯誤分類與重試次數
+            Box::new(m20240825_001_insert_notify_status_delivered::Migration), // 新增已送達通知狀態
         ]
     }
 }
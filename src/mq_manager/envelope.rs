@@ -0,0 +1,210 @@
+use super::{BatchNotifyModel, SingleNotifyModel};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// 目前的訊息格式版本
+/// 0: 舊版 直接傳送model的json 沒有envelope
+/// 1: 以MessageEnvelope包裝
+pub const LEGACY_SCHEMA_VERSION: u32 = 0;
+pub const CURRENT_SCHEMA_VERSION: u32 = 1;
+
+// producer發送的版本 滾動更新時先以舊版發送 等所有consumer都能解析新版後再切換
+static PRODUCE_SCHEMA_VERSION: AtomicU32 = AtomicU32::new(LEGACY_SCHEMA_VERSION);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageType {
+    SingleNotify,
+    BatchNotify,
+}
+
+/// queue中傳送的訊息外層
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageEnvelope<T> {
+    pub schema_version: u32,
+    pub message_type: MessageType,
+    pub produced_at: i64, // 發送時間(毫秒)
+    #[serde(default)]
+    pub trace_context: HashMap<String, String>, // 追蹤資訊 用於串接producer與consumer的trace
+    pub idempotency_key: String, // 同一筆訊息重複發送時key相同 consumer可據此去重
+    pub payload: T,
+}
+
+/// 可以被envelope包裝的訊息
+pub trait EnvelopeMessage: Serialize + DeserializeOwned {
+    const MESSAGE_TYPE: MessageType;
+
+    fn idempotency_key(&self) -> String;
+}
+
+impl EnvelopeMessage for SingleNotifyModel {
+    const MESSAGE_TYPE: MessageType = MessageType::SingleNotify;
+
+    fn idempotency_key(&self) -> String {
+        format!("single_notify:{}", self.notify_id)
+    }
+}
+
+impl EnvelopeMessage for BatchNotifyModel {
+    const MESSAGE_TYPE: MessageType = MessageType::BatchNotify;
+
+    fn idempotency_key(&self) -> String {
+        format!("batch_notify:{}", self.task_id)
+    }
+}
+
+pub fn set_produce_schema_version(schema_version: u32) {
+    PRODUCE_SCHEMA_VERSION.store(schema_version, Ordering::Relaxed);
+}
+
+/// 依producer設定的版本將訊息轉成payload
+pub fn encode<T: EnvelopeMessage>(
+    message: &T,
+    trace_context: &HashMap<String, String>,
+) -> Result<Vec<u8>, serde_json::Error> {
+    match PRODUCE_SCHEMA_VERSION.load(Ordering::Relaxed) {
+        LEGACY_SCHEMA_VERSION => serde_json::to_vec(message),
+        _ => serde_json::to_vec(&MessageEnvelope {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            message_type: T::MESSAGE_TYPE,
+            produced_at: chrono::Utc::now().timestamp_millis(),
+            trace_context: trace_context.clone(),
+            idempotency_key: message.idempotency_key(),
+            payload: message,
+        }),
+    }
+}
+
+/// 解析queue中的payload 同時支援舊版與新版格式
+/// 比目前版本新的訊息仍會嘗試解析 新增的欄位會被忽略
+pub fn decode<T: EnvelopeMessage>(data: &[u8]) -> Result<MessageEnvelope<T>, serde_json::Error> {
+    let value: serde_json::Value = serde_json::from_slice(data)?;
+
+    // 舊版訊息沒有schema_version 整個json就是model
+    if value.get("schema_version").is_none() {
+        let payload: T = serde_json::from_value(value)?;
+        return Ok(MessageEnvelope {
+            schema_version: LEGACY_SCHEMA_VERSION,
+            message_type: T::MESSAGE_TYPE,
+            produced_at: 0,
+            trace_context: HashMap::new(),
+            idempotency_key: payload.idempotency_key(),
+            payload,
+        });
+    }
+
+    let envelope: MessageEnvelope<T> = serde_json::from_value(value)?;
+    if envelope.message_type != T::MESSAGE_TYPE {
+        return Err(serde::de::Error::custom(format!(
+            "unexpected message type: {:?}, expected: {:?}",
+            envelope.message_type,
+            T::MESSAGE_TYPE
+        )));
+    }
+
+    Ok(envelope)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums;
+
+    fn single_notify_model() -> SingleNotifyModel {
+        SingleNotifyModel {
+            client_id: 1,
+            user_id: 2,
+            notify_id: 100,
+            sender_id: 0,
+            sender_account: "".to_string(),
+            sender_ip: None,
+            notify_type: enums::NotifyType::InApp,
+            notify_level: enums::NotifyLevel::Info,
+            title: "title".to_string(),
+            content: "content".to_string(),
+            receive_address: "".to_string(),
+            key_map: HashMap::new(),
+            client_event_id: 0,
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn decode_legacy_message() {
+        let model = single_notify_model();
+        let data = serde_json::to_vec(&model).unwrap();
+
+        let envelope = decode::<SingleNotifyModel>(&data).unwrap();
+        assert_eq!(envelope.schema_version, LEGACY_SCHEMA_VERSION);
+        assert_eq!(envelope.message_type, MessageType::SingleNotify);
+        assert_eq!(envelope.produced_at, 0);
+        assert!(envelope.trace_context.is_empty());
+        assert_eq!(envelope.idempotency_key, "single_notify:100");
+        assert_eq!(envelope.payload.notify_id, model.notify_id);
+        assert_eq!(envelope.payload.title, model.title);
+    }
+
+    #[test]
+    fn decode_current_message() {
+        let model = single_notify_model();
+        let trace_context = HashMap::from([("traceparent".to_string(), "trace".to_string())]);
+        let data = serde_json::to_vec(&MessageEnvelope {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            message_type: MessageType::SingleNotify,
+            produced_at: 1000,
+            trace_context: trace_context.clone(),
+            idempotency_key: "custom_key".to_string(),
+            payload: &model,
+        })
+        .unwrap();
+
+        let envelope = decode::<SingleNotifyModel>(&data).unwrap();
+        assert_eq!(envelope.schema_version, CURRENT_SCHEMA_VERSION);
+        assert_eq!(envelope.produced_at, 1000);
+        assert_eq!(envelope.trace_context, trace_context);
+        assert_eq!(envelope.idempotency_key, "custom_key");
+        assert_eq!(envelope.payload.content, model.content);
+    }
+
+    #[test]
+    fn decode_newer_message_ignores_unknown_fields() {
+        let mut value = serde_json::to_value(MessageEnvelope {
+            schema_version: CURRENT_SCHEMA_VERSION + 1,
+            message_type: MessageType::SingleNotify,
+            produced_at: 1000,
+            trace_context: HashMap::new(),
+            idempotency_key: "single_notify:100".to_string(),
+            payload: single_notify_model(),
+        })
+        .unwrap();
+        value["new_field"] = serde_json::json!("value");
+        let data = serde_json::to_vec(&value).unwrap();
+
+        let envelope = decode::<SingleNotifyModel>(&data).unwrap();
+        assert_eq!(envelope.schema_version, CURRENT_SCHEMA_VERSION + 1);
+        assert_eq!(envelope.payload.notify_id, 100);
+    }
+
+    #[test]
+    fn decode_rejects_unexpected_message_type() {
+        let data = serde_json::to_vec(&MessageEnvelope {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            message_type: MessageType::BatchNotify,
+            produced_at: 1000,
+            trace_context: HashMap::new(),
+            idempotency_key: "batch_notify:1".to_string(),
+            payload: single_notify_model(),
+        })
+        .unwrap();
+
+        assert!(decode::<SingleNotifyModel>(&data).is_err());
+    }
+
+    #[test]
+    fn decode_rejects_invalid_payload() {
+        assert!(decode::<SingleNotifyModel>(b"not json").is_err());
+        assert!(decode::<SingleNotifyModel>(br#"{"notify_id":"invalid"}"#).is_err());
+    }
+}
@@ -1,24 +1,32 @@
 use deadpool::managed::QueueMode;
 use deadpool_lapin::Connection;
+use futures::StreamExt;
 use kgs_tracing::tracing;
 use lapin::{
-    message::Delivery,
-    options::{BasicAckOptions, BasicConsumeOptions, BasicQosOptions},
-    types::FieldTable,
-    Channel, ConnectionProperties, Consumer,
+    options::{BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicQosOptions},
+    types::{AMQPValue, FieldTable, ShortString},
+    Channel, ConnectionProperties,
 };
 use once_cell::sync::OnceCell;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
+use std::time::Duration;
+use tonic::async_trait;
 
-use super::{model::SingleNotifyModel, BatchNotifyModel};
+use super::queue::{
+    get_message_queue, set_message_queue, MessageQueue, QueueConsumer, QueueError, QueueMessage,
+    QueueName,
+};
+use super::{envelope, model::SingleNotifyModel, trace_context, BatchNotifyModel};
 
 static MQ_CONNECTION_POOL: OnceCell<Arc<deadpool_lapin::Pool>> = OnceCell::new();
 
 const EXCHANGE_NAME: &str = "notify_exchange";
-const SINGLE_NOTIFY_QUEUE_NAME: &str = "single_notify_queue"; // 對單一玩家通知的queue
 const SINGLE_NOTIFY_ROUTING_KEY: &str = "single_notify_routing_key"; // 對單一玩家通知的routing key
 const BATCH_NOTIFY_ROUTING_KEY: &str = "batch_notify_routing_key"; // 對多玩家通知的routing key
-const BATCH_NOTIFY_QUEUE_NAME: &str = "batch_notify_queue"; // 對多玩家通知的queue
+const DEAD_LETTER_ROUTING_KEY: &str = "dead_letter_routing_key"; // 超過重試次數的訊息的routing key
+const SINGLE_NOTIFY_RETRY_QUEUE: &str = "single_notify_retry_queue"; // 單一玩家通知等待重試的queue 過期後回到原queue
+const BATCH_NOTIFY_RETRY_QUEUE: &str = "batch_notify_retry_queue"; // 多玩家通知等待重試的queue 過期後回到原queue
 
 pub struct Builder<'a> {
     pub host: &'a str,
@@ -111,6 +119,7 @@ impl<'a> Builder<'a> {
 
         // init rabbit mq
         let _ = init_rabbit_mq().await.expect("Failed to init rabbit mq");
+        set_message_queue(Arc::new(RabbitMessageQueue));
 
         // return rabbit connection pool
         MQ_CONNECTION_POOL
@@ -157,17 +166,53 @@ async fn init_rabbit_mq() -> Result<(), Box<dyn std::error::Error>> {
         ..Default::default()
     };
     channel
-        .queue_declare(SINGLE_NOTIFY_QUEUE_NAME, queue_opt, FieldTable::default())
+        .queue_declare(
+            QueueName::SingleNotify.as_str(),
+            queue_opt,
+            FieldTable::default(),
+        )
+        .await?;
+    channel
+        .queue_declare(
+            QueueName::BatchNotify.as_str(),
+            queue_opt,
+            FieldTable::default(),
+        )
         .await?;
     channel
-        .queue_declare(BATCH_NOTIFY_QUEUE_NAME, queue_opt, FieldTable::default())
+        .queue_declare(
+            QueueName::DeadLetter.as_str(),
+            queue_opt,
+            FieldTable::default(),
+        )
         .await?;
 
+    // declare retry queue
+    // 重試的訊息帶有expiration 過期後由dead letter exchange送回原本的routing key
+    // retry queue不會被consume 只有queue前端的訊息會過期 較短的延遲可能被前面較長的延遲擋住
+    for queue in [QueueName::SingleNotify, QueueName::BatchNotify] {
+        let Some(retry_queue) = RabbitMessageQueue::get_retry_queue(queue) else {
+            continue;
+        };
+        let mut arguments = FieldTable::default();
+        arguments.insert(
+            "x-dead-letter-exchange".into(),
+            AMQPValue::LongString(EXCHANGE_NAME.into()),
+        );
+        arguments.insert(
+            "x-dead-letter-routing-key".into(),
+            AMQPValue::LongString(RabbitMessageQueue::get_routing_key(queue).into()),
+        );
+        channel
+            .queue_declare(retry_queue, queue_opt, arguments)
+            .await?;
+    }
+
     // bind queue to exchange
     let bind_opt = lapin::options::QueueBindOptions::default();
     channel
         .queue_bind(
-            SINGLE_NOTIFY_QUEUE_NAME,
+            QueueName::SingleNotify.as_str(),
             EXCHANGE_NAME,
             SINGLE_NOTIFY_ROUTING_KEY,
             bind_opt,
@@ -176,108 +221,213 @@ async fn init_rabbit_mq() -> Result<(), Box<dyn std::error::Error>> {
         .await?;
     channel
         .queue_bind(
-            BATCH_NOTIFY_QUEUE_NAME,
+            QueueName::BatchNotify.as_str(),
             EXCHANGE_NAME,
             BATCH_NOTIFY_ROUTING_KEY,
             bind_opt,
             FieldTable::default(),
         )
         .await?;
+    channel
+        .queue_bind(
+            QueueName::DeadLetter.as_str(),
+            EXCHANGE_NAME,
+            DEAD_LETTER_ROUTING_KEY,
+            bind_opt,
+            FieldTable::default(),
+        )
+        .await?;
 
     Ok(())
 }
 
+/// Publish a single notify message to message queue
 #[tracing::instrument]
-/// Publish a message to rabbit mq
 pub async fn publish_single_notify(
     message: &SingleNotifyModel,
 ) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
-    let channel = open_channel().await?;
-
-    let payload = serde_json::to_string(message)?;
-    let publish_opt = lapin::options::BasicPublishOptions::default();
-    channel
-        .basic_publish(
-            EXCHANGE_NAME,
-            SINGLE_NOTIFY_ROUTING_KEY,
-            publish_opt,
-            payload.as_bytes(),
-            lapin::BasicProperties::default(),
-        )
-        .await?;
-
-    Ok(())
+    let trace_context = trace_context::inject_current_context();
+    let payload = envelope::encode(message, &trace_context)?;
+    get_message_queue()
+        .publish(QueueName::SingleNotify, payload, trace_context)
+        .await
 }
 
+/// Publish a batch notify message to message queue
 #[tracing::instrument]
-/// Publish a batch notify message to rabbit mq
 pub async fn publish_batch_notify(
     message: &BatchNotifyModel,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let channel = open_channel().await?;
+) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
+    let trace_context = trace_context::inject_current_context();
+    let payload = envelope::encode(message, &trace_context)?;
+    get_message_queue()
+        .publish(QueueName::BatchNotify, payload, trace_context)
+        .await
+}
 
-    let payload = serde_json::to_string(message)?;
-    let publish_opt = lapin::options::BasicPublishOptions::default();
-    channel
-        .basic_publish(
-            EXCHANGE_NAME,
-            BATCH_NOTIFY_ROUTING_KEY,
-            publish_opt,
-            payload.as_bytes(),
-            lapin::BasicProperties::default(),
-        )
-        .await?;
+/// 以rabbit_mq實作的queue 使用全域的rabbit_mq連線池
+#[derive(Debug)]
+pub struct RabbitMessageQueue;
 
-    Ok(())
-}
+impl RabbitMessageQueue {
+    fn get_routing_key(queue: QueueName) -> &'static str {
+        match queue {
+            QueueName::SingleNotify => SINGLE_NOTIFY_ROUTING_KEY,
+            QueueName::BatchNotify => BATCH_NOTIFY_ROUTING_KEY,
+            QueueName::DeadLetter => DEAD_LETTER_ROUTING_KEY,
+        }
+    }
 
-#[tracing::instrument]
-pub async fn consume_single_notify(
-    consumer_tag: &str,
-) -> Result<Consumer, deadpool::managed::PoolError<lapin::Error>> {
-    let channel = open_channel().await?;
+    /// 等待重試的queue dead letter queue不會重試
+    fn get_retry_queue(queue: QueueName) -> Option<&'static str> {
+        match queue {
+            QueueName::SingleNotify => Some(SINGLE_NOTIFY_RETRY_QUEUE),
+            QueueName::BatchNotify => Some(BATCH_NOTIFY_RETRY_QUEUE),
+            QueueName::DeadLetter => None,
+        }
+    }
 
-    // setting consumer
-    let basic_qos = BasicQosOptions { global: true }; // 全部的consumer共享一個Qos
-    channel.basic_qos(1, basic_qos).await?; // 一次只處理一個message
-    let consume_opt = BasicConsumeOptions::default();
+    fn get_properties(headers: HashMap<String, String>) -> lapin::BasicProperties {
+        let headers: BTreeMap<ShortString, AMQPValue> = headers
+            .into_iter()
+            .map(|(key, value)| (key.into(), AMQPValue::LongString(value.into())))
+            .collect();
+        lapin::BasicProperties::default().with_headers(headers.into())
+    }
 
-    // consume message
-    channel
-        .basic_consume(
-            SINGLE_NOTIFY_QUEUE_NAME,
-            consumer_tag,
-            consume_opt,
-            FieldTable::default(),
-        )
-        .await
-        .map_err(|err| err.into())
+    /// 將AMQP headers轉成字串 方便記錄與傳遞
+    fn get_headers(properties: &lapin::BasicProperties) -> HashMap<String, String> {
+        properties
+            .headers()
+            .as_ref()
+            .map(|headers| {
+                headers
+                    .inner()
+                    .iter()
+                    .map(|(key, value)| {
+                        let value = match value {
+                            AMQPValue::LongString(v) => v.to_string(),
+                            AMQPValue::ShortString(v) => v.to_string(),
+                            v => format!("{:?}", v),
+                        };
+                        (key.to_string(), value)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 }
 
-#[tracing::instrument]
-pub async fn consume_batch_notify(
-    consumer_tag: &str,
-) -> Result<Consumer, deadpool::managed::PoolError<lapin::Error>> {
-    let channel = open_channel().await?;
+#[async_trait]
+impl MessageQueue for RabbitMessageQueue {
+    #[tracing::instrument(skip(payload, headers))]
+    async fn publish(
+        &self,
+        queue: QueueName,
+        payload: Vec<u8>,
+        headers: HashMap<String, String>,
+    ) -> Result<(), QueueError> {
+        let channel = open_channel().await?;
+
+        let properties = Self::get_properties(headers);
+
+        let publish_opt = lapin::options::BasicPublishOptions::default();
+        channel
+            .basic_publish(
+                EXCHANGE_NAME,
+                Self::get_routing_key(queue),
+                publish_opt,
+                &payload,
+                properties,
+            )
+            .await?;
+
+        Ok(())
+    }
 
-    // setting consumer
-    let basic_qos = BasicQosOptions { global: true }; // 全部的consumer共享一個Qos
-    channel.basic_qos(1, basic_qos).await?; // 一次只處理一個message
-    let consume_opt = BasicConsumeOptions::default();
+    #[tracing::instrument(skip(payload, headers))]
+    async fn publish_delayed(
+        &self,
+        queue: QueueName,
+        payload: Vec<u8>,
+        headers: HashMap<String, String>,
+        delay: Duration,
+    ) -> Result<(), QueueError> {
+        let retry_queue = match Self::get_retry_queue(queue) {
+            Some(retry_queue) => retry_queue,
+            None => return self.publish(queue, payload, headers).await,
+        };
+        let channel = open_channel().await?;
 
-    // consume message
-    channel
-        .basic_consume(
-            BATCH_NOTIFY_QUEUE_NAME,
-            consumer_tag,
-            consume_opt,
-            FieldTable::default(),
-        )
-        .await
-        .map_err(|err| err.into())
-}
+        // 以預設exchange直接送到retry queue 過期後再送回原本的queue
+        let properties =
+            Self::get_properties(headers).with_expiration(delay.as_millis().to_string().into());
 
-#[tracing::instrument]
-pub async fn rabbit_consumer_ack(delivery: &Delivery) -> Result<(), lapin::Error> {
-    delivery.ack(BasicAckOptions::default()).await
+        let publish_opt = lapin::options::BasicPublishOptions::default();
+        channel
+            .basic_publish("", retry_queue, publish_opt, &payload, properties)
+            .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    async fn consume(
+        &self,
+        queue: QueueName,
+        consumer_tag: &str,
+    ) -> Result<QueueConsumer, QueueError> {
+        let channel = open_channel().await?;
+
+        // setting consumer
+        let basic_qos = BasicQosOptions { global: true }; // 全部的consumer共享一個Qos
+        channel.basic_qos(1, basic_qos).await?; // 一次只處理一個message
+        let consume_opt = BasicConsumeOptions::default();
+
+        // consume message
+        let consumer = channel
+            .basic_consume(
+                queue.as_str(),
+                consumer_tag,
+                consume_opt,
+                FieldTable::default(),
+            )
+            .await?;
+
+        let stream = consumer.map(move |delivery| {
+            delivery
+                .map(|delivery| QueueMessage {
+                    queue,
+                    delivery_tag: delivery.delivery_tag,
+                    headers: Self::get_headers(&delivery.properties),
+                    data: delivery.data,
+                    redelivered: delivery.redelivered,
+                    acker: Some(delivery.acker),
+                })
+                .map_err(|err| Box::new(err) as QueueError)
+        });
+
+        Ok(QueueConsumer::new(consumer_tag, Box::pin(stream)))
+    }
+
+    #[tracing::instrument(skip(message))]
+    async fn ack(&self, message: &QueueMessage) -> Result<(), QueueError> {
+        if let Some(acker) = &message.acker {
+            acker.ack(BasicAckOptions::default()).await?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(message))]
+    async fn nack(&self, message: &QueueMessage, requeue: bool) -> Result<(), QueueError> {
+        if let Some(acker) = &message.acker {
+            acker
+                .nack(BasicNackOptions {
+                    requeue,
+                    ..Default::default()
+                })
+                .await?;
+        }
+        Ok(())
+    }
 }
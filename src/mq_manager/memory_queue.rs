@@ -0,0 +1,262 @@
+use super::queue::{MessageQueue, QueueConsumer, QueueError, QueueMessage, QueueName};
+use kgs_tracing::tracing;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+use tonic::async_trait;
+
+#[derive(Debug)]
+struct MemoryQueue {
+    sender: mpsc::UnboundedSender<QueueMessage>,
+    receiver: Arc<Mutex<mpsc::UnboundedReceiver<QueueMessage>>>, // 多個consumer共用 同一筆訊息只會被一個consumer拿到
+}
+
+/// 以tokio channel實作的queue 用於整合測試與單機開發模式
+/// 訊息只存在記憶體中 服務重啟後就會消失
+#[derive(Debug)]
+pub struct InMemoryMessageQueue {
+    queues: HashMap<QueueName, MemoryQueue>,
+    delivery_tag: AtomicU64,
+}
+
+impl InMemoryMessageQueue {
+    pub fn new() -> Self {
+        let queues = QueueName::all()
+            .into_iter()
+            .map(|queue| {
+                let (sender, receiver) = mpsc::unbounded_channel();
+                let memory_queue = MemoryQueue {
+                    sender,
+                    receiver: Arc::new(Mutex::new(receiver)),
+                };
+                (queue, memory_queue)
+            })
+            .collect();
+
+        InMemoryMessageQueue {
+            queues,
+            delivery_tag: AtomicU64::new(0),
+        }
+    }
+
+    fn get_queue(&self, queue: QueueName) -> &MemoryQueue {
+        self.queues
+            .get(&queue)
+            .expect("in-memory queue should be declared")
+    }
+
+    fn new_message(
+        &self,
+        queue: QueueName,
+        payload: Vec<u8>,
+        headers: HashMap<String, String>,
+    ) -> QueueMessage {
+        let delivery_tag = self.delivery_tag.fetch_add(1, Ordering::Relaxed) + 1;
+        QueueMessage {
+            queue,
+            delivery_tag,
+            data: payload,
+            headers,
+            redelivered: false,
+            acker: None,
+        }
+    }
+
+    fn send(&self, message: QueueMessage) -> Result<(), QueueError> {
+        self.get_queue(message.queue)
+            .sender
+            .send(message)
+            .map_err(|err| format!("in-memory queue closed: {}", err).into())
+    }
+}
+
+#[async_trait]
+impl MessageQueue for InMemoryMessageQueue {
+    #[tracing::instrument(skip(payload, headers))]
+    async fn publish(
+        &self,
+        queue: QueueName,
+        payload: Vec<u8>,
+        headers: HashMap<String, String>,
+    ) -> Result<(), QueueError> {
+        self.send(self.new_message(queue, payload, headers))
+    }
+
+    #[tracing::instrument(skip(payload, headers))]
+    async fn publish_delayed(
+        &self,
+        queue: QueueName,
+        payload: Vec<u8>,
+        headers: HashMap<String, String>,
+        delay: Duration,
+    ) -> Result<(), QueueError> {
+        let message = self.new_message(queue, payload, headers);
+        let sender = self.get_queue(queue).sender.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            // channel關閉代表服務正在結束 訊息本來就不會保留
+            let _ = sender.send(message);
+        });
+
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    async fn consume(
+        &self,
+        queue: QueueName,
+        consumer_tag: &str,
+    ) -> Result<QueueConsumer, QueueError> {
+        let receiver = Arc::clone(&self.get_queue(queue).receiver);
+        let stream = futures::stream::unfold(receiver, |receiver| async move {
+            let message = receiver.lock().await.recv().await;
+            message.map(|message| (Ok(message), receiver))
+        });
+
+        Ok(QueueConsumer::new(consumer_tag, Box::pin(stream)))
+    }
+
+    async fn ack(&self, _message: &QueueMessage) -> Result<(), QueueError> {
+        // 訊息取出後就已離開channel 不需要再確認
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(message))]
+    async fn nack(&self, message: &QueueMessage, requeue: bool) -> Result<(), QueueError> {
+        if requeue {
+            self.send(QueueMessage {
+                redelivered: true,
+                ..message.clone()
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn next_message(consumer: &mut QueueConsumer) -> QueueMessage {
+        tokio::time::timeout(Duration::from_secs(1), consumer.next())
+            .await
+            .expect("message should be received")
+            .expect("queue should not be closed")
+            .expect("message should be ok")
+    }
+
+    #[tokio::test]
+    async fn publish_and_consume() {
+        let message_queue = InMemoryMessageQueue::new();
+        let headers = HashMap::from([("key".to_string(), "value".to_string())]);
+        message_queue
+            .publish(QueueName::SingleNotify, b"first".to_vec(), headers.clone())
+            .await
+            .unwrap();
+        message_queue
+            .publish(QueueName::SingleNotify, b"second".to_vec(), HashMap::new())
+            .await
+            .unwrap();
+
+        let mut consumer = message_queue
+            .consume(QueueName::SingleNotify, "test")
+            .await
+            .unwrap();
+
+        // messages are received in order
+        let message = next_message(&mut consumer).await;
+        assert_eq!(message.queue, QueueName::SingleNotify);
+        assert_eq!(message.data, b"first".to_vec());
+        assert_eq!(message.headers, headers);
+        assert_eq!(message.delivery_tag, 1);
+        assert!(!message.redelivered);
+        message_queue.ack(&message).await.unwrap();
+
+        let message = next_message(&mut consumer).await;
+        assert_eq!(message.data, b"second".to_vec());
+        assert_eq!(message.delivery_tag, 2);
+    }
+
+    #[tokio::test]
+    async fn queues_are_isolated() {
+        let message_queue = InMemoryMessageQueue::new();
+        message_queue
+            .publish(QueueName::BatchNotify, b"batch".to_vec(), HashMap::new())
+            .await
+            .unwrap();
+
+        let mut consumer = message_queue
+            .consume(QueueName::SingleNotify, "test")
+            .await
+            .unwrap();
+        let result = tokio::time::timeout(Duration::from_millis(100), consumer.next()).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn nack_with_requeue_redelivers() {
+        let message_queue = InMemoryMessageQueue::new();
+        message_queue
+            .publish(QueueName::SingleNotify, b"retry".to_vec(), HashMap::new())
+            .await
+            .unwrap();
+
+        let mut consumer = message_queue
+            .consume(QueueName::SingleNotify, "test")
+            .await
+            .unwrap();
+        let message = next_message(&mut consumer).await;
+        message_queue.nack(&message, true).await.unwrap();
+
+        let redelivered = next_message(&mut consumer).await;
+        assert_eq!(redelivered.data, message.data);
+        assert_eq!(redelivered.delivery_tag, message.delivery_tag);
+        assert!(redelivered.redelivered);
+    }
+
+    #[tokio::test]
+    async fn nack_without_requeue_drops() {
+        let message_queue = InMemoryMessageQueue::new();
+        message_queue
+            .publish(QueueName::SingleNotify, b"drop".to_vec(), HashMap::new())
+            .await
+            .unwrap();
+
+        let mut consumer = message_queue
+            .consume(QueueName::SingleNotify, "test")
+            .await
+            .unwrap();
+        let message = next_message(&mut consumer).await;
+        message_queue.nack(&message, false).await.unwrap();
+
+        let result = tokio::time::timeout(Duration::from_millis(100), consumer.next()).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn publish_delayed_waits_for_delay() {
+        let message_queue = InMemoryMessageQueue::new();
+        message_queue
+            .publish_delayed(
+                QueueName::SingleNotify,
+                b"delayed".to_vec(),
+                HashMap::new(),
+                Duration::from_millis(200),
+            )
+            .await
+            .unwrap();
+
+        let mut consumer = message_queue
+            .consume(QueueName::SingleNotify, "test")
+            .await
+            .unwrap();
+        let result = tokio::time::timeout(Duration::from_millis(50), consumer.next()).await;
+        assert!(result.is_err());
+
+        let message = next_message(&mut consumer).await;
+        assert_eq!(message.data, b"delayed".to_vec());
+    }
+}
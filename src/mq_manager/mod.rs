@@ -1,7 +1,13 @@
+pub mod envelope;
 mod manager;
+mod memory_queue;
 mod model;
+mod queue;
+pub mod trace_context;
 
 pub use manager::*;
+pub use memory_queue::InMemoryMessageQueue;
 pub use model::BatchNotifyModel;
 pub use model::SingleNotifyModel;
 pub use model::TemplateModel;
+pub use queue::*;
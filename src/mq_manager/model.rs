@@ -19,25 +19,16 @@ pub struct SingleNotifyModel {
     pub receive_address: String,
     pub key_map: HashMap<String, String>,
     pub client_event_id: i64,
+    #[serde(default)]
+    pub expires_at: Option<i64>, // 過期時間(毫秒) 過期後不再發送
 }
 
-impl Default for SingleNotifyModel {
-    fn default() -> Self {
-        Self {
-            notify_id: 0,
-            client_id: 0,
-            user_id: 0,
-            sender_id: 0,
-            sender_account: "".to_string(),
-            sender_ip: None,
-            notify_type: enums::NotifyType::InApp,
-            notify_level: enums::NotifyLevel::Info,
-            title: "".to_string(),
-            content: "".to_string(),
-            receive_address: "".to_string(),
-            key_map: HashMap::new(),
-            client_event_id: 0,
-        }
+impl SingleNotifyModel {
+    /// 通知是否已過期
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.map_or(false, |expires_at| {
+            expires_at <= chrono::Utc::now().timestamp_millis()
+        })
     }
 }
 
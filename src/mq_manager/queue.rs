@@ -0,0 +1,145 @@
+use futures::{Stream, StreamExt};
+use once_cell::sync::OnceCell;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tonic::async_trait;
+
+pub type QueueError = Box<dyn std::error::Error + Send + Sync>;
+
+pub type MessageStream = Pin<Box<dyn Stream<Item = Result<QueueMessage, QueueError>> + Send>>;
+
+pub const RETRY_COUNT_HEADER: &str = "x-retry-count"; // 訊息已重試的次數
+pub const ORIGINAL_QUEUE_HEADER: &str = "x-original-queue"; // 移至dead letter queue前所在的queue
+pub const ERROR_MESSAGE_HEADER: &str = "x-error-message"; // 移至dead letter queue的錯誤原因
+pub const ERROR_CLASS_HEADER: &str = "x-error-class"; // 錯誤分類
+
+static MESSAGE_QUEUE: OnceCell<Arc<dyn MessageQueue>> = OnceCell::new();
+
+/// 通知使用的queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QueueName {
+    SingleNotify, // 對單一玩家通知的queue
+    BatchNotify,  // 對多玩家通知的queue
+    DeadLetter,   // 超過重試次數的訊息 不會被consume 供人工檢查
+}
+
+impl QueueName {
+    pub fn all() -> [QueueName; 3] {
+        [
+            QueueName::SingleNotify,
+            QueueName::BatchNotify,
+            QueueName::DeadLetter,
+        ]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QueueName::SingleNotify => "single_notify_queue",
+            QueueName::BatchNotify => "batch_notify_queue",
+            QueueName::DeadLetter => "notify_dead_letter_queue",
+        }
+    }
+}
+
+impl TryFrom<&str> for QueueName {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        QueueName::all()
+            .into_iter()
+            .find(|queue| queue.as_str() == value)
+            .ok_or(format!("unknown queue name: {}", value))
+    }
+}
+
+/// 從queue收到的訊息
+#[derive(Debug, Clone)]
+pub struct QueueMessage {
+    pub queue: QueueName,
+    pub delivery_tag: u64,
+    pub data: Vec<u8>,
+    pub headers: HashMap<String, String>,
+    pub redelivered: bool,
+    pub(super) acker: Option<lapin::acker::Acker>, // 只有rabbit_mq的訊息才有
+}
+
+impl QueueMessage {
+    /// 訊息已重試的次數 第一次收到時為0
+    pub fn retry_count(&self) -> u32 {
+        self.headers
+            .get(RETRY_COUNT_HEADER)
+            .and_then(|count| count.parse().ok())
+            .unwrap_or(0)
+    }
+}
+
+/// queue的消費者 依序取得queue中的訊息
+pub struct QueueConsumer {
+    consumer_tag: String,
+    stream: MessageStream,
+}
+
+impl QueueConsumer {
+    pub fn new(consumer_tag: &str, stream: MessageStream) -> Self {
+        QueueConsumer {
+            consumer_tag: consumer_tag.to_string(),
+            stream,
+        }
+    }
+
+    /// 等待下一筆訊息 回傳None代表queue已關閉
+    pub async fn next(&mut self) -> Option<Result<QueueMessage, QueueError>> {
+        self.stream.next().await
+    }
+}
+
+impl Debug for QueueConsumer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueueConsumer")
+            .field("consumer_tag", &self.consumer_tag)
+            .finish()
+    }
+}
+
+/// 訊息佇列的後端 rabbit_mq與in-memory各自實作
+#[async_trait]
+pub trait MessageQueue: Send + Sync + Debug {
+    async fn publish(
+        &self,
+        queue: QueueName,
+        payload: Vec<u8>,
+        headers: HashMap<String, String>,
+    ) -> Result<(), QueueError>;
+    /// 延遲一段時間後才送入queue 用於重試的退避 避免錯誤時立即重送造成空轉
+    async fn publish_delayed(
+        &self,
+        queue: QueueName,
+        payload: Vec<u8>,
+        headers: HashMap<String, String>,
+        delay: Duration,
+    ) -> Result<(), QueueError>;
+    async fn consume(
+        &self,
+        queue: QueueName,
+        consumer_tag: &str,
+    ) -> Result<QueueConsumer, QueueError>;
+    async fn ack(&self, message: &QueueMessage) -> Result<(), QueueError>;
+    async fn nack(&self, message: &QueueMessage, requeue: bool) -> Result<(), QueueError>;
+}
+
+/// 設定全域使用的queue後端 只能設定一次
+pub fn set_message_queue(message_queue: Arc<dyn MessageQueue>) {
+    MESSAGE_QUEUE
+        .set(message_queue)
+        .expect("Failed to set message queue");
+}
+
+pub fn get_message_queue() -> Arc<dyn MessageQueue> {
+    MESSAGE_QUEUE
+        .get()
+        .expect("please init message queue first")
+        .clone()
+}
@@ -0,0 +1,113 @@
+use crate::enums;
+use protos::{backstage_notify, frontend_notify};
+
+/// token所屬的身份
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub client_id: i64,
+    pub user_id: i64,
+    pub role_ids: Vec<i64>, // 後台使用者的角色
+    pub kind: IdentityKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdentityKind {
+    User(enums::Platform), // 前台用戶或後台使用者
+    Service,               // 其他服務 以client credentials取得的token
+}
+
+/// 以token的身份取代請求中由呼叫端帶入的用戶
+pub trait ApplyIdentity {
+    fn apply_identity(&mut self, identity: &Identity);
+}
+
+// 請求中的client_id與user_id
+macro_rules! apply_user_identity {
+    ($($request:ty),* $(,)?) => {
+        $(
+            impl ApplyIdentity for $request {
+                fn apply_identity(&mut self, identity: &Identity) {
+                    self.client_id = identity.client_id;
+                    self.user_id = identity.user_id;
+                }
+            }
+        )*
+    };
+}
+
+// 請求中的client_id與發送者sender_id
+macro_rules! apply_sender_identity {
+    ($($request:ty),* $(,)?) => {
+        $(
+            impl ApplyIdentity for $request {
+                fn apply_identity(&mut self, identity: &Identity) {
+                    self.client_id = identity.client_id;
+                    self.sender_id = identity.user_id;
+                }
+            }
+        )*
+    };
+}
+
+// 請求中只有client_id
+macro_rules! apply_client_identity {
+    ($($request:ty),* $(,)?) => {
+        $(
+            impl ApplyIdentity for $request {
+                fn apply_identity(&mut self, identity: &Identity) {
+                    self.client_id = identity.client_id;
+                }
+            }
+        )*
+    };
+}
+
+apply_user_identity!(
+    frontend_notify::ConnectionRequest,
+    frontend_notify::GetNotifyRecordRequest,
+    frontend_notify::UpdateNotifyRecordRequest,
+    frontend_notify::GetUnreadNotifyCountRequest,
+    frontend_notify::AllReadRequest,
+    frontend_notify::GetNotifyByIdRequest,
+    frontend_notify::AckNotifyRequest,
+    frontend_notify::TopicSubscriptionRequest,
+    backstage_notify::CloseRequest,
+    backstage_notify::GetNotifyRecordRequest,
+    backstage_notify::UpdateNotifyRecordRequest,
+    backstage_notify::GetUnreadNotifyCountRequest,
+    backstage_notify::AllReadRequest,
+    backstage_notify::GetNotifyByIdRequest,
+    backstage_notify::UpdateClientEventRequest,
+    backstage_notify::CreateClientEventRequest,
+);
+
+apply_sender_identity!(
+    backstage_notify::BackstageSendToUserRequest,
+    backstage_notify::CreateRecurringCampaignRequest,
+);
+
+apply_client_identity!(
+    backstage_notify::GetUserNotifyRecordRequest,
+    backstage_notify::GetClientEventSummaryRequest,
+    backstage_notify::GetClientTemplatesRequest,
+    backstage_notify::GetClientEventRequest,
+    backstage_notify::DeleteClientEventRequest,
+    backstage_notify::UpdateRecurringCampaignRequest,
+    backstage_notify::DeleteRecurringCampaignRequest,
+    backstage_notify::GetRecurringCampaignDetailsRequest,
+    backstage_notify::GetNotifyTaskListRequest,
+    backstage_notify::GetNotifyTaskDetailsRequest,
+    backstage_notify::GetRecurringCampaignListRequest,
+    backstage_notify::GetOnlineStatusRequest,
+    backstage_notify::GetOnlineCountRequest,
+    backstage_notify::GetOnlineUserListRequest,
+);
+
+// 後台連線的角色也以token為準 不信任呼叫端帶入的role_ids
+impl ApplyIdentity for backstage_notify::ConnectionRequest {
+    fn apply_identity(&mut self, identity: &Identity) {
+        self.client_id = identity.client_id;
+        self.user_id = identity.user_id;
+        self.role_ids = identity.role_ids.clone();
+    }
+}
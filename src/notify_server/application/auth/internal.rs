@@ -0,0 +1,137 @@
+use crate::config::config;
+use futures::future::BoxFuture;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::codegen::http;
+use tower::{Layer, Service};
+
+/// pod之間呼叫時帶入共用密鑰的header
+const INTERNAL_SECRET_HEADER: &str = "x-internal-secret";
+
+/// pod之間轉發用的方法 只在內部grpc服務開放
+const INTERNAL_METHODS: [&str; 5] = [
+    "ForwardNotify",
+    "ForwardRevokeSessions",
+    "ForwardRecordsChanged",
+    "ForwardTopicSubscription",
+    "ForwardTopicPublish",
+];
+
+/// 是否啟動內部grpc服務 未設定共用密鑰時不開放轉發
+pub fn is_internal_enabled() -> bool {
+    !config::get_internal().internal_secret.is_empty()
+}
+
+fn is_internal_method(path: &str) -> bool {
+    path.rsplit('/')
+        .next()
+        .map_or(false, |method| INTERNAL_METHODS.contains(&method))
+}
+
+/// 依所在的grpc服務限制可呼叫的方法
+/// 對外服務拒絕轉發用的方法 內部服務只接受轉發用的方法
+#[derive(Debug, Clone, Copy)]
+pub struct InternalMethodLayer {
+    internal: bool,
+}
+
+impl InternalMethodLayer {
+    /// 用於對外的grpc服務
+    pub fn public() -> Self {
+        InternalMethodLayer { internal: false }
+    }
+
+    /// 用於pod之間的內部grpc服務
+    pub fn internal() -> Self {
+        InternalMethodLayer { internal: true }
+    }
+}
+
+impl<S> Layer<S> for InternalMethodLayer {
+    type Service = InternalMethodService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        InternalMethodService {
+            inner,
+            internal: self.internal,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InternalMethodService<S> {
+    inner: S,
+    internal: bool,
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for InternalMethodService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<ReqBody>) -> Self::Future {
+        // 使用已ready的service 留下複本給下一個請求
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let internal = self.internal;
+
+        Box::pin(async move {
+            if is_internal_method(request.uri().path()) != internal {
+                return Ok(tonic::Status::permission_denied("method not allowed").to_http());
+            }
+
+            inner.call(request).await
+        })
+    }
+}
+
+/// 內部grpc服務的interceptor 檢查呼叫方帶入的共用密鑰
+pub fn check_internal_secret(
+    request: tonic::Request<()>,
+) -> Result<tonic::Request<()>, tonic::Status> {
+    let secret = &config::get_internal().internal_secret;
+    let provided = request
+        .metadata()
+        .get(INTERNAL_SECRET_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    if secret.is_empty() || !constant_time_eq(secret.as_bytes(), provided.as_bytes()) {
+        return Err(tonic::Status::unauthenticated("invalid internal secret"));
+    }
+
+    Ok(request)
+}
+
+/// 呼叫其他pod時的interceptor 帶入共用密鑰
+pub fn attach_internal_secret(
+    mut request: tonic::Request<()>,
+) -> Result<tonic::Request<()>, tonic::Status> {
+    let secret = config::get_internal()
+        .internal_secret
+        .parse()
+        .map_err(|_| tonic::Status::internal("invalid internal secret"))?;
+    request
+        .metadata_mut()
+        .insert(INTERNAL_SECRET_HEADER, secret);
+
+    Ok(request)
+}
+
+/// 比對時間不受相同前綴長度影響 避免以回應時間猜出密鑰
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -0,0 +1,153 @@
+use super::{Identity, IdentityKind, TokenVerifier};
+use crate::enums;
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use kgs_tracing::warn;
+use serde::Deserialize;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tonic::async_trait;
+
+const MIN_REFRESH_INTERVAL_SECS: u64 = 30; // 找不到kid時重新取得公鑰的最短間隔
+
+/// jwt中的身份
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String, // user_id
+    client_id: i64,
+    #[serde(default)]
+    platform: Option<i32>, // 沒有平台時為服務間呼叫的token
+    #[serde(default)]
+    role_ids: Vec<i64>,
+}
+
+/// 以jwks公鑰在本地驗證jwt 公鑰輪替時自動重新取得
+#[derive(Debug)]
+pub struct JwksVerifier {
+    jwks_url: String,
+    issuer: Option<String>,
+    audience: Option<String>,
+    algorithm: Option<Algorithm>, // 公鑰未指定alg時使用的演算法
+    jwks: RwLock<Option<(Instant, JwkSet)>>, // 取得時間與公鑰
+}
+
+impl JwksVerifier {
+    pub fn new(
+        jwks_url: String,
+        issuer: Option<String>,
+        audience: Option<String>,
+        algorithm: Option<&str>,
+    ) -> Self {
+        let algorithm = algorithm.and_then(|algorithm| {
+            Algorithm::from_str(algorithm)
+                .inspect_err(|_| warn!("invalid jwt algorithm: {}", algorithm))
+                .ok()
+        });
+
+        JwksVerifier {
+            jwks_url,
+            issuer,
+            audience,
+            algorithm,
+            jwks: RwLock::new(None),
+        }
+    }
+
+    /// 取得公鑰與驗證用的演算法 演算法以公鑰或設定為準 不使用token header中的alg
+    fn to_decoding_key(&self, jwk: &Jwk) -> Result<(DecodingKey, Algorithm), tonic::Status> {
+        let algorithm = match &jwk.common.key_algorithm {
+            Some(key_algorithm) => Algorithm::from_str(&key_algorithm.to_string())
+                .map_err(|_| tonic::Status::unauthenticated("unsupported jwk algorithm"))?,
+            None => self
+                .algorithm
+                .ok_or_else(|| tonic::Status::unauthenticated("missing jwk algorithm"))?,
+        };
+        let decoding_key = DecodingKey::from_jwk(jwk)
+            .map_err(|_| tonic::Status::unauthenticated("invalid jwk"))?;
+
+        Ok((decoding_key, algorithm))
+    }
+
+    /// 取得kid對應的公鑰 快取中沒有時重新取得
+    async fn get_decoding_key(&self, kid: &str) -> Result<(DecodingKey, Algorithm), tonic::Status> {
+        if let Some((_, jwks)) = self.jwks.read().await.as_ref() {
+            if let Some(jwk) = jwks.find(kid) {
+                return self.to_decoding_key(jwk);
+            }
+        }
+
+        let mut cache = self.jwks.write().await;
+        let should_refresh = cache.as_ref().map_or(true, |(fetched_at, _)| {
+            fetched_at.elapsed() >= Duration::from_secs(MIN_REFRESH_INTERVAL_SECS)
+        });
+        if should_refresh {
+            match self.fetch_jwks().await {
+                Ok(jwks) => *cache = Some((Instant::now(), jwks)),
+                Err(err) => warn!("fetch jwks failed: {}", err),
+            }
+        }
+
+        let jwk = cache
+            .as_ref()
+            .and_then(|(_, jwks)| jwks.find(kid))
+            .ok_or_else(|| tonic::Status::unauthenticated("unknown kid"))?;
+
+        self.to_decoding_key(jwk)
+    }
+
+    async fn fetch_jwks(&self) -> Result<JwkSet, reqwest::Error> {
+        reqwest::get(&self.jwks_url).await?.json::<JwkSet>().await
+    }
+}
+
+#[async_trait]
+impl TokenVerifier for JwksVerifier {
+    async fn verify(&self, token: &str) -> Result<Identity, tonic::Status> {
+        let header = jsonwebtoken::decode_header(token)
+            .map_err(|_| tonic::Status::unauthenticated("invalid token"))?;
+        let kid = header
+            .kid
+            .ok_or_else(|| tonic::Status::unauthenticated("missing kid"))?;
+        let (decoding_key, algorithm) = self.get_decoding_key(&kid).await?;
+
+        // 拒絕header中的alg與公鑰不符的token 避免以其他演算法偽造簽章
+        if header.alg != algorithm {
+            return Err(tonic::Status::unauthenticated("invalid token algorithm"));
+        }
+
+        let mut validation = Validation::new(algorithm);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        let claims = jsonwebtoken::decode::<Claims>(token, &decoding_key, &validation)
+            .map_err(|err| {
+                warn!("verify jwt failed: {}", err);
+                tonic::Status::unauthenticated("invalid token")
+            })?
+            .claims;
+
+        let kind = match claims.platform {
+            Some(platform) => IdentityKind::User(
+                enums::Platform::try_from(platform)
+                    .map_err(|_| tonic::Status::unauthenticated("invalid token platform"))?,
+            ),
+            None => IdentityKind::Service,
+        };
+
+        Ok(Identity {
+            client_id: claims.client_id,
+            user_id: claims
+                .sub
+                .parse()
+                .map_err(|_| tonic::Status::unauthenticated("invalid token subject"))?,
+            role_ids: claims.role_ids,
+            kind,
+        })
+    }
+}
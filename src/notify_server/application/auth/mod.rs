@@ -0,0 +1,177 @@
+mod identity;
+mod internal;
+mod jwks;
+mod layer;
+mod oauth;
+
+use crate::config::config::{self, AuthMode};
+use crate::enums;
+use dashmap::DashMap;
+use jwks::JwksVerifier;
+use kgs_tracing::info;
+use oauth::OauthVerifier;
+use once_cell::sync::Lazy;
+use std::fmt::Debug;
+use std::time::{Duration, Instant};
+use tonic::async_trait;
+
+pub use identity::{ApplyIdentity, Identity, IdentityKind};
+pub use internal::{
+    attach_internal_secret, check_internal_secret, is_internal_enabled, InternalMethodLayer,
+};
+pub use layer::AuthLayer;
+
+const MAX_CACHE_SIZE: usize = 10000; // 超過時清除已過期的快取
+
+static TOKEN_VERIFIER: Lazy<CachedTokenVerifier> = Lazy::new(|| {
+    let auth_config = config::get_auth();
+    CachedTokenVerifier::new(
+        new_token_verifier(),
+        Duration::from_secs(auth_config.auth_cache_secs),
+    )
+});
+
+/// 驗證bearer token 並取得token所屬的身份
+#[async_trait]
+pub trait TokenVerifier: Send + Sync + Debug {
+    async fn verify(&self, token: &str) -> Result<Identity, tonic::Status>;
+}
+
+/// 快取驗證結果 避免每個請求都呼叫oauth服務
+#[derive(Debug)]
+pub struct CachedTokenVerifier {
+    verifier: Box<dyn TokenVerifier>,
+    ttl: Duration,
+    cache: DashMap<String, (Instant, Identity)>, // key: token
+}
+
+impl CachedTokenVerifier {
+    pub fn new(verifier: Box<dyn TokenVerifier>, ttl: Duration) -> Self {
+        CachedTokenVerifier {
+            verifier,
+            ttl,
+            cache: DashMap::new(),
+        }
+    }
+
+    pub async fn verify(&self, token: &str) -> Result<Identity, tonic::Status> {
+        if let Some(cached) = self.cache.get(token) {
+            let (verified_at, identity) = cached.value();
+            if verified_at.elapsed() < self.ttl {
+                return Ok(identity.clone());
+            }
+        }
+
+        let identity = self.verifier.verify(token).await?;
+
+        if self.cache.len() >= MAX_CACHE_SIZE {
+            self.cache
+                .retain(|_, (verified_at, _)| verified_at.elapsed() < self.ttl);
+        }
+        self.cache
+            .insert(token.to_string(), (Instant::now(), identity.clone()));
+
+        Ok(identity)
+    }
+}
+
+/// 依設定建立token的驗證方式
+fn new_token_verifier() -> Box<dyn TokenVerifier> {
+    let auth_config = config::get_auth();
+    info!("auth mode: {:?}", auth_config.auth_mode);
+
+    match auth_config.auth_mode {
+        AuthMode::Oauth => Box::new(OauthVerifier),
+        AuthMode::Jwks => Box::new(JwksVerifier::new(
+            auth_config.auth_jwks_url.clone(),
+            auth_config.auth_jwt_issuer.clone(),
+            auth_config.auth_jwt_audience.clone(),
+            auth_config.auth_jwt_algorithm.as_deref(),
+        )),
+    }
+}
+
+pub fn is_enabled() -> bool {
+    config::get_auth().auth_enabled
+}
+
+/// 驗證authorization header中的bearer token
+pub async fn verify_bearer(authorization: Option<&str>) -> Result<Identity, tonic::Status> {
+    let token = authorization
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim())
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| tonic::Status::unauthenticated("missing bearer token"))?;
+
+    verify_token(token).await
+}
+
+pub async fn verify_token(token: &str) -> Result<Identity, tonic::Status> {
+    TOKEN_VERIFIER.verify(token).await
+}
+
+/// 以token的身份取代請求中的用戶 服務間呼叫的token則沿用請求中的用戶
+pub fn authorize<T: ApplyIdentity>(
+    request: tonic::Request<T>,
+    platform: &enums::Platform,
+) -> Result<T, tonic::Status> {
+    if !is_enabled() {
+        return Ok(request.into_inner());
+    }
+
+    let identity = get_identity(&request)?;
+    let mut inner = request.into_inner();
+    match &identity.kind {
+        IdentityKind::Service => {}
+        IdentityKind::User(user_platform) if user_platform == platform => {
+            inner.apply_identity(&identity);
+        }
+        IdentityKind::User(_) => {
+            return Err(tonic::Status::permission_denied("platform not allowed"));
+        }
+    }
+
+    Ok(inner)
+}
+
+/// 僅允許其他服務呼叫 例如系統通知與強制下線
+pub fn authorize_service<T>(request: tonic::Request<T>) -> Result<T, tonic::Status> {
+    if !is_enabled() {
+        return Ok(request.into_inner());
+    }
+
+    match get_identity(&request)?.kind {
+        IdentityKind::Service => Ok(request.into_inner()),
+        IdentityKind::User(_) => Err(tonic::Status::permission_denied(
+            "only service token allowed",
+        )),
+    }
+}
+
+/// 允許其他服務或指定平台的使用者呼叫 回傳使用者的身份 由呼叫端決定如何限制範圍
+/// 服務間呼叫或未啟用驗證時身份為None
+pub fn authorize_service_or_user<T>(
+    request: tonic::Request<T>,
+    platform: &enums::Platform,
+) -> Result<(T, Option<Identity>), tonic::Status> {
+    if !is_enabled() {
+        return Ok((request.into_inner(), None));
+    }
+
+    let identity = get_identity(&request)?;
+    match &identity.kind {
+        IdentityKind::Service => Ok((request.into_inner(), None)),
+        IdentityKind::User(user_platform) if user_platform == platform => {
+            Ok((request.into_inner(), Some(identity)))
+        }
+        IdentityKind::User(_) => Err(tonic::Status::permission_denied("platform not allowed")),
+    }
+}
+
+fn get_identity<T>(request: &tonic::Request<T>) -> Result<Identity, tonic::Status> {
+    request
+        .extensions()
+        .get::<Identity>()
+        .cloned()
+        .ok_or_else(|| tonic::Status::unauthenticated("missing identity"))
+}
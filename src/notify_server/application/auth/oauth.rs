@@ -0,0 +1,34 @@
+use super::{Identity, IdentityKind, TokenVerifier};
+use crate::enums;
+use crate::notify_server::application::oauth_rpc;
+use kgs_tracing::warn;
+use tonic::async_trait;
+
+/// 透過oauth服務驗證token
+#[derive(Debug)]
+pub struct OauthVerifier;
+
+#[async_trait]
+impl TokenVerifier for OauthVerifier {
+    async fn verify(&self, token: &str) -> Result<Identity, tonic::Status> {
+        let response = oauth_rpc::verify_token(token).await.map_err(|err| {
+            warn!("verify token by oauth failed: {}", err);
+            tonic::Status::unauthenticated("invalid token")
+        })?;
+
+        let kind = if response.is_service {
+            IdentityKind::Service
+        } else {
+            let platform = enums::Platform::try_from(response.platform)
+                .map_err(|_| tonic::Status::unauthenticated("invalid token platform"))?;
+            IdentityKind::User(platform)
+        };
+
+        Ok(Identity {
+            client_id: response.client_id,
+            user_id: response.user_id,
+            role_ids: response.role_ids,
+            kind,
+        })
+    }
+}
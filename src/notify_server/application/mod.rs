@@ -1,6 +1,10 @@
-pub mod kube_api;
+pub mod auth;
+pub mod notify_bus;
 pub mod notify_handler;
 mod oauth_server;
+pub mod peer_discovery;
+pub mod pod_channel;
+pub mod stream_sender;
 mod user_server;
 
 pub use oauth_server::oauth_rpc;
@@ -0,0 +1,378 @@
+use crate::config::config;
+use crate::enums;
+use crate::notify_server::{BACKSTAGE_NOTIFY_SERVER, FRONTEND_NOTIFY_SERVER};
+use futures::StreamExt;
+use kgs_err::models::status::Status as KgsStatus;
+use kgs_tracing::{info, tracing, warn};
+use protos::frontend_notify;
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const BUS_CHANNEL_PREFIX: &str = "notify_server:bus";
+const RECONNECT_INTERVAL_SECS: u64 = 3;
+
+/// pod之間透過redis pub/sub轉發的訊息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BusMessage {
+    pub origin: String, // 發送訊息的pod
+    pub event: BusEvent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BusEvent {
+    // 發送給前台用戶的站內信
+    FrontendNotify {
+        client_id: i64,
+        user_id: i64,
+        notify_id: i64,
+        notify_level: i32,
+        title: String,
+        content: String,
+    },
+    // 廣播給後台使用者的站內信
+    BackstageNotify {
+        client_id: i64,
+        role_ids: Vec<i64>,
+        client_notify_event_id: i64,
+        title: String,
+        content: String,
+    },
+    // 前台用戶的通知狀態已變更
+    FrontendRecordsChanged {
+        client_id: i64,
+        user_id: i64,
+        notify_ids: Vec<i64>,
+        notify_status: i32,
+        unread_count: u64,
+    },
+    // 後台使用者的通知狀態已變更
+    BackstageRecordsChanged {
+        client_id: i64,
+        user_id: i64,
+        notify_ids: Vec<i64>,
+        notify_status: i32,
+        unread_count: u64,
+    },
+    // 更新前台用戶在此pod上連線的主題訂閱
+    TopicSubscription {
+        client_id: i64,
+        user_id: i64,
+        session_id: Option<String>,
+        topics: Vec<String>,
+        is_subscribe: bool,
+    },
+    // 發送給訂閱主題的前台用戶
+    TopicPublish {
+        client_id: i64,
+        topic: String,
+        notify_level: i32,
+        title: String,
+        content: String,
+        persist: bool,
+        client_event_id: Option<i64>,
+    },
+    // 強制關閉前台用戶在此pod上的所有連線
+    RevokeSessions {
+        client_id: i64,
+        user_id: i64,
+        reason: String,
+    },
+}
+
+/// 是否使用redis pub/sub轉發 關閉時以grpc直接連線其他pod
+pub fn is_enabled() -> bool {
+    config::get_redis().redis_notify_bus
+}
+
+/// 每個pod各自訂閱的channel
+fn get_pod_channel(pod_id: &str) -> String {
+    format!("{}:{}", BUS_CHANNEL_PREFIX, pod_id)
+}
+
+/// 所有pod都會訂閱的廣播channel
+fn get_broadcast_channel() -> String {
+    format!("{}:broadcast", BUS_CHANNEL_PREFIX)
+}
+
+fn get_pod_id() -> &'static str {
+    &config::get_kubernetes().pod_ip
+}
+
+/// 發送訊息給指定的pod
+#[tracing::instrument]
+pub fn publish_to_pod(pod_id: &str, event: BusEvent) -> Result<(), KgsStatus> {
+    publish(&get_pod_channel(pod_id), event)
+}
+
+/// 廣播訊息給其他所有pod 自己發出的廣播不會再處理
+#[tracing::instrument]
+pub fn broadcast(event: BusEvent) -> Result<(), KgsStatus> {
+    publish(&get_broadcast_channel(), event)
+}
+
+fn publish(channel: &str, event: BusEvent) -> Result<(), KgsStatus> {
+    let payload = serde_json::to_string(&BusMessage {
+        origin: get_pod_id().to_string(),
+        event,
+    })
+    .map_err(|e| {
+        warn!("serialize bus message failed: {}", e);
+        KgsStatus::InternalServerError
+    })?;
+
+    let mut redis_conn = database_manager::redis::RedisManager::get_conn();
+    redis_conn
+        .publish::<_, _, i64>(channel, payload)
+        .map_err(|e| {
+            warn!("publish bus message to {} failed: {}", channel, e);
+            KgsStatus::InternalServerError
+        })?;
+
+    Ok(())
+}
+
+/// 啟動訂閱 連線中斷時會自動重新訂閱
+pub fn start() -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = subscribe().await {
+                warn!("notify bus subscribe failed: {}", e);
+            }
+            tokio::time::sleep(Duration::from_secs(RECONNECT_INTERVAL_SECS)).await;
+        }
+    })
+}
+
+/// 以設定組出連線資訊 不經過url 密碼中的特殊字元不需跳脫
+fn get_connection_info(redis_config: &config::Redis) -> redis::ConnectionInfo {
+    let password = Some(redis_config.redis_auth.clone()).filter(|auth| !auth.is_empty());
+    redis::ConnectionInfo {
+        addr: redis::ConnectionAddr::Tcp(
+            redis_config.redis_host.clone(),
+            redis_config.redis_port as u16,
+        ),
+        redis: redis::RedisConnectionInfo {
+            db: redis_config.redis_database as i64,
+            password,
+            ..Default::default()
+        },
+    }
+}
+
+async fn subscribe() -> Result<(), redis::RedisError> {
+    let connection_info = get_connection_info(config::get_redis());
+
+    // pub/sub需要獨立的連線 不能使用連線池中的連線
+    let client = redis::Client::open(connection_info)?;
+    let mut pubsub = client.get_async_pubsub().await?;
+    pubsub.subscribe(get_pod_channel(get_pod_id())).await?;
+    pubsub.subscribe(get_broadcast_channel()).await?;
+    info!("notify bus subscribed, pod: {}", get_pod_id());
+
+    let mut messages = pubsub.on_message();
+    while let Some(msg) = messages.next().await {
+        let payload: String = match msg.get_payload() {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("get bus message payload failed: {}", e);
+                continue;
+            }
+        };
+
+        let bus_message: BusMessage = match serde_json::from_str(&payload) {
+            Ok(bus_message) => bus_message,
+            Err(e) => {
+                warn!("deserialize bus message failed: {} payload: {}", e, payload);
+                continue;
+            }
+        };
+
+        // 自己發出的廣播已在本機處理過
+        if bus_message.origin == get_pod_id() {
+            continue;
+        }
+
+        // 逐筆處理 避免阻塞訂閱
+        tokio::spawn(handle_event(bus_message.event));
+    }
+
+    Ok(())
+}
+
+#[tracing::instrument]
+async fn handle_event(event: BusEvent) {
+    let result = match event {
+        BusEvent::FrontendNotify {
+            client_id,
+            user_id,
+            notify_id,
+            notify_level,
+            title,
+            content,
+        } => match enums::NotifyLevel::try_from(notify_level) {
+            Ok(notify_level) => {
+                FRONTEND_NOTIFY_SERVER
+                    .handle_notify(
+                        client_id,
+                        user_id,
+                        notify_id,
+                        notify_level,
+                        &title,
+                        &content,
+                    )
+                    .await
+            }
+            Err(e) => Err(e),
+        },
+        BusEvent::BackstageNotify {
+            client_id,
+            role_ids,
+            client_notify_event_id,
+            title,
+            content,
+        } => {
+            BACKSTAGE_NOTIFY_SERVER
+                .handle_notify(
+                    client_id,
+                    &role_ids,
+                    enums::NotifyType::InApp,
+                    client_notify_event_id,
+                    &title,
+                    &content,
+                )
+                .await
+        }
+        BusEvent::FrontendRecordsChanged {
+            client_id,
+            user_id,
+            notify_ids,
+            notify_status,
+            unread_count,
+        } => match enums::NotifyStatus::try_from(notify_status) {
+            Ok(notify_status) => {
+                FRONTEND_NOTIFY_SERVER
+                    .handle_records_changed(
+                        client_id,
+                        user_id,
+                        &notify_ids,
+                        notify_status,
+                        unread_count,
+                    )
+                    .await
+            }
+            Err(e) => Err(e),
+        },
+        BusEvent::BackstageRecordsChanged {
+            client_id,
+            user_id,
+            notify_ids,
+            notify_status,
+            unread_count,
+        } => match enums::NotifyStatus::try_from(notify_status) {
+            Ok(notify_status) => {
+                BACKSTAGE_NOTIFY_SERVER
+                    .handle_records_changed(
+                        client_id,
+                        user_id,
+                        &notify_ids,
+                        notify_status,
+                        unread_count,
+                    )
+                    .await
+            }
+            Err(e) => Err(e),
+        },
+        BusEvent::TopicSubscription {
+            client_id,
+            user_id,
+            session_id,
+            topics,
+            is_subscribe,
+        } => {
+            FRONTEND_NOTIFY_SERVER.handle_topic_subscription(
+                &frontend_notify::TopicSubscriptionRequest {
+                    client_id,
+                    user_id,
+                    session_id,
+                    topics,
+                },
+                is_subscribe,
+            );
+            Ok(())
+        }
+        BusEvent::TopicPublish {
+            client_id,
+            topic,
+            notify_level,
+            title,
+            content,
+            persist,
+            client_event_id,
+        } => {
+            FRONTEND_NOTIFY_SERVER
+                .handle_topic_publish(&frontend_notify::PublishToTopicRequest {
+                    client_id,
+                    topic,
+                    notify_level,
+                    title,
+                    content,
+                    persist,
+                    client_event_id,
+                })
+                .await
+        }
+        BusEvent::RevokeSessions {
+            client_id,
+            user_id,
+            reason,
+        } => {
+            FRONTEND_NOTIFY_SERVER
+                .revoke_local_sessions(client_id, user_id, &reason)
+                .await
+        }
+    };
+
+    if let Err(e) = result {
+        warn!("handle bus event failed: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redis_config(redis_auth: &str) -> config::Redis {
+        config::Redis {
+            redis_host: "redis.local".to_string(),
+            redis_port: 6380,
+            redis_auth: redis_auth.to_string(),
+            redis_database: 2,
+            redis_max_size: 10,
+            redis_min_idle: 1,
+            redis_connection_timeout: 3,
+            redis_notify_bus: true,
+        }
+    }
+
+    #[test]
+    fn password_with_reserved_characters() {
+        let connection_info = get_connection_info(&redis_config("p@ss:w/rd#1"));
+        assert_eq!(
+            connection_info.addr,
+            redis::ConnectionAddr::Tcp("redis.local".to_string(), 6380)
+        );
+        assert_eq!(connection_info.redis.db, 2);
+        assert_eq!(
+            connection_info.redis.password,
+            Some("p@ss:w/rd#1".to_string())
+        );
+    }
+
+    #[test]
+    fn empty_password_is_none() {
+        let connection_info = get_connection_info(&redis_config(""));
+        assert_eq!(connection_info.redis.password, None);
+    }
+}
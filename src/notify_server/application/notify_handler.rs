@@ -1,15 +1,17 @@
 use crate::config;
 use crate::enums;
+use crate::notify_server::model;
 use crate::repository;
 use kgs_err::models::status::Status as KgsStatus;
 use kgs_tracing::tracing;
 use kgs_tracing::warn;
 use redis::Commands;
 use std::collections::HashMap;
-use std::collections::HashSet;
 
 const REDIS_KEY: &str = "notify_server";
 const USER_EXPIRE_SECS: usize = 60 * 60 * 24 * 7;
+const USER_PRESENCE_EXPIRE_SECS: usize = 60; // 用戶所在pod的過期時間 連線存在時由心跳持續延長
+const POD_HEARTBEAT_EXPIRE_SECS: usize = 30; // pod心跳的過期時間 過期代表pod已失效
 
 #[tracing::instrument]
 pub async fn is_platform_has_event<C>(
@@ -35,12 +37,6 @@ where
     }
 }
 
-#[tracing::instrument]
-pub fn has_common_role(a: &Vec<i64>, b: &Vec<i64>) -> bool {
-    let set_a: HashSet<_> = a.iter().cloned().collect();
-    b.iter().any(|x| set_a.contains(x))
-}
-
 #[tracing::instrument]
 pub fn replace_title_and_content(
     title: &str,
@@ -116,40 +112,419 @@ pub fn get_receive_address_opt(
     }
 }
 
+/// 用戶所在的pod 同一用戶可能在多個pod上都有連線 以set保存
+/// 不同client可能有相同的user_id 需以client_id區分
+fn get_user_located_servers_key(client_id: i64, user_id: i64) -> String {
+    format!("{}:pods:{}:{}", REDIS_KEY, client_id, user_id)
+}
+
+/// pod的心跳 存在代表pod仍在運作
+fn get_pod_heartbeat_key(pod_ip: &str) -> String {
+    format!("{}:heartbeat:{}", REDIS_KEY, pod_ip)
+}
+
+/// 各平台各client用戶最後在線的時間(毫秒)
+fn get_user_last_seen_key(platform: &enums::Platform, client_id: i64, user_id: i64) -> String {
+    format!(
+        "{}:last_seen:{}:{}:{}",
+        REDIS_KEY,
+        platform.to_id(),
+        client_id,
+        user_id
+    )
+}
+
+/// 各平台各client的在線用戶 以sorted set保存 score為最後一次心跳的時間(毫秒)
+fn get_online_users_key(platform: &enums::Platform, client_id: i64) -> String {
+    format!("{}:online:{}:{}", REDIS_KEY, platform.to_id(), client_id)
+}
+
+/// 在線用戶最後一次心跳的時間需晚於此時間
+fn get_online_threshold() -> i64 {
+    chrono::Utc::now().timestamp_millis() - (USER_PRESENCE_EXPIRE_SECS * 1000) as i64
+}
+
 #[tracing::instrument]
-pub fn save_user_located_server_to_redis(user_id: i64) -> Result<(), KgsStatus> {
-    let key = format!("{}:{}", REDIS_KEY, user_id);
+pub fn save_user_located_server_to_redis(user: &model::User) -> Result<(), KgsStatus> {
+    refresh_user_located_servers(std::slice::from_ref(user))
+}
+
+/// 延長用戶在此pod上的存在時間 並更新最後在線時間
+#[tracing::instrument(skip(users))]
+pub fn refresh_user_located_servers(users: &[model::User]) -> Result<(), KgsStatus> {
+    if users.is_empty() {
+        return Ok(());
+    }
+
     let value = &config::config::get_kubernetes().pod_ip;
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut pipe = redis::pipe();
+    for user in users {
+        let key = get_user_located_servers_key(user.client_id, user.user_id);
+        pipe.sadd(&key, value)
+            .ignore()
+            .expire(&key, USER_PRESENCE_EXPIRE_SECS as i64)
+            .ignore()
+            .set_ex(
+                get_user_last_seen_key(&enums::Platform::Frontend, user.client_id, user.user_id),
+                now,
+                USER_EXPIRE_SECS as u64,
+            )
+            .ignore()
+            .zadd(
+                get_online_users_key(&enums::Platform::Frontend, user.client_id),
+                user.user_id,
+                now,
+            )
+            .ignore();
+    }
+
     let mut redis_conn = database_manager::redis::RedisManager::get_conn();
-    redis_conn
-        .set_ex(key, value, USER_EXPIRE_SECS as u64)
+    pipe.query::<()>(&mut *redis_conn).map_err(|e| {
+        warn!("save user to redis failed: {}", e);
+        KgsStatus::InternalServerError
+    })?;
+
+    Ok(())
+}
+
+/// 取得用戶所在的pod 心跳已過期的pod會被移除
+#[tracing::instrument]
+pub fn get_user_located_servers_from_redis(
+    client_id: i64,
+    user_id: i64,
+) -> Result<Vec<String>, KgsStatus> {
+    let key = get_user_located_servers_key(client_id, user_id);
+    let mut redis_conn = database_manager::redis::RedisManager::get_conn();
+    let pod_ips: Vec<String> = redis_conn.smembers(&key).map_err(|e| {
+        warn!("get user location server from redis failed: {}", e);
+        KgsStatus::InternalServerError
+    })?;
+    if pod_ips.is_empty() {
+        return Ok(pod_ips);
+    }
+
+    // check the heartbeat of the pods
+    let own_pod_ip = &config::config::get_kubernetes().pod_ip;
+    let mut pipe = redis::pipe();
+    for pod_ip in pod_ips.iter() {
+        pipe.exists(get_pod_heartbeat_key(pod_ip));
+    }
+    let alive_flags: Vec<bool> = pipe.query(&mut *redis_conn).map_err(|e| {
+        warn!("get pod heartbeat from redis failed: {}", e);
+        KgsStatus::InternalServerError
+    })?;
+
+    let (alive_pod_ips, dead_pod_ips): (Vec<(String, bool)>, Vec<(String, bool)>) = pod_ips
+        .into_iter()
+        .zip(alive_flags)
+        .partition(|(pod_ip, is_alive)| *is_alive || pod_ip == own_pod_ip);
+
+    // remove the dead pods
+    if !dead_pod_ips.is_empty() {
+        let dead_pod_ips: Vec<String> =
+            dead_pod_ips.into_iter().map(|(pod_ip, _)| pod_ip).collect();
+        warn!("remove dead pods of user {}: {:?}", user_id, dead_pod_ips);
+        for pod_ip in dead_pod_ips.iter() {
+            super::pod_channel::evict(pod_ip);
+        }
+        redis_conn
+            .srem::<_, _, ()>(&key, dead_pod_ips)
+            .map_err(|e| {
+                warn!("remove dead pods from redis failed: {}", e);
+                KgsStatus::InternalServerError
+            })?;
+    }
+
+    Ok(alive_pod_ips
+        .into_iter()
+        .map(|(pod_ip, _)| pod_ip)
+        .collect())
+}
+
+/// 用戶在此pod上已沒有任何連線時 將此pod從用戶所在的pod中移除
+/// 用戶在其他pod上也沒有連線時 從在線用戶中移除
+#[tracing::instrument]
+pub fn remove_user_located_server_from_redis(user: &model::User) -> Result<(), KgsStatus> {
+    let key = get_user_located_servers_key(user.client_id, user.user_id);
+    let value = &config::config::get_kubernetes().pod_ip;
+    let mut redis_conn = database_manager::redis::RedisManager::get_conn();
+    let (remaining_pods,): (u64,) = redis::pipe()
+        .srem(&key, value)
+        .ignore()
+        .set_ex(
+            get_user_last_seen_key(&enums::Platform::Frontend, user.client_id, user.user_id),
+            chrono::Utc::now().timestamp_millis(),
+            USER_EXPIRE_SECS as u64,
+        )
+        .ignore()
+        .scard(&key)
+        .query(&mut *redis_conn)
         .map_err(|e| {
-            warn!("save user to redis failed: {}", e);
+            warn!("remove user from redis failed: {}", e);
             KgsStatus::InternalServerError
         })?;
 
+    if remaining_pods == 0 {
+        remove_online_user_from_redis(&enums::Platform::Frontend, user)?;
+    }
+
     Ok(())
 }
 
+/// 強制下線時 清除用戶在所有pod上的位置與在線狀態
 #[tracing::instrument]
-pub fn get_user_located_server_from_redis(user_id: i64) -> Result<Option<String>, KgsStatus> {
-    let key = format!("{}:{}", REDIS_KEY, user_id);
+pub fn clear_user_located_servers_from_redis(user: &model::User) -> Result<(), KgsStatus> {
     let mut redis_conn = database_manager::redis::RedisManager::get_conn();
-    let value = redis_conn.get(key).map_err(|e| {
-        warn!("get user location server from redis failed: {}", e);
+    redis::pipe()
+        .del(get_user_located_servers_key(user.client_id, user.user_id))
+        .ignore()
+        .zrem(
+            get_online_users_key(&enums::Platform::Frontend, user.client_id),
+            user.user_id,
+        )
+        .ignore()
+        .set_ex(
+            get_user_last_seen_key(&enums::Platform::Frontend, user.client_id, user.user_id),
+            chrono::Utc::now().timestamp_millis(),
+            USER_EXPIRE_SECS as u64,
+        )
+        .ignore()
+        .query::<()>(&mut *redis_conn)
+        .map_err(|e| {
+            warn!("clear user located servers from redis failed: {}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+    Ok(())
+}
+
+/// 更新在線用戶的心跳時間 前台用戶由refresh_user_located_servers一併更新
+#[tracing::instrument(skip(users))]
+pub fn save_online_users_to_redis(
+    platform: &enums::Platform,
+    users: &[model::User],
+) -> Result<(), KgsStatus> {
+    if users.is_empty() {
+        return Ok(());
+    }
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut pipe = redis::pipe();
+    for user in users {
+        pipe.zadd(
+            get_online_users_key(platform, user.client_id),
+            user.user_id,
+            now,
+        )
+        .ignore()
+        .set_ex(
+            get_user_last_seen_key(platform, user.client_id, user.user_id),
+            now,
+            USER_EXPIRE_SECS as u64,
+        )
+        .ignore();
+    }
+
+    let mut redis_conn = database_manager::redis::RedisManager::get_conn();
+    pipe.query::<()>(&mut *redis_conn).map_err(|e| {
+        warn!("save online users to redis failed: {}", e);
         KgsStatus::InternalServerError
     })?;
-    Ok(value)
+
+    Ok(())
 }
 
+/// 用戶已離線 從在線用戶中移除 並更新最後在線時間
 #[tracing::instrument]
-pub fn remove_user_located_server_from_redis(user_id: i64) -> Result<(), KgsStatus> {
-    let key = format!("{}:{}", REDIS_KEY, user_id);
+pub fn remove_online_user_from_redis(
+    platform: &enums::Platform,
+    user: &model::User,
+) -> Result<(), KgsStatus> {
+    let mut redis_conn = database_manager::redis::RedisManager::get_conn();
+    redis::pipe()
+        .zrem(get_online_users_key(platform, user.client_id), user.user_id)
+        .ignore()
+        .set_ex(
+            get_user_last_seen_key(platform, user.client_id, user.user_id),
+            chrono::Utc::now().timestamp_millis(),
+            USER_EXPIRE_SECS as u64,
+        )
+        .ignore()
+        .query::<()>(&mut *redis_conn)
+        .map_err(|e| {
+            warn!("remove online user from redis failed: {}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+    Ok(())
+}
+
+/// 取得用戶是否在線與最後在線時間(毫秒)
+#[tracing::instrument]
+pub fn get_online_status_from_redis(
+    platform: &enums::Platform,
+    client_id: i64,
+    user_ids: &[i64],
+) -> Result<Vec<(bool, Option<i64>)>, KgsStatus> {
+    if user_ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let key = get_online_users_key(platform, client_id);
+    let mut pipe = redis::pipe();
+    for user_id in user_ids {
+        pipe.zscore(&key, *user_id)
+            .get(get_user_last_seen_key(platform, client_id, *user_id));
+    }
+
     let mut redis_conn = database_manager::redis::RedisManager::get_conn();
-    redis_conn.del(key).map_err(|e| {
-        warn!("remove user from redis failed: {}", e);
+    let values: Vec<(Option<i64>, Option<i64>)> = pipe.query(&mut *redis_conn).map_err(|e| {
+        warn!("get online status from redis failed: {}", e);
         KgsStatus::InternalServerError
     })?;
 
+    let threshold = get_online_threshold();
+    Ok(values
+        .into_iter()
+        .map(|(heartbeat_at, last_seen_at)| to_online_status(heartbeat_at, last_seen_at, threshold))
+        .collect())
+}
+
+/// 最後一次心跳晚於threshold才算在線 沒有最後在線時間時以心跳時間代替
+fn to_online_status(
+    heartbeat_at: Option<i64>,
+    last_seen_at: Option<i64>,
+    threshold: i64,
+) -> (bool, Option<i64>) {
+    let is_online = heartbeat_at.map_or(false, |heartbeat_at| heartbeat_at >= threshold);
+    (is_online, last_seen_at.or(heartbeat_at))
+}
+
+/// 取得在線用戶數量
+#[tracing::instrument]
+pub fn get_online_user_count_from_redis(
+    platform: &enums::Platform,
+    client_id: i64,
+) -> Result<u64, KgsStatus> {
+    let mut redis_conn = database_manager::redis::RedisManager::get_conn();
+    redis_conn
+        .zcount(
+            get_online_users_key(platform, client_id),
+            get_online_threshold(),
+            "+inf",
+        )
+        .map_err(|e| {
+            warn!("get online user count from redis failed: {}", e);
+            KgsStatus::InternalServerError
+        })
+}
+
+/// 取得所有在線用戶與最後一次心跳的時間(毫秒) 並移除已過期的用戶
+#[tracing::instrument]
+pub fn get_online_users_from_redis(
+    platform: &enums::Platform,
+    client_id: i64,
+) -> Result<Vec<(i64, i64)>, KgsStatus> {
+    let key = get_online_users_key(platform, client_id);
+    let threshold = get_online_threshold();
+    let mut redis_conn = database_manager::redis::RedisManager::get_conn();
+    let (users,): (Vec<(i64, i64)>,) = redis::pipe()
+        .zrembyscore(&key, "-inf", format!("({}", threshold))
+        .ignore()
+        .zrangebyscore_withscores(&key, threshold, "+inf")
+        .query(&mut *redis_conn)
+        .map_err(|e| {
+            warn!("get online users from redis failed: {}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+    Ok(users)
+}
+
+/// 所有pod的註冊表 score為最後一次心跳的時間(毫秒)
+fn get_pod_registry_key() -> String {
+    format!("{}:pod_registry", REDIS_KEY)
+}
+
+/// 更新此pod的心跳 同時註冊到pod註冊表
+#[tracing::instrument]
+pub fn save_pod_heartbeat() -> Result<(), KgsStatus> {
+    let pod_ip = &config::config::get_kubernetes().pod_ip;
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut redis_conn = database_manager::redis::RedisManager::get_conn();
+    redis::pipe()
+        .set_ex(
+            get_pod_heartbeat_key(pod_ip),
+            now,
+            POD_HEARTBEAT_EXPIRE_SECS as u64,
+        )
+        .ignore()
+        .zadd(get_pod_registry_key(), pod_ip, now)
+        .ignore()
+        .query::<()>(&mut *redis_conn)
+        .map_err(|e| {
+            warn!("save pod heartbeat to redis failed: {}", e);
+            KgsStatus::InternalServerError
+        })?;
+
     Ok(())
 }
+
+/// 取得心跳尚未過期的pod 並移除已過期的pod
+#[tracing::instrument]
+pub fn get_alive_pods_from_redis() -> Result<Vec<String>, KgsStatus> {
+    let key = get_pod_registry_key();
+    let expired_before =
+        chrono::Utc::now().timestamp_millis() - (POD_HEARTBEAT_EXPIRE_SECS * 1000) as i64;
+    let mut redis_conn = database_manager::redis::RedisManager::get_conn();
+    let (pod_ips,): (Vec<String>,) = redis::pipe()
+        .zrembyscore(&key, "-inf", format!("({}", expired_before))
+        .ignore()
+        .zrangebyscore(&key, expired_before, "+inf")
+        .query(&mut *redis_conn)
+        .map_err(|e| {
+            warn!("get alive pods from redis failed: {}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+    Ok(pod_ips)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frontend_presence_is_keyed_by_frontend_client() {
+        // the connection refresh writes the frontend client, the query must read the same key
+        assert_eq!(
+            get_online_users_key(&enums::Platform::Frontend, 100),
+            "notify_server:online:1:100"
+        );
+        assert_ne!(
+            get_online_users_key(&enums::Platform::Frontend, 100),
+            get_online_users_key(&enums::Platform::Backstage, 100)
+        );
+    }
+
+    #[test]
+    fn connected_user_is_online() {
+        let now = chrono::Utc::now().timestamp_millis();
+        assert_eq!(
+            to_online_status(Some(now), Some(now), get_online_threshold()),
+            (true, Some(now))
+        );
+    }
+
+    #[test]
+    fn expired_heartbeat_is_offline() {
+        let threshold = chrono::Utc::now().timestamp_millis();
+        let heartbeat_at = threshold - 1;
+        assert_eq!(
+            to_online_status(Some(heartbeat_at), None, threshold),
+            (false, Some(heartbeat_at))
+        );
+        assert_eq!(to_online_status(None, None, threshold), (false, None));
+    }
+}
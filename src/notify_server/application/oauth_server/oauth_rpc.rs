@@ -48,3 +48,20 @@ pub async fn get_frontend_client(backstage_client_id: i64) -> Result<i64, KgsSta
 
     Ok(response.into_inner().frontend_client_id)
 }
+
+/// 驗證access token 回傳token所屬的client與用戶
+#[tracing::instrument(skip(access_token))]
+pub async fn verify_token(access_token: &str) -> Result<VerifyTokenResponse, KgsStatus> {
+    let mut client = get_client().await?;
+
+    let request = kgs_tracing::tonic::create_request_with_span(VerifyTokenRequest {
+        access_token: access_token.to_string(),
+    });
+
+    let response = client.verify_token(request).await.map_err(|err| {
+        warn!("Failed to verify token: {:?}", err);
+        kgs_err::models::status::tonic_to_kgs(err)
+    })?;
+
+    Ok(response.into_inner())
+}
@@ -0,0 +1,76 @@
+use super::PeerDiscovery;
+use hickory_resolver::TokioAsyncResolver;
+use kgs_err::models::status::Status as KgsStatus;
+use kgs_tracing::{tracing, warn};
+use std::fmt::Debug;
+use tonic::async_trait;
+
+/// DNS記錄的類型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnsRecordType {
+    A,   // 名稱直接解析為所有pod的ip 例如k8s的headless service
+    Srv, // 先解析SRV取得各pod的主機名稱 再解析為ip
+}
+
+/// 透過DNS解析取得所有pod的位址
+pub struct DnsDiscovery {
+    name: String,
+    record_type: DnsRecordType,
+    resolver: TokioAsyncResolver,
+}
+
+impl DnsDiscovery {
+    pub fn new(name: &str, record_type: DnsRecordType) -> Result<Self, KgsStatus> {
+        let resolver = TokioAsyncResolver::tokio_from_system_conf().map_err(|err| {
+            warn!("create dns resolver failed: {}", err);
+            KgsStatus::InternalServerError
+        })?;
+
+        Ok(DnsDiscovery {
+            name: name.to_string(),
+            record_type,
+            resolver,
+        })
+    }
+
+    async fn lookup_ipv4(&self, name: &str) -> Result<Vec<String>, KgsStatus> {
+        let lookup = self.resolver.ipv4_lookup(name).await.map_err(|err| {
+            warn!("dns A lookup {} failed: {}", name, err);
+            KgsStatus::InternalServerError
+        })?;
+
+        Ok(lookup.iter().map(|ip| ip.to_string()).collect())
+    }
+}
+
+impl Debug for DnsDiscovery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DnsDiscovery")
+            .field("name", &self.name)
+            .field("record_type", &self.record_type)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl PeerDiscovery for DnsDiscovery {
+    #[tracing::instrument]
+    async fn discover(&self) -> Result<Vec<String>, KgsStatus> {
+        match self.record_type {
+            DnsRecordType::A => self.lookup_ipv4(&self.name).await,
+            DnsRecordType::Srv => {
+                let lookup = self.resolver.srv_lookup(&self.name).await.map_err(|err| {
+                    warn!("dns SRV lookup {} failed: {}", self.name, err);
+                    KgsStatus::InternalServerError
+                })?;
+
+                let mut ips = vec![];
+                for srv in lookup.iter() {
+                    ips.extend(self.lookup_ipv4(&srv.target().to_utf8()).await?);
+                }
+
+                Ok(ips)
+            }
+        }
+    }
+}
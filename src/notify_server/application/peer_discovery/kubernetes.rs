@@ -0,0 +1,46 @@
+use super::PeerDiscovery;
+use crate::config::config;
+use k8s_openapi::api::core::v1::Pod;
+use kgs_err::models::status::Status as KgsStatus;
+use kgs_tracing::{tracing, warn};
+use kube::{api::ListParams, Api, Client};
+use tonic::async_trait;
+
+/// 透過k8s api 以deployment的app label取得同一服務的pod
+#[derive(Debug)]
+pub struct KubernetesDiscovery;
+
+#[async_trait]
+impl PeerDiscovery for KubernetesDiscovery {
+    #[tracing::instrument]
+    async fn discover(&self) -> Result<Vec<String>, KgsStatus> {
+        let result: Result<Vec<String>, kube::Error> = async {
+            // get k8s client
+            let client = Client::try_default().await?;
+
+            // get k8s config
+            let kube_config = config::get_kubernetes();
+
+            // use deployment name to get Pod list
+            let pods: Api<Pod> = Api::namespaced(client, &kube_config.pod_namespace);
+
+            let lp = ListParams::default().labels(&format!("app={}", kube_config.deployment_name));
+            let pod_list = pods.list(&lp).await?;
+
+            // get pod ips
+            let ip_vec: Vec<String> = pod_list
+                .items
+                .into_iter()
+                .filter_map(|pod| pod.status.and_then(|status| status.pod_ip))
+                .collect();
+
+            Ok(ip_vec)
+        }
+        .await;
+
+        result.map_err(|err| {
+            warn!("非k8s環境, 無法獲取其他Pod IP err:{}", err);
+            KgsStatus::InternalServerError
+        })
+    }
+}
@@ -0,0 +1,114 @@
+mod dns;
+mod kubernetes;
+mod redis_registry;
+mod static_list;
+
+use crate::config::config::{self, DiscoveryMode};
+use dns::{DnsDiscovery, DnsRecordType};
+use kgs_err::models::status::Status as KgsStatus;
+use kgs_tracing::{info, tracing, warn};
+use kubernetes::KubernetesDiscovery;
+use once_cell::sync::Lazy;
+use redis_registry::RedisRegistryDiscovery;
+use static_list::StaticDiscovery;
+use std::fmt::Debug;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tonic::async_trait;
+
+static PEER_DISCOVERY: Lazy<CachedPeerDiscovery> = Lazy::new(|| {
+    let discovery_config = config::get_discovery();
+    CachedPeerDiscovery::new(
+        new_peer_discovery(),
+        Duration::from_secs(discovery_config.discovery_cache_secs),
+    )
+});
+
+/// 取得同一服務的所有pod位址
+#[async_trait]
+pub trait PeerDiscovery: Send + Sync + Debug {
+    /// 回傳的位址可能包含自己
+    async fn discover(&self) -> Result<Vec<String>, KgsStatus>;
+}
+
+/// 快取查詢結果 避免每次廣播都查詢
+#[derive(Debug)]
+pub struct CachedPeerDiscovery {
+    discovery: Box<dyn PeerDiscovery>,
+    ttl: Duration,
+    cache: RwLock<Option<(Instant, Vec<String>)>>, // 查詢時間與結果
+}
+
+impl CachedPeerDiscovery {
+    pub fn new(discovery: Box<dyn PeerDiscovery>, ttl: Duration) -> Self {
+        CachedPeerDiscovery {
+            discovery,
+            ttl,
+            cache: RwLock::new(None),
+        }
+    }
+
+    /// 取得所有pod 查詢失敗時沿用上一次的結果
+    pub async fn get_peers(&self) -> Vec<String> {
+        if let Some((queried_at, peers)) = self.cache.read().await.as_ref() {
+            if queried_at.elapsed() < self.ttl {
+                return peers.clone();
+            }
+        }
+
+        let mut cache = self.cache.write().await;
+        match self.discovery.discover().await {
+            Ok(peers) => {
+                *cache = Some((Instant::now(), peers.clone()));
+                peers
+            }
+            Err(err) => {
+                warn!("discover peers failed: {}", err);
+                cache
+                    .as_ref()
+                    .map(|(_, peers)| peers.clone())
+                    .unwrap_or_default()
+            }
+        }
+    }
+}
+
+/// 依設定建立pod的查詢方式
+fn new_peer_discovery() -> Box<dyn PeerDiscovery> {
+    let discovery_config = config::get_discovery();
+    info!("peer discovery mode: {:?}", discovery_config.discovery_mode);
+
+    let dns_discovery = |record_type| -> Box<dyn PeerDiscovery> {
+        match DnsDiscovery::new(&discovery_config.discovery_dns_name, record_type) {
+            Ok(discovery) => Box::new(discovery),
+            Err(err) => {
+                warn!("dns discovery init failed, use static discovery: {}", err);
+                Box::new(StaticDiscovery::new(
+                    discovery_config.discovery_static_peers.clone(),
+                ))
+            }
+        }
+    };
+
+    match discovery_config.discovery_mode {
+        DiscoveryMode::Kubernetes => Box::new(KubernetesDiscovery),
+        DiscoveryMode::Static => Box::new(StaticDiscovery::new(
+            discovery_config.discovery_static_peers.clone(),
+        )),
+        DiscoveryMode::Dns => dns_discovery(DnsRecordType::A),
+        DiscoveryMode::DnsSrv => dns_discovery(DnsRecordType::Srv),
+        DiscoveryMode::Redis => Box::new(RedisRegistryDiscovery),
+    }
+}
+
+/// 取得其他pod的位址 不包含自己
+#[tracing::instrument]
+pub async fn get_other_pod_ips() -> Vec<String> {
+    let own_pod_ip = &config::get_kubernetes().pod_ip;
+    PEER_DISCOVERY
+        .get_peers()
+        .await
+        .into_iter()
+        .filter(|pod_ip| pod_ip != own_pod_ip)
+        .collect()
+}
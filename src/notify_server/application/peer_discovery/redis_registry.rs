@@ -0,0 +1,15 @@
+use super::PeerDiscovery;
+use crate::notify_server::application::notify_handler;
+use kgs_err::models::status::Status as KgsStatus;
+use tonic::async_trait;
+
+/// 每個pod定期將自己註冊到redis 以心跳未過期的pod作為其他pod
+#[derive(Debug)]
+pub struct RedisRegistryDiscovery;
+
+#[async_trait]
+impl PeerDiscovery for RedisRegistryDiscovery {
+    async fn discover(&self) -> Result<Vec<String>, KgsStatus> {
+        notify_handler::get_alive_pods_from_redis()
+    }
+}
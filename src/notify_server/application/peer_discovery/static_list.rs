@@ -0,0 +1,22 @@
+use super::PeerDiscovery;
+use kgs_err::models::status::Status as KgsStatus;
+use tonic::async_trait;
+
+/// 由設定檔指定所有pod的位址 用於docker-compose或固定的主機
+#[derive(Debug)]
+pub struct StaticDiscovery {
+    peers: Vec<String>,
+}
+
+impl StaticDiscovery {
+    pub fn new(peers: Vec<String>) -> Self {
+        StaticDiscovery { peers }
+    }
+}
+
+#[async_trait]
+impl PeerDiscovery for StaticDiscovery {
+    async fn discover(&self) -> Result<Vec<String>, KgsStatus> {
+        Ok(self.peers.clone())
+    }
+}
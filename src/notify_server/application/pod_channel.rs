@@ -0,0 +1,86 @@
+use crate::config::config;
+use dashmap::DashMap;
+use kgs_err::models::status::Status as KgsStatus;
+use kgs_tracing::{info, tracing, warn};
+use once_cell::sync::Lazy;
+use std::time::Duration;
+use tonic::service::interceptor::InterceptedService;
+use tonic::transport::{Channel, Endpoint};
+
+const CONNECT_TIMEOUT_SECS: u64 = 3; // 建立連線的逾時時間
+const REQUEST_TIMEOUT_SECS: u64 = 5; // 每次呼叫的逾時時間
+const KEEPALIVE_INTERVAL_SECS: u64 = 30; // http2 ping的間隔
+const KEEPALIVE_TIMEOUT_SECS: u64 = 10; // http2 ping未回應視為斷線的時間
+
+// pod之間轉發使用的grpc channel key: pod_ip
+// channel本身可複製並共用同一條http2連線 斷線時會自動重新連線
+static POD_CHANNELS: Lazy<DashMap<String, Channel>> = Lazy::new(DashMap::new);
+
+type SecretInterceptor = fn(tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status>;
+
+/// 連到其他pod內部grpc服務的channel 每個請求都會帶入共用密鑰
+pub type PodChannel = InterceptedService<Channel, SecretInterceptor>;
+
+/// 取得連到指定pod的channel 第一次呼叫時才建立連線
+#[tracing::instrument]
+pub fn get_channel(pod_ip: &str) -> Result<PodChannel, KgsStatus> {
+    if !super::auth::is_internal_enabled() {
+        warn!(
+            "internal secret is not configured, cannot forward to pod {}",
+            pod_ip
+        );
+        return Err(KgsStatus::InternalServerError);
+    }
+
+    let channel = match POD_CHANNELS.get(pod_ip) {
+        Some(channel) => channel.clone(),
+        None => connect(pod_ip)?,
+    };
+
+    Ok(InterceptedService::new(
+        channel,
+        super::auth::attach_internal_secret as SecretInterceptor,
+    ))
+}
+
+fn connect(pod_ip: &str) -> Result<Channel, KgsStatus> {
+    let url = format!("http://{}:{}", pod_ip, config::get_internal().internal_port);
+    let channel = Endpoint::from_shared(url)
+        .map_err(|err| {
+            warn!("invalid pod address {}: {:?}", pod_ip, err);
+            KgsStatus::InternalServerError
+        })?
+        .connect_timeout(Duration::from_secs(CONNECT_TIMEOUT_SECS))
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .tcp_keepalive(Some(Duration::from_secs(KEEPALIVE_INTERVAL_SECS)))
+        .http2_keep_alive_interval(Duration::from_secs(KEEPALIVE_INTERVAL_SECS))
+        .keep_alive_timeout(Duration::from_secs(KEEPALIVE_TIMEOUT_SECS))
+        .keep_alive_while_idle(true)
+        .connect_lazy();
+
+    Ok(POD_CHANNELS
+        .entry(pod_ip.to_string())
+        .or_insert(channel)
+        .clone())
+}
+
+/// 移除已失效pod的channel
+#[tracing::instrument]
+pub fn evict(pod_ip: &str) {
+    if POD_CHANNELS.remove(pod_ip).is_some() {
+        info!("evict pod channel {}", pod_ip);
+    }
+}
+
+/// 只保留仍存在的pod的channel
+#[tracing::instrument(skip_all)]
+pub fn retain(pod_ips: &[String]) {
+    POD_CHANNELS.retain(|pod_ip, _| pod_ips.contains(pod_ip));
+}
+
+/// 呼叫失敗時 若是連線問題則移除channel 下次呼叫時重新建立
+pub fn evict_if_unavailable(pod_ip: &str, status: &tonic::Status) {
+    if status.code() == tonic::Code::Unavailable {
+        evict(pod_ip);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::config::config::{self, StreamOverflowPolicy};
+use crate::enums;
+use kgs_tracing::{tracing, warn};
+use once_cell::sync::Lazy;
+use opentelemetry::metrics::Counter;
+use opentelemetry::KeyValue;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::Sender;
+
+// 連線暫存已滿而被丟棄的訊息數量
+static DROPPED_MESSAGES: Lazy<Counter<u64>> = Lazy::new(|| {
+    opentelemetry::global::meter("notify_server")
+        .u64_counter("notify_stream_dropped_messages")
+        .with_description("連線暫存已滿而被丟棄的訊息數量")
+        .init()
+});
+
+// 連線暫存已滿而被關閉的連線數量
+static EVICTED_SESSIONS: Lazy<Counter<u64>> = Lazy::new(|| {
+    opentelemetry::global::meter("notify_server")
+        .u64_counter("notify_stream_evicted_sessions")
+        .with_description("連線暫存已滿而被關閉的連線數量")
+        .init()
+});
+
+/// 推送到連線的結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendResult {
+    Sent,    // 已放入連線暫存
+    Dropped, // 暫存已滿 訊息被丟棄 連線保留
+    Evicted, // 暫存已滿 連線需被關閉
+    Closed,  // 連線已關閉
+}
+
+impl SendResult {
+    /// 連線是否需要從連線表中移除
+    pub fn should_remove(&self) -> bool {
+        matches!(self, SendResult::Evicted | SendResult::Closed)
+    }
+}
+
+/// 依平台取得每個連線的暫存數量
+pub fn get_buffer_size(platform: &enums::Platform) -> usize {
+    let stream_config = config::get_stream();
+    match platform {
+        enums::Platform::Frontend => stream_config.stream_frontend_buffer_size,
+        _ => stream_config.stream_backstage_buffer_size,
+    }
+    .max(1)
+}
+
+/// 不等待地推送訊息到連線 暫存已滿時依設定丟棄訊息或關閉連線 避免單一連線阻塞其他連線
+#[tracing::instrument(skip(tx, message))]
+pub fn try_send<T>(
+    platform: &enums::Platform,
+    tx: &Sender<Result<T, tonic::Status>>,
+    message: T,
+) -> SendResult {
+    match tx.try_send(Ok(message)) {
+        Ok(()) => SendResult::Sent,
+        Err(TrySendError::Closed(_)) => SendResult::Closed,
+        Err(TrySendError::Full(_)) => {
+            let attributes = [KeyValue::new("platform", platform.to_string())];
+            match config::get_stream().stream_overflow_policy {
+                StreamOverflowPolicy::Drop => {
+                    warn!("{} stream buffer full, drop the message", platform);
+                    DROPPED_MESSAGES.add(1, &attributes);
+                    SendResult::Dropped
+                }
+                StreamOverflowPolicy::Evict => {
+                    warn!("{} stream buffer full, evict the session", platform);
+                    EVICTED_SESSIONS.add(1, &attributes);
+                    SendResult::Evicted
+                }
+            }
+        }
+    }
+}
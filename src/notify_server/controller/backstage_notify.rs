@@ -1,24 +1,140 @@
-use crate::{enums, notify_server::model::User};
+use crate::{
+    enums,
+    notify_server::{application, model::User},
+};
+use dashmap::DashMap;
 use kgs_tracing::tracing;
 use once_cell::sync::Lazy;
 use protos::backstage_notify::{self, back_stage_notify_service_server::*};
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::RwLock;
+use std::{collections::HashSet, sync::Arc};
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response};
 
 pub static BACKSTAGE_NOTIFY_SERVER: Lazy<Arc<BackstageNotifyServer>> =
     Lazy::new(|| BackstageNotifyServer::default().into());
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct StreamInfo {
     pub role_ids: Vec<i64>,
     pub user_account: String,
     pub tx: tokio::sync::mpsc::Sender<Result<backstage_notify::Receiver, tonic::Status>>,
 }
 
+/// 後台使用者的連線表 依使用者分片鎖定 並以(client_id, role_id)建立索引
+#[derive(Default, Debug)]
+pub struct BackstageConnections {
+    streams: DashMap<User, StreamInfo>,
+    role_index: DashMap<(i64, i64), HashSet<User>>, // key: (client_id, role_id)
+}
+
+impl BackstageConnections {
+    /// 新增連線 同一使用者重新連線時取代舊的連線
+    pub fn insert(&self, user: User, stream_info: StreamInfo) {
+        let role_ids = stream_info.role_ids.clone();
+        if let Some(old_stream_info) = self.streams.insert(user.clone(), stream_info) {
+            self.remove_role_index(&user, &old_stream_info.role_ids);
+        }
+
+        for role_id in role_ids {
+            self.role_index
+                .entry((user.client_id, role_id))
+                .or_default()
+                .insert(user.clone());
+        }
+    }
+
+    pub fn remove(&self, user: &User) -> Option<StreamInfo> {
+        let (_, stream_info) = self.streams.remove(user)?;
+        self.remove_role_index(user, &stream_info.role_ids);
+        Some(stream_info)
+    }
+
+    /// 移除指定的連線 使用者已重新連線時不會移除新的連線 回傳是否有移除
+    pub fn remove_stream(
+        &self,
+        user: &User,
+        tx: &tokio::sync::mpsc::Sender<Result<backstage_notify::Receiver, tonic::Status>>,
+    ) -> bool {
+        match self
+            .streams
+            .remove_if(user, |_, stream_info| stream_info.tx.same_channel(tx))
+        {
+            Some((_, stream_info)) => {
+                self.remove_role_index(user, &stream_info.role_ids);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 移除已關閉的連線 回傳(仍在線的使用者, 已移除的使用者)
+    pub fn refresh_streams(&self) -> (Vec<User>, Vec<User>) {
+        let mut online_users = vec![];
+        let mut closed_streams = vec![];
+        for stream in self.streams.iter() {
+            if stream.tx.is_closed() {
+                closed_streams.push((stream.key().clone(), stream.tx.clone()));
+            } else {
+                online_users.push(stream.key().clone());
+            }
+        }
+
+        // remove the closed streams after the iteration
+        let removed_users = closed_streams
+            .into_iter()
+            .filter(|(user, tx)| self.remove_stream(user, tx))
+            .map(|(user, _)| user)
+            .collect();
+
+        (online_users, removed_users)
+    }
+
+    /// 取得使用者的連線 回傳複本 發送時不需持有鎖
+    pub fn get(&self, user: &User) -> Option<StreamInfo> {
+        self.streams
+            .get(user)
+            .map(|stream_info| stream_info.clone())
+    }
+
+    /// 取得擁有任一角色的使用者連線 回傳複本 發送時不需持有鎖
+    pub fn find_by_roles(&self, client_id: i64, role_ids: &[i64]) -> Vec<(User, StreamInfo)> {
+        let users: HashSet<User> = role_ids
+            .iter()
+            .filter_map(|role_id| {
+                self.role_index
+                    .get(&(client_id, *role_id))
+                    .map(|users| users.clone())
+            })
+            .flatten()
+            .collect();
+
+        // 索引可能殘留重新連線前的角色 以連線目前的角色為準
+        users
+            .into_iter()
+            .filter_map(|user| {
+                let stream_info = self.streams.get(&user)?.clone();
+                stream_info
+                    .role_ids
+                    .iter()
+                    .any(|role_id| role_ids.contains(role_id))
+                    .then_some((user, stream_info))
+            })
+            .collect()
+    }
+
+    fn remove_role_index(&self, user: &User, role_ids: &[i64]) {
+        for role_id in role_ids {
+            let key = (user.client_id, *role_id);
+            if let Some(mut users) = self.role_index.get_mut(&key) {
+                users.remove(user);
+            }
+            self.role_index.remove_if(&key, |_, users| users.is_empty());
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct BackstageNotifyServer {
-    pub connections: Arc<RwLock<HashMap<User, StreamInfo>>>,
+    pub connections: BackstageConnections,
 }
 
 #[async_trait::async_trait]
@@ -31,8 +147,9 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::ConnectionRequest>,
     ) -> Result<Response<Self::CreateConnectionStream>, tonic::Status> {
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
         let receiver = self
-            .create_connection(request.into_inner())
+            .create_connection(request)
             .await
             .map_err(|e| e.to_tonic_status())?;
 
@@ -44,7 +161,8 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::CloseRequest>,
     ) -> Result<Response<backstage_notify::Empty>, tonic::Status> {
-        self.close_connection(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        self.close_connection(request)
             .await
             .map_err(|e| e.to_tonic_status())?;
 
@@ -56,7 +174,8 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::SendRequest>,
     ) -> Result<Response<backstage_notify::Empty>, tonic::Status> {
-        self.system_broadcast_to_backstage_user(request.into_inner())
+        let request = application::auth::authorize_service(request)?;
+        self.system_broadcast_to_backstage_user(request)
             .await
             .map_err(|e| e.to_tonic_status())?;
 
@@ -68,7 +187,8 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::GetNotifyRecordRequest>,
     ) -> Result<Response<backstage_notify::GetNotifyRecordResponse>, tonic::Status> {
-        let res = BackstageNotifyServer::get_notify_records(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::get_notify_records(request)
             .await
             .map_err(|e| e.to_tonic_status())?;
 
@@ -80,7 +200,8 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::UpdateNotifyRecordRequest>,
     ) -> Result<Response<backstage_notify::UpdateNotifyRecordResponse>, tonic::Status> {
-        let res = BackstageNotifyServer::update_notify_records(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::update_notify_records(request)
             .await
             .map_err(|e| e.to_tonic_status())?;
 
@@ -92,7 +213,8 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::GetUnreadNotifyCountRequest>,
     ) -> Result<Response<backstage_notify::GetUnreadNotifyCountResponse>, tonic::Status> {
-        let res = BackstageNotifyServer::get_unread_notify_count(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::get_unread_notify_count(request)
             .await
             .map_err(|e| e.to_tonic_status())?;
 
@@ -104,7 +226,8 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::AllReadRequest>,
     ) -> Result<Response<backstage_notify::Empty>, tonic::Status> {
-        let res = BackstageNotifyServer::all_read(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::all_read(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -116,7 +239,8 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::GetUserNotifyRecordRequest>,
     ) -> Result<Response<backstage_notify::GetUserNotifyRecordResponse>, tonic::Status> {
-        let res = BackstageNotifyServer::get_user_notify_records(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::get_user_notify_records(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -128,7 +252,8 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::GetNotifyByIdRequest>,
     ) -> Result<Response<backstage_notify::Notify>, tonic::Status> {
-        let res = BackstageNotifyServer::get_notify_by_id(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::get_notify_by_id(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -140,7 +265,8 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::BackstageSendToUserRequest>,
     ) -> Result<Response<backstage_notify::Empty>, tonic::Status> {
-        let res = BackstageNotifyServer::backstage_send_to_user(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::backstage_send_to_user(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -152,7 +278,8 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::GetClientEventSummaryRequest>,
     ) -> Result<Response<backstage_notify::ClientEventSummaryList>, tonic::Status> {
-        let res = BackstageNotifyServer::get_client_event_summary(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::get_client_event_summary(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -164,7 +291,8 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::GetClientTemplatesRequest>,
     ) -> Result<Response<backstage_notify::ClientTemplateList>, tonic::Status> {
-        let res = BackstageNotifyServer::get_client_templates(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::get_client_templates(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -176,7 +304,8 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::GetNotifyTaskListRequest>,
     ) -> Result<Response<backstage_notify::NotifyTaskList>, tonic::Status> {
-        let res = BackstageNotifyServer::get_notify_task_list(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::get_notify_task_list(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -188,7 +317,8 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::GetNotifyTaskDetailsRequest>,
     ) -> Result<Response<backstage_notify::NotifyTaskDetailList>, tonic::Status> {
-        let res = BackstageNotifyServer::get_notify_task_details(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::get_notify_task_details(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -200,7 +330,8 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::GetClientEventRequest>,
     ) -> Result<Response<backstage_notify::ClientEventList>, tonic::Status> {
-        let res = BackstageNotifyServer::get_client_event(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::get_client_event(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -212,7 +343,8 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::UpdateClientEventRequest>,
     ) -> Result<Response<backstage_notify::Empty>, tonic::Status> {
-        let res = BackstageNotifyServer::update_client_event(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::update_client_event(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -224,7 +356,8 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::DeleteClientEventRequest>,
     ) -> Result<Response<backstage_notify::Empty>, tonic::Status> {
-        let res = BackstageNotifyServer::delete_client_event(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::delete_client_event(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -236,7 +369,140 @@ impl BackStageNotifyService for BackstageNotifyServer {
         &self,
         request: Request<backstage_notify::CreateClientEventRequest>,
     ) -> Result<Response<backstage_notify::Empty>, tonic::Status> {
-        let res = BackstageNotifyServer::create_client_event(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::create_client_event(request)
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn create_recurring_campaign(
+        &self,
+        request: Request<backstage_notify::CreateRecurringCampaignRequest>,
+    ) -> Result<Response<backstage_notify::Empty>, tonic::Status> {
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::create_recurring_campaign(request)
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn update_recurring_campaign(
+        &self,
+        request: Request<backstage_notify::UpdateRecurringCampaignRequest>,
+    ) -> Result<Response<backstage_notify::Empty>, tonic::Status> {
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::update_recurring_campaign(request)
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn delete_recurring_campaign(
+        &self,
+        request: Request<backstage_notify::DeleteRecurringCampaignRequest>,
+    ) -> Result<Response<backstage_notify::Empty>, tonic::Status> {
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::delete_recurring_campaign(request)
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn get_recurring_campaign_list(
+        &self,
+        request: Request<backstage_notify::GetRecurringCampaignListRequest>,
+    ) -> Result<Response<backstage_notify::RecurringCampaignList>, tonic::Status> {
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::get_recurring_campaign_list(request)
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn get_recurring_campaign_details(
+        &self,
+        request: Request<backstage_notify::GetRecurringCampaignDetailsRequest>,
+    ) -> Result<Response<backstage_notify::NotifyTaskDetailList>, tonic::Status> {
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::get_recurring_campaign_details(request)
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn get_quarantine_record_list(
+        &self,
+        request: Request<backstage_notify::GetQuarantineRecordListRequest>,
+    ) -> Result<Response<backstage_notify::QuarantineRecordList>, tonic::Status> {
+        // 隔離的訊息不分client 只開放給其他服務
+        let request = application::auth::authorize_service(request)?;
+        let res = BackstageNotifyServer::get_quarantine_record_list(request)
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn replay_quarantine_record(
+        &self,
+        request: Request<backstage_notify::ReplayQuarantineRecordRequest>,
+    ) -> Result<Response<backstage_notify::Empty>, tonic::Status> {
+        // 隔離的訊息不分client 只開放給其他服務
+        let request = application::auth::authorize_service(request)?;
+        let res = BackstageNotifyServer::replay_quarantine_record(request)
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn get_online_status(
+        &self,
+        request: Request<backstage_notify::GetOnlineStatusRequest>,
+    ) -> Result<Response<backstage_notify::GetOnlineStatusResponse>, tonic::Status> {
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::get_online_status(request)
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn get_online_count(
+        &self,
+        request: Request<backstage_notify::GetOnlineCountRequest>,
+    ) -> Result<Response<backstage_notify::GetOnlineCountResponse>, tonic::Status> {
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::get_online_count(request)
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn get_online_user_list(
+        &self,
+        request: Request<backstage_notify::GetOnlineUserListRequest>,
+    ) -> Result<Response<backstage_notify::GetOnlineUserListResponse>, tonic::Status> {
+        let request = application::auth::authorize(request, &enums::Platform::Backstage)?;
+        let res = BackstageNotifyServer::get_online_user_list(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -264,4 +530,84 @@ impl BackStageNotifyService for BackstageNotifyServer {
 
         Ok(Response::new(backstage_notify::Empty {}))
     }
+
+    #[tracing::instrument]
+    async fn forward_records_changed(
+        &self,
+        request: Request<backstage_notify::ForwardRecordsChangedRequest>,
+    ) -> Result<Response<backstage_notify::Empty>, tonic::Status> {
+        let request = request.into_inner();
+        let notify_status = enums::NotifyStatus::try_from(request.notify_status)
+            .map_err(|err| err.to_tonic_status())?;
+        self.handle_records_changed(
+            request.client_id,
+            request.user_id,
+            &request.notify_ids,
+            notify_status,
+            request.unread_count,
+        )
+        .await
+        .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(backstage_notify::Empty {}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream_info(
+        role_ids: Vec<i64>,
+    ) -> (
+        StreamInfo,
+        tokio::sync::mpsc::Receiver<Result<backstage_notify::Receiver, tonic::Status>>,
+    ) {
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let stream_info = StreamInfo {
+            role_ids,
+            user_account: "account".to_string(),
+            tx,
+        };
+        (stream_info, rx)
+    }
+
+    #[test]
+    fn reconnect_with_other_roles() {
+        let connections = BackstageConnections::default();
+        let user = User {
+            client_id: 1,
+            user_id: 2,
+        };
+
+        let (first, _first_rx) = stream_info(vec![10]);
+        connections.insert(user.clone(), first);
+        let (second, _second_rx) = stream_info(vec![20]);
+        connections.insert(user.clone(), second);
+
+        assert!(connections.find_by_roles(1, &[10]).is_empty());
+        assert_eq!(connections.find_by_roles(1, &[20]).len(), 1);
+    }
+
+    #[test]
+    fn stale_role_index_is_ignored() {
+        let connections = BackstageConnections::default();
+        let user = User {
+            client_id: 1,
+            user_id: 2,
+        };
+
+        let (stream, _rx) = stream_info(vec![20]);
+        connections.insert(user.clone(), stream);
+
+        // an index entry left behind by a concurrent reconnect
+        connections
+            .role_index
+            .entry((1, 10))
+            .or_default()
+            .insert(user.clone());
+
+        assert!(connections.find_by_roles(1, &[10]).is_empty());
+        assert_eq!(connections.find_by_roles(1, &[10, 20]).len(), 1);
+    }
 }
@@ -1,18 +1,43 @@
-use crate::notify_server::model;
+use crate::enums;
+use crate::notify_server::{application, model};
+use dashmap::DashMap;
 use kgs_tracing::tracing;
 use once_cell::sync::Lazy;
 use protos::frontend_notify::{self, frontend_notify_service_server::*, ForwardNotifyRequest};
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::{mpsc::Sender, RwLock};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use tokio::sync::mpsc::Sender;
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response};
 
 pub static FRONTEND_NOTIFY_SERVER: Lazy<Arc<FrontendNotifyServer>> =
     Lazy::new(|| FrontendNotifyServer::default().into());
+/// 用戶的單一連線 同一用戶可以同時從多個裝置連線
+#[derive(Debug)]
+pub struct SessionInfo {
+    pub session_id: String,
+    pub device_info: String,
+    pub connected_at: i64,
+    pub topics: HashSet<String>, // 此連線訂閱的主題
+    pub tx: Sender<Result<frontend_notify::Receiver, tonic::Status>>,
+}
+
+/// 已推送但裝置尚未確認收到的通知
+#[derive(Debug, Clone)]
+pub struct PendingAck {
+    pub client_id: i64,
+    pub user_id: i64,
+    pub sent_at: i64,         // 最後一次推送的時間(毫秒)
+    pub redeliver_count: u32, // 已重新推送的次數
+}
+
 #[derive(Default, Debug)]
 pub struct FrontendNotifyServer {
-    pub connections:
-        Arc<RwLock<HashMap<model::User, Sender<Result<frontend_notify::Receiver, tonic::Status>>>>>,
+    pub connections: DashMap<model::User, HashMap<String, SessionInfo>>, // 依用戶分片鎖定 key: session_id
+    pub pending_acks: DashMap<i64, PendingAck>,                          // key: notify_id
+    pub topic_index: DashMap<(i64, String), HashSet<model::User>>, // key: (client_id, topic) 發送時再確認是否仍訂閱
 }
 
 #[async_trait::async_trait]
@@ -25,8 +50,9 @@ impl FrontendNotifyService for FrontendNotifyServer {
         &self,
         request: Request<frontend_notify::ConnectionRequest>,
     ) -> Result<Response<Self::CreateConnectionStream>, tonic::Status> {
+        let request = application::auth::authorize(request, &enums::Platform::Frontend)?;
         let receiver = self
-            .create_connection(request.into_inner())
+            .create_connection(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -38,7 +64,8 @@ impl FrontendNotifyService for FrontendNotifyServer {
         &self,
         request: Request<frontend_notify::ConnectionRequest>,
     ) -> Result<Response<frontend_notify::Empty>, tonic::Status> {
-        self.close_connection(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Frontend)?;
+        self.close_connection(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -50,7 +77,8 @@ impl FrontendNotifyService for FrontendNotifyServer {
         &self,
         request: Request<frontend_notify::SendRequest>,
     ) -> Result<Response<frontend_notify::Empty>, tonic::Status> {
-        self.system_to_frontend_user(request.into_inner())
+        let request = application::auth::authorize_service(request)?;
+        self.system_to_frontend_user(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -62,7 +90,8 @@ impl FrontendNotifyService for FrontendNotifyServer {
         &self,
         request: Request<frontend_notify::GetNotifyRecordRequest>,
     ) -> Result<Response<frontend_notify::GetNotifyRecordResponse>, tonic::Status> {
-        let res = FrontendNotifyServer::get_notify_records(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Frontend)?;
+        let res = FrontendNotifyServer::get_notify_records(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -74,7 +103,8 @@ impl FrontendNotifyService for FrontendNotifyServer {
         &self,
         request: Request<frontend_notify::UpdateNotifyRecordRequest>,
     ) -> Result<Response<frontend_notify::UpdateNotifyRecordResponse>, tonic::Status> {
-        let res = FrontendNotifyServer::update_notify_records(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Frontend)?;
+        let res = FrontendNotifyServer::update_notify_records(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -86,7 +116,8 @@ impl FrontendNotifyService for FrontendNotifyServer {
         &self,
         request: Request<frontend_notify::GetUnreadNotifyCountRequest>,
     ) -> Result<Response<frontend_notify::GetUnreadNotifyCountResponse>, tonic::Status> {
-        let res = FrontendNotifyServer::get_unread_notify_count(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Frontend)?;
+        let res = FrontendNotifyServer::get_unread_notify_count(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -98,7 +129,8 @@ impl FrontendNotifyService for FrontendNotifyServer {
         &self,
         request: Request<frontend_notify::AllReadRequest>,
     ) -> Result<Response<frontend_notify::Empty>, tonic::Status> {
-        let res = FrontendNotifyServer::all_read(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Frontend)?;
+        let res = FrontendNotifyServer::all_read(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -110,7 +142,98 @@ impl FrontendNotifyService for FrontendNotifyServer {
         &self,
         request: Request<frontend_notify::GetNotifyByIdRequest>,
     ) -> Result<Response<frontend_notify::Notify>, tonic::Status> {
-        let res = FrontendNotifyServer::get_notify_by_id(request.into_inner())
+        let request = application::auth::authorize(request, &enums::Platform::Frontend)?;
+        let res = FrontendNotifyServer::get_notify_by_id(request)
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn cancel_delay_notify(
+        &self,
+        request: Request<frontend_notify::CancelDelayNotifyRequest>,
+    ) -> Result<Response<frontend_notify::Empty>, tonic::Status> {
+        let request = application::auth::authorize_service(request)?;
+        let res = FrontendNotifyServer::cancel_delay_notify(request)
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn ack_notify(
+        &self,
+        request: Request<frontend_notify::AckNotifyRequest>,
+    ) -> Result<Response<frontend_notify::Empty>, tonic::Status> {
+        let request = application::auth::authorize(request, &enums::Platform::Frontend)?;
+        let res = self
+            .ack_notify(request)
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn revoke_sessions(
+        &self,
+        request: Request<frontend_notify::RevokeSessionsRequest>,
+    ) -> Result<Response<frontend_notify::Empty>, tonic::Status> {
+        // 後台使用者只能撤銷自己client對應的前台用戶
+        let (mut request, identity) =
+            application::auth::authorize_service_or_user(request, &enums::Platform::Backstage)?;
+        if let Some(identity) = identity {
+            request.client_id = application::oauth_rpc::get_frontend_client(identity.client_id)
+                .await
+                .map_err(|err| err.to_tonic_status())?;
+        }
+        let res = self
+            .revoke_sessions(request)
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn subscribe_topics(
+        &self,
+        request: Request<frontend_notify::TopicSubscriptionRequest>,
+    ) -> Result<Response<frontend_notify::Empty>, tonic::Status> {
+        let request = application::auth::authorize(request, &enums::Platform::Frontend)?;
+        let res = self
+            .update_topic_subscription(request, true)
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn unsubscribe_topics(
+        &self,
+        request: Request<frontend_notify::TopicSubscriptionRequest>,
+    ) -> Result<Response<frontend_notify::Empty>, tonic::Status> {
+        let request = application::auth::authorize(request, &enums::Platform::Frontend)?;
+        let res = self
+            .update_topic_subscription(request, false)
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn publish_to_topic(
+        &self,
+        request: Request<frontend_notify::PublishToTopicRequest>,
+    ) -> Result<Response<frontend_notify::Empty>, tonic::Status> {
+        let request = application::auth::authorize_service(request)?;
+        let res = self
+            .publish_to_topic(request)
             .await
             .map_err(|err| err.to_tonic_status())?;
 
@@ -131,4 +254,64 @@ impl FrontendNotifyService for FrontendNotifyServer {
 
         Ok(Response::new(res))
     }
+
+    #[tracing::instrument]
+    async fn forward_revoke_sessions(
+        &self,
+        request: Request<frontend_notify::RevokeSessionsRequest>,
+    ) -> Result<Response<frontend_notify::Empty>, tonic::Status> {
+        let request = request.into_inner();
+        self.revoke_local_sessions(request.client_id, request.user_id, &request.reason)
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(frontend_notify::Empty {}))
+    }
+
+    #[tracing::instrument]
+    async fn forward_records_changed(
+        &self,
+        request: Request<frontend_notify::ForwardRecordsChangedRequest>,
+    ) -> Result<Response<frontend_notify::Empty>, tonic::Status> {
+        let request = request.into_inner();
+        let notify_status = enums::NotifyStatus::try_from(request.notify_status)
+            .map_err(|err| err.to_tonic_status())?;
+        self.handle_records_changed(
+            request.client_id,
+            request.user_id,
+            &request.notify_ids,
+            notify_status,
+            request.unread_count,
+        )
+        .await
+        .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(frontend_notify::Empty {}))
+    }
+
+    #[tracing::instrument]
+    async fn forward_topic_subscription(
+        &self,
+        request: Request<frontend_notify::ForwardTopicSubscriptionRequest>,
+    ) -> Result<Response<frontend_notify::Empty>, tonic::Status> {
+        let request = request.into_inner();
+        let subscription = request.subscription.ok_or_else(|| {
+            kgs_err::models::status::Status::MissingBodyArgument.to_tonic_status()
+        })?;
+        self.handle_topic_subscription(&subscription, request.is_subscribe);
+
+        Ok(Response::new(frontend_notify::Empty {}))
+    }
+
+    #[tracing::instrument]
+    async fn forward_topic_publish(
+        &self,
+        request: Request<frontend_notify::PublishToTopicRequest>,
+    ) -> Result<Response<frontend_notify::Empty>, tonic::Status> {
+        self.handle_topic_publish(&request.into_inner())
+            .await
+            .map_err(|err| err.to_tonic_status())?;
+
+        Ok(Response::new(frontend_notify::Empty {}))
+    }
 }
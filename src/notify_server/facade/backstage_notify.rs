@@ -1,6 +1,7 @@
-use crate::config::config;
 use crate::notify_server::application;
-use crate::notify_server::controller::backstage_notify::{BackstageNotifyServer, StreamInfo};
+use crate::notify_server::controller::backstage_notify::{
+    BackstageNotifyServer, StreamInfo, BACKSTAGE_NOTIFY_SERVER,
+};
 use crate::notify_server::model;
 use crate::{entity, enums, repository};
 use crate::{helper, mq_manager};
@@ -22,7 +23,9 @@ impl BackstageNotifyServer {
         request: ConnectionRequest,
     ) -> Result<Receiver<Result<backstage_notify::Receiver, tonic::Status>>, KgsStatus> {
         // create channel
-        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let (tx, rx) = tokio::sync::mpsc::channel(application::stream_sender::get_buffer_size(
+            &enums::Platform::Backstage,
+        ));
 
         // get user_profile
         let user_account =
@@ -36,17 +39,20 @@ impl BackstageNotifyServer {
         };
 
         // insert connection
-        {
-            let mut connections = self.connections.write().await;
-            connections.insert(
-                user,
-                StreamInfo {
-                    user_account: user_account.account,
-                    role_ids: request.role_ids,
-                    tx,
-                },
-            );
-        }
+        self.connections.insert(
+            user.clone(),
+            StreamInfo {
+                user_account: user_account.account,
+                role_ids: request.role_ids,
+                tx,
+            },
+        );
+
+        // save online user to redis
+        application::notify_handler::save_online_users_to_redis(
+            &enums::Platform::Backstage,
+            std::slice::from_ref(&user),
+        )?;
 
         Ok(rx)
     }
@@ -60,11 +66,12 @@ impl BackstageNotifyServer {
         };
 
         // remove connection
-        {
-            let mut connections = self.connections.write().await;
-            if let Some(stream) = connections.remove(&user) {
-                drop(stream);
-            }
+        if let Some(stream) = self.connections.remove(&user) {
+            drop(stream);
+            application::notify_handler::remove_online_user_from_redis(
+                &enums::Platform::Backstage,
+                &user,
+            )?;
         }
 
         Ok(())
@@ -169,18 +176,18 @@ impl BackstageNotifyServer {
                 notify_status: enums::NotifyStatus::Unread.to_id(),
             });
 
-            // send message
-            stream
-                .tx
-                .clone()
-                .send(Ok(backstage_notify::Receiver {
+            // send message without waiting
+            let result = application::stream_sender::try_send(
+                &enums::Platform::Backstage,
+                &stream.tx,
+                backstage_notify::Receiver {
                     message: Some(response),
-                }))
-                .await
-                .map_err(|e| {
-                    warn!("send message failed: {:?}", e);
-                    KgsStatus::UserConnectionNotFound
-                })?;
+                },
+            );
+            if result.should_remove() {
+                warn!("send message failed: {:?}", result);
+                return Err(KgsStatus::UserConnectionNotFound);
+            }
 
             Ok(())
         }
@@ -191,28 +198,21 @@ impl BackstageNotifyServer {
             KgsStatus::InternalServerError
         })?;
 
-        // broadcast to backstage user
-        let connections = self.connections.read().await;
-        for (user, stream_info) in connections.iter() {
-            // 觸發事件的使用者的backstage 與 後台人員的client_id相同
-            if backstage_client_id != user.client_id {
-                continue;
-            }
-
-            // 後台人員擁有該權限才會收到廣播
-            if !application::notify_handler::has_common_role(role_ids, &stream_info.role_ids) {
-                continue;
-            }
+        // 觸發事件的使用者的backstage 與 後台人員的client_id相同 且後台人員擁有該權限才會收到廣播
+        let streams = self
+            .connections
+            .find_by_roles(backstage_client_id, role_ids);
 
+        // broadcast to backstage user, 失效的連線在發送結束後才移除
+        let mut evicted_streams = vec![];
+        for (user, stream_info) in streams.iter() {
             // create notify_id
             let notify_id = helper::generate_snowflake_id().await;
 
             // send message
             if let Err(e) = send_to_local_user(notify_id, stream_info, &title, &content).await {
                 warn!("send message failed: {:?}", e);
-                {
-                    self.connections.write().await.remove(user);
-                }
+                evicted_streams.push((user, &stream_info.tx));
                 continue;
             }
 
@@ -231,6 +231,7 @@ impl BackstageNotifyServer {
                 notify_status: Set(enums::NotifyStatus::Unread),
                 title: Set(title.to_string()),
                 content: Set(content.to_string()),
+                expires_at: Set(None),
                 create_at: NotSet,
                 update_at: NotSet,
             }
@@ -242,6 +243,18 @@ impl BackstageNotifyServer {
             })?;
         }
 
+        // remove the evicted connections
+        for (user, tx) in evicted_streams {
+            if self.connections.remove_stream(user, tx) {
+                if let Err(err) = application::notify_handler::remove_online_user_from_redis(
+                    &enums::Platform::Backstage,
+                    user,
+                ) {
+                    warn!("remove online user {} failed: {}", user.user_id, err);
+                }
+            }
+        }
+
         // commit
         txn.commit().await.map_err(|e| {
             warn!("commit failed: {:?}", e);
@@ -251,6 +264,169 @@ impl BackstageNotifyServer {
         Ok(())
     }
 
+    /// 通知狀態變更後 推送最新的未讀數量與變更的通知到使用者的連線
+    /// 後台沒有記錄使用者所在的pod 需廣播給所有pod
+    #[tracing::instrument(skip(notify_ids))]
+    async fn publish_records_changed(
+        client_id: i64,
+        user_id: i64,
+        notify_ids: Vec<i64>,
+        notify_status: enums::NotifyStatus,
+    ) {
+        // define internal function
+        async fn publish(
+            client_id: i64,
+            user_id: i64,
+            notify_ids: Vec<i64>,
+            notify_status: enums::NotifyStatus,
+        ) -> Result<(), KgsStatus> {
+            // get the latest unread count
+            let db = database_manager::sea_orm::get_db();
+            let unread_count = repository::notify_record::get_all_unread_app_records_count(
+                &*db, client_id, user_id,
+            )
+            .await?;
+
+            // handle the local connection
+            BACKSTAGE_NOTIFY_SERVER
+                .handle_records_changed(
+                    client_id,
+                    user_id,
+                    &notify_ids,
+                    notify_status.clone(),
+                    unread_count,
+                )
+                .await?;
+
+            // forward to other pods
+            BackstageNotifyServer::forward_records_changed(
+                backstage_notify::ForwardRecordsChangedRequest {
+                    client_id,
+                    user_id,
+                    notify_ids,
+                    notify_status: notify_status.to_id(),
+                    unread_count,
+                },
+            )
+            .await
+        }
+
+        if let Err(err) = publish(client_id, user_id, notify_ids, notify_status).await {
+            warn!("publish records changed failed: {}", err);
+        }
+    }
+
+    // 轉發通知狀態變更到其他pod
+    async fn forward_records_changed(
+        request: backstage_notify::ForwardRecordsChangedRequest,
+    ) -> Result<(), KgsStatus> {
+        // define internal function
+        async fn send(
+            ip: String,
+            request: backstage_notify::ForwardRecordsChangedRequest,
+        ) -> Result<(), KgsStatus> {
+            // get client
+            let mut client = back_stage_notify_service_client::BackStageNotifyServiceClient::new(
+                application::pod_channel::get_channel(&ip)?,
+            );
+
+            // forward records changed
+            let _res = client.forward_records_changed(request).await.map_err(|e| {
+                warn!("forward records changed failed: {:?}", e);
+                application::pod_channel::evict_if_unavailable(&ip, &e);
+                kgs_err::models::status::tonic_to_kgs(e)
+            })?;
+
+            Ok(())
+        }
+
+        // 透過redis pub/sub廣播給其他pod
+        if application::notify_bus::is_enabled() {
+            return application::notify_bus::broadcast(
+                application::notify_bus::BusEvent::BackstageRecordsChanged {
+                    client_id: request.client_id,
+                    user_id: request.user_id,
+                    notify_ids: request.notify_ids,
+                    notify_status: request.notify_status,
+                    unread_count: request.unread_count,
+                },
+            );
+        }
+
+        // get other pod ips
+        let ip_list = application::peer_discovery::get_other_pod_ips().await;
+
+        let mut futures = vec![];
+        for ip in ip_list {
+            futures.push(send(ip, request.clone()));
+        }
+
+        // wait all futures
+        futures::future::try_join_all(futures).await?;
+
+        Ok(())
+    }
+
+    /// 推送通知狀態變更與未讀數量到使用者在此pod上的連線
+    #[tracing::instrument(skip(notify_ids))]
+    pub async fn handle_records_changed(
+        &self,
+        client_id: i64,
+        user_id: i64,
+        notify_ids: &[i64],
+        notify_status: enums::NotifyStatus,
+        unread_count: u64,
+    ) -> Result<(), KgsStatus> {
+        let user = model::User { client_id, user_id };
+
+        // get the connection
+        let stream_info = match self.connections.get(&user) {
+            Some(stream_info) => stream_info,
+            None => return Ok(()),
+        };
+
+        // create the messages
+        let mut messages = vec![];
+        if !notify_ids.is_empty() {
+            messages.push(backstage_notify::Receiver {
+                message: Some(backstage_notify::receiver::Message::RecordsChanged(
+                    backstage_notify::RecordsChanged {
+                        notify_ids: notify_ids.to_vec(),
+                        notify_status: notify_status.to_id(),
+                    },
+                )),
+            });
+        }
+        messages.push(backstage_notify::Receiver {
+            message: Some(backstage_notify::receiver::Message::UnreadCountChanged(
+                backstage_notify::UnreadCountChanged {
+                    total_rows: unread_count,
+                },
+            )),
+        });
+
+        // send the messages, remove the connection if it is closed
+        for message in messages {
+            let result = application::stream_sender::try_send(
+                &enums::Platform::Backstage,
+                &stream_info.tx,
+                message,
+            );
+            if result.should_remove() {
+                warn!("send records changed failed: {:?}", result);
+                if self.connections.remove_stream(&user, &stream_info.tx) {
+                    application::notify_handler::remove_online_user_from_redis(
+                        &enums::Platform::Backstage,
+                        &user,
+                    )?;
+                }
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
     // 轉發notify到其他pod
     async fn forward_notify(
         request: backstage_notify::ForwardNotifyRequest,
@@ -260,28 +436,37 @@ impl BackstageNotifyServer {
             ip: String,
             request: backstage_notify::ForwardNotifyRequest,
         ) -> Result<(), KgsStatus> {
-            let dst = format!("http://{}:{}", ip, config::get_host().service_port);
-
             // get client
-            let mut client =
-                back_stage_notify_service_client::BackStageNotifyServiceClient::connect(dst)
-                    .await
-                    .map_err(|e| {
-                        warn!("connect to other pod failed: {:?}", e);
-                        KgsStatus::InternalServerError
-                    })?;
+            let mut client = back_stage_notify_service_client::BackStageNotifyServiceClient::new(
+                application::pod_channel::get_channel(&ip)?,
+            );
 
             // forward notify
             let _res = client.forward_notify(request).await.map_err(|e| {
                 warn!("forward notify failed: {:?}", e);
+                application::pod_channel::evict_if_unavailable(&ip, &e);
                 kgs_err::models::status::tonic_to_kgs(e)
             })?;
 
             Ok(())
         }
 
+        // 透過redis pub/sub廣播給其他pod
+        if application::notify_bus::is_enabled() {
+            return application::notify_bus::broadcast(
+                application::notify_bus::BusEvent::BackstageNotify {
+                    client_id: request.client_id,
+                    role_ids: request.role_ids,
+                    client_notify_event_id: request.client_notify_event_id,
+                    title: request.title,
+                    content: request.content,
+                },
+            );
+        }
+
         // get other pod ips
-        let ip_list = application::kube_api::get_other_pod_ips().await;
+        let ip_list = application::peer_discovery::get_other_pod_ips().await;
+        application::pod_channel::retain(&ip_list);
 
         let mut futures = vec![];
         for ip in ip_list {
@@ -371,11 +556,12 @@ impl BackstageNotifyServer {
             })?;
 
         // update notify_records
+        let notify_status = enums::NotifyStatus::try_from(request.notify_status)?;
         let result = repository::notify_record::update_notify_records(
             &txn,
             request.client_id,
             request.user_id,
-            enums::NotifyStatus::try_from(request.notify_status)?,
+            notify_status.clone(),
             request.notify_ids,
         )
         .await?;
@@ -386,6 +572,15 @@ impl BackstageNotifyServer {
             KgsStatus::InternalServerError
         })?;
 
+        // 通知使用者其他pod上的連線更新狀態
+        Self::publish_records_changed(
+            request.client_id,
+            request.user_id,
+            result.iter().map(|record| record.id).collect(),
+            notify_status,
+        )
+        .await;
+
         Ok(UpdateNotifyRecordResponse {
             list: result.into_iter().map(|record| record.to_proto()).collect(),
         })
@@ -424,7 +619,7 @@ impl BackstageNotifyServer {
             .transpose()?;
 
         // update notify_records
-        let _all_entities = repository::notify_record::update_all_with_notify_level(
+        let all_entities = repository::notify_record::update_all_with_notify_level(
             &txn,
             request.client_id,
             request.user_id,
@@ -439,6 +634,15 @@ impl BackstageNotifyServer {
             KgsStatus::InternalServerError
         })?;
 
+        // 通知使用者其他pod上的連線更新狀態
+        Self::publish_records_changed(
+            request.client_id,
+            request.user_id,
+            all_entities.iter().map(|record| record.id).collect(),
+            enums::NotifyStatus::Read,
+        )
+        .await;
+
         Ok(Empty {})
     }
 
@@ -514,24 +718,13 @@ impl BackstageNotifyServer {
         let notify_level = enums::NotifyLevel::try_from(request.notify_level)?;
 
         // get receiver_account
-        let receivers = if request.is_all {
-            application::user_rpc::get_accounts_by_client_id(frontend_client_id)
-                .await?
-                .user_accounts
-        } else if request.receiver_ids.len() > 0 {
-            application::user_rpc::get_accounts_by_user_ids(
-                frontend_client_id,
-                request.receiver_ids,
-            )
-            .await?
-            .user_accounts
-        } else if request.vip_levels.len() > 0 {
-            application::user_rpc::get_accounts_by_vip_level(frontend_client_id, request.vip_levels)
-                .await?
-                .user_accounts
-        } else {
-            return Err(KgsStatus::InvalidArgument);
-        };
+        let receivers = Self::get_receivers(
+            frontend_client_id,
+            request.is_all,
+            request.receiver_ids,
+            request.vip_levels,
+        )
+        .await?;
 
         // if need to save the event
         if request.is_save_as_event && request.client_event_name.is_some() {
@@ -547,60 +740,35 @@ impl BackstageNotifyServer {
             .await?;
         }
 
-        // insert a new backstage_send_task
-        let task_id = helper::generate_snowflake_id().await;
-        let task_entity = entity::backstage_send_task::ActiveModel {
-            id: Set(task_id),
-            client_event_id: Set(request.client_event_id.unwrap_or_default()),
-            client_id: Set(request.client_id),
-            sender_id: Set(request.sender_id),
-            sender_ip: Set(request.sender_ip),
-            sender_account: Set(sender_account.account),
-            task_name: Set(Self::get_task_name(&request.templates)),
-            notify_level: Set(notify_level),
-            task_status: Set(enums::TaskStatus::Pending),
-            receiver_count: Set(receivers.len() as i32),
-            receiver_account: Set(receivers
-                .iter()
-                .map(|account| account.account.to_owned())
-                .collect()),
-            receiver_id: Set(receivers.iter().map(|account| account.user_id).collect()),
-            create_at: NotSet,
-            update_at: NotSet,
-            error_message: NotSet,
-        }
-        .insert(&txn)
-        .await
-        .map_err(|e| {
-            warn!("insert backstage_send_task failed: {:?}", e);
-            KgsStatus::InternalServerError
-        })?;
-
-        // insert backstage_send_task_details
+        // transpose templates
         let mut templates_models = vec![];
-        for template in request.templates {
+        for template in request.templates.iter() {
             if template.title.len() > 0 && template.content.len() > 0 {
-                let entity = repository::backstage_send_task_detail::insert(
-                    &txn,
-                    task_id,
-                    enums::NotifyType::try_from(template.notify_type)?,
+                templates_models.push(mq_manager::TemplateModel {
+                    notify_type: enums::NotifyType::try_from(template.notify_type)?,
                     notify_level,
-                    template.title,
-                    template.content,
-                )
-                .await?;
-                templates_models.push(entity.to_publish_model());
+                    title: template.title.clone(),
+                    content: template.content.clone(),
+                });
             }
         }
 
-        // push to notify_queue
-        let mq_task = task_entity.to_publish_model(frontend_client_id, templates_models);
-        mq_manager::publish_batch_notify(&mq_task)
-            .await
-            .map_err(|e| {
-                warn!("publish batch notify failed: {:?}", e);
-                KgsStatus::InternalServerError
-            })?;
+        // insert a new backstage_send_task
+        let (_task_id, mq_task) = Self::create_send_task(
+            &txn,
+            frontend_client_id,
+            request.client_id,
+            request.client_event_id.unwrap_or_default(),
+            request.sender_id,
+            sender_account.account,
+            request.sender_ip,
+            Self::get_task_name(&request.templates),
+            notify_level,
+            &receivers,
+            templates_models,
+            None,
+        )
+        .await?;
 
         // commit
         txn.commit().await.map_err(|e| {
@@ -608,6 +776,9 @@ impl BackstageNotifyServer {
             KgsStatus::InternalServerError
         })?;
 
+        // push to notify_queue (commit之後才推送 避免rollback後仍發送沒有任務的通知)
+        Self::publish_send_task(&mq_task).await?;
+
         Ok(backstage_notify::Empty {})
     }
 
@@ -726,9 +897,16 @@ impl BackstageNotifyServer {
     ) -> Result<backstage_notify::NotifyTaskDetailList, KgsStatus> {
         let db = database_manager::sea_orm::get_db();
 
-        let details =
-            repository::backstage_send_task_detail::find_list_by_task_id(&*db, request.task_id)
-                .await?;
+        // 任務不存在或屬於其他client時都視為找不到
+        let details = repository::backstage_send_task_detail::find_list_by_task_id(
+            &*db,
+            request.client_id,
+            request.task_id,
+        )
+        .await?;
+        if details.is_empty() {
+            return Err(KgsStatus::DataNotFound);
+        }
 
         Ok(NotifyTaskDetailList {
             list: details
@@ -883,9 +1061,595 @@ impl BackstageNotifyServer {
 
         Ok(Empty {})
     }
+
+    #[tracing::instrument]
+    pub async fn create_recurring_campaign(
+        request: backstage_notify::CreateRecurringCampaignRequest,
+    ) -> Result<backstage_notify::Empty, KgsStatus> {
+        // transpose request
+        let notify_level = enums::NotifyLevel::try_from(request.notify_level)?;
+        let start_at = request.start_at.map(Self::to_naive_date_time).transpose()?;
+        let end_at = request.end_at.map(Self::to_naive_date_time).transpose()?;
+        Self::check_campaign_window(start_at, end_at)?;
+
+        // 必須指定發送對象
+        if !request.is_all && request.receiver_ids.is_empty() && request.vip_levels.is_empty() {
+            return Err(KgsStatus::InvalidArgument);
+        }
+
+        // calculate the first run time (also validate cron expression and timezone)
+        let next_run_at = entity::recurring_campaign::get_next_run_at(
+            &request.cron_expression,
+            &request.timezone,
+            start_at,
+            end_at,
+            chrono::Utc::now().naive_utc(),
+        )?;
+
+        // get sender's user profile
+        let sender_account =
+            application::user_rpc::get_account_by_user_id(request.client_id, request.sender_id)
+                .await?;
+
+        // get transaction
+        let txn = database_manager::sea_orm::get_trans().await.map_err(|e| {
+            warn!("get db failed: {:?}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+        // insert recurring_campaign
+        let campaign_id = helper::generate_snowflake_id().await;
+        entity::recurring_campaign::ActiveModel {
+            id: Set(campaign_id),
+            client_id: Set(request.client_id),
+            client_event_id: Set(request.client_event_id.unwrap_or_default()),
+            sender_id: Set(request.sender_id),
+            sender_account: Set(sender_account.account),
+            sender_ip: Set(request.sender_ip),
+            campaign_name: Set(request.campaign_name),
+            cron_expression: Set(request.cron_expression),
+            timezone: Set(request.timezone),
+            notify_level: Set(notify_level),
+            is_all: Set(request.is_all),
+            receiver_ids: Set(request.receiver_ids),
+            vip_levels: Set(request.vip_levels),
+            is_active: Set(true),
+            start_at: Set(start_at),
+            end_at: Set(end_at),
+            next_run_at: Set(next_run_at),
+            last_run_at: Set(None),
+            create_at: NotSet,
+            update_at: NotSet,
+        }
+        .insert(&txn)
+        .await
+        .map_err(|e| {
+            warn!("insert recurring_campaign failed: {:?}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+        // insert recurring_campaign_details
+        Self::insert_campaign_details(&txn, campaign_id, request.templates).await?;
+
+        // commit
+        txn.commit().await.map_err(|e| {
+            warn!("commit failed: {:?}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+        Ok(Empty {})
+    }
+
+    #[tracing::instrument]
+    pub async fn update_recurring_campaign(
+        request: backstage_notify::UpdateRecurringCampaignRequest,
+    ) -> Result<backstage_notify::Empty, KgsStatus> {
+        // transpose request
+        let notify_level = enums::NotifyLevel::try_from(request.notify_level)?;
+        let start_at = request.start_at.map(Self::to_naive_date_time).transpose()?;
+        let end_at = request.end_at.map(Self::to_naive_date_time).transpose()?;
+        Self::check_campaign_window(start_at, end_at)?;
+
+        // 必須指定發送對象
+        if !request.is_all && request.receiver_ids.is_empty() && request.vip_levels.is_empty() {
+            return Err(KgsStatus::InvalidArgument);
+        }
+
+        // recalculate the next run time with the new schedule
+        let next_run_at = entity::recurring_campaign::get_next_run_at(
+            &request.cron_expression,
+            &request.timezone,
+            start_at,
+            end_at,
+            chrono::Utc::now().naive_utc(),
+        )?;
+
+        // get transaction
+        let txn = database_manager::sea_orm::get_trans().await.map_err(|e| {
+            warn!("get db failed: {:?}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+        // update recurring_campaign
+        let campaign = repository::recurring_campaign::find_one_by_id_and_client_id(
+            &txn,
+            request.campaign_id,
+            request.client_id,
+        )
+        .await?;
+        let mut active_model: entity::recurring_campaign::ActiveModel = campaign.into();
+        active_model.campaign_name = Set(request.campaign_name);
+        active_model.cron_expression = Set(request.cron_expression);
+        active_model.timezone = Set(request.timezone);
+        active_model.notify_level = Set(notify_level);
+        active_model.is_all = Set(request.is_all);
+        active_model.receiver_ids = Set(request.receiver_ids);
+        active_model.vip_levels = Set(request.vip_levels);
+        active_model.is_active = Set(request.is_active);
+        active_model.start_at = Set(start_at);
+        active_model.end_at = Set(end_at);
+        active_model.next_run_at = Set(next_run_at);
+        active_model.update(&txn).await.map_err(|e| {
+            warn!("update recurring_campaign failed: {:?}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+        // replace recurring_campaign_details
+        if !request.templates.is_empty() {
+            repository::recurring_campaign_detail::delete_by_campaign_id(&txn, request.campaign_id)
+                .await?;
+            Self::insert_campaign_details(&txn, request.campaign_id, request.templates).await?;
+        }
+
+        // commit
+        txn.commit().await.map_err(|e| {
+            warn!("commit failed: {:?}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+        Ok(Empty {})
+    }
+
+    #[tracing::instrument]
+    pub async fn delete_recurring_campaign(
+        request: backstage_notify::DeleteRecurringCampaignRequest,
+    ) -> Result<backstage_notify::Empty, KgsStatus> {
+        let txn = database_manager::sea_orm::get_trans().await.map_err(|e| {
+            warn!("get db failed: {:?}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+        // delete recurring_campaign
+        repository::recurring_campaign::delete(&txn, request.campaign_id, request.client_id)
+            .await?;
+
+        // delete recurring_campaign_details
+        repository::recurring_campaign_detail::delete_by_campaign_id(&txn, request.campaign_id)
+            .await?;
+
+        // commit
+        txn.commit().await.map_err(|e| {
+            warn!("commit failed: {:?}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+        Ok(Empty {})
+    }
+
+    #[tracing::instrument]
+    pub async fn get_recurring_campaign_list(
+        request: backstage_notify::GetRecurringCampaignListRequest,
+    ) -> Result<backstage_notify::RecurringCampaignList, KgsStatus> {
+        let db = database_manager::sea_orm::get_db();
+
+        let page_size = request.page_size.unwrap_or(NOTIFY_PAGE_SIZE).max(1);
+        let now_page = request.now_page.unwrap_or(1).max(1);
+
+        let (entities, total_rows, total_page) =
+            repository::recurring_campaign::get_recurring_campaign_list(
+                &*db, page_size, now_page, request,
+            )
+            .await?;
+
+        Ok(backstage_notify::RecurringCampaignList {
+            list: entities
+                .into_iter()
+                .map(|entity| entity.to_proto())
+                .collect(),
+            total_rows,
+            total_page,
+            now_page,
+        })
+    }
+
+    #[tracing::instrument]
+    pub async fn get_recurring_campaign_details(
+        request: backstage_notify::GetRecurringCampaignDetailsRequest,
+    ) -> Result<backstage_notify::NotifyTaskDetailList, KgsStatus> {
+        let db = database_manager::sea_orm::get_db();
+
+        // check the campaign belongs to the client
+        repository::recurring_campaign::find_one_by_id_and_client_id(
+            &*db,
+            request.campaign_id,
+            request.client_id,
+        )
+        .await?;
+
+        let details = repository::recurring_campaign_detail::find_list_by_campaign_id(
+            &*db,
+            request.campaign_id,
+        )
+        .await?;
+
+        Ok(NotifyTaskDetailList {
+            list: details
+                .into_iter()
+                .map(|entity| entity.to_proto())
+                .collect(),
+        })
+    }
+
+    #[tracing::instrument]
+    pub async fn get_quarantine_record_list(
+        request: backstage_notify::GetQuarantineRecordListRequest,
+    ) -> Result<backstage_notify::QuarantineRecordList, KgsStatus> {
+        let db = database_manager::sea_orm::get_db();
+
+        let page_size = request.page_size.unwrap_or(NOTIFY_PAGE_SIZE).max(1);
+        let now_page = request.now_page.unwrap_or(1).max(1);
+
+        let (entities, total_rows, total_page) =
+            repository::mq_quarantine_record::get_quarantine_record_list(
+                &*db, page_size, now_page, request,
+            )
+            .await?;
+
+        Ok(backstage_notify::QuarantineRecordList {
+            list: entities
+                .into_iter()
+                .map(|entity| entity.to_proto())
+                .collect(),
+            total_rows,
+            total_page,
+            now_page,
+        })
+    }
+
+    /// 將隔離的訊息重新發送到原本的queue 可傳入修正後的payload取代原始內容
+    #[tracing::instrument]
+    pub async fn replay_quarantine_record(
+        request: backstage_notify::ReplayQuarantineRecordRequest,
+    ) -> Result<backstage_notify::Empty, KgsStatus> {
+        let db = database_manager::sea_orm::get_db();
+
+        let record = repository::mq_quarantine_record::find_one_by_id(&*db, request.id).await?;
+        let queue = mq_manager::QueueName::try_from(record.queue_name.as_str()).map_err(|e| {
+            warn!("replay quarantine record failed: {}", e);
+            KgsStatus::InternalServerError
+        })?;
+        let payload = match request.payload {
+            Some(payload) => payload.into_bytes(),
+            None => record.payload,
+        };
+
+        // 重新發送前先確認payload可以被consumer解析 避免再次被隔離
+        let parse_result = match queue {
+            mq_manager::QueueName::SingleNotify => {
+                mq_manager::envelope::decode::<mq_manager::SingleNotifyModel>(&payload).map(|_| ())
+            }
+            mq_manager::QueueName::BatchNotify => {
+                mq_manager::envelope::decode::<mq_manager::BatchNotifyModel>(&payload).map(|_| ())
+            }
+            mq_manager::QueueName::DeadLetter => {
+                warn!("dead letter queue cannot be replayed");
+                return Err(KgsStatus::InvalidArgument);
+            }
+        };
+        parse_result.map_err(|e| {
+            warn!("quarantine payload still invalid: {}", e);
+            KgsStatus::InvalidArgument
+        })?;
+
+        // publish to original queue
+        mq_manager::get_message_queue()
+            .publish(
+                queue,
+                payload,
+                mq_manager::trace_context::inject_current_context(),
+            )
+            .await
+            .map_err(|e| {
+                warn!("replay quarantine record publish failed: {:?}", e);
+                KgsStatus::InternalServerError
+            })?;
+
+        repository::mq_quarantine_record::update_replayed(
+            &*db,
+            record.id,
+            chrono::Utc::now().naive_utc(),
+        )
+        .await?;
+
+        Ok(Empty {})
+    }
+
+    /// 取得用戶是否在線與最後在線時間 在線狀態由所有pod的心跳彙整
+    #[tracing::instrument]
+    pub async fn get_online_status(
+        request: backstage_notify::GetOnlineStatusRequest,
+    ) -> Result<backstage_notify::GetOnlineStatusResponse, KgsStatus> {
+        let platform = enums::Platform::try_from(request.platform)?;
+        let client_id = Self::get_presence_client_id(
+            &platform,
+            request.client_id,
+            application::oauth_rpc::get_frontend_client,
+        )
+        .await?;
+
+        let statuses = application::notify_handler::get_online_status_from_redis(
+            &platform,
+            client_id,
+            &request.user_ids,
+        )?;
+
+        Ok(backstage_notify::GetOnlineStatusResponse {
+            list: request
+                .user_ids
+                .into_iter()
+                .zip(statuses)
+                .map(
+                    |(user_id, (is_online, last_seen_at))| backstage_notify::UserOnlineStatus {
+                        user_id,
+                        is_online,
+                        last_seen_at,
+                    },
+                )
+                .collect(),
+        })
+    }
+
+    /// 取得client在前台與後台的在線人數 只能查詢token所屬的client
+    #[tracing::instrument]
+    pub async fn get_online_count(
+        request: backstage_notify::GetOnlineCountRequest,
+    ) -> Result<backstage_notify::GetOnlineCountResponse, KgsStatus> {
+        let mut list = vec![];
+        for platform in [enums::Platform::Frontend, enums::Platform::Backstage] {
+            let client_id = Self::get_presence_client_id(
+                &platform,
+                request.client_id,
+                application::oauth_rpc::get_frontend_client,
+            )
+            .await?;
+            let count = application::notify_handler::get_online_user_count_from_redis(
+                &platform, client_id,
+            )?;
+            list.push(backstage_notify::OnlineCount {
+                client_id: request.client_id,
+                platform: platform.to_id(),
+                count,
+            });
+        }
+
+        Ok(backstage_notify::GetOnlineCountResponse { list })
+    }
+
+    /// 分頁取得在線用戶 依user_id排序
+    #[tracing::instrument]
+    pub async fn get_online_user_list(
+        request: backstage_notify::GetOnlineUserListRequest,
+    ) -> Result<backstage_notify::GetOnlineUserListResponse, KgsStatus> {
+        let platform = enums::Platform::try_from(request.platform)?;
+
+        let page_size = request.page_size.unwrap_or(NOTIFY_PAGE_SIZE).max(1);
+        let now_page = request.now_page.unwrap_or(1).max(1);
+
+        // get all online users
+        let client_id = Self::get_presence_client_id(
+            &platform,
+            request.client_id,
+            application::oauth_rpc::get_frontend_client,
+        )
+        .await?;
+        let mut users =
+            application::notify_handler::get_online_users_from_redis(&platform, client_id)?;
+        users.sort_by_key(|(user_id, _)| *user_id);
+
+        let total_rows = users.len() as u64;
+        let total_page = (total_rows as f64 / page_size as f64).ceil() as u64;
+
+        Ok(backstage_notify::GetOnlineUserListResponse {
+            list: users
+                .into_iter()
+                .skip(((now_page - 1) * page_size) as usize)
+                .take(page_size as usize)
+                .map(|(user_id, last_seen_at)| backstage_notify::OnlineUser {
+                    user_id,
+                    last_seen_at,
+                })
+                .collect(),
+            total_rows,
+            total_page,
+            now_page,
+        })
+    }
+
+    /// 前台用戶的在線狀態以前台client記錄 查詢前需將後台client轉成對應的前台client
+    async fn get_presence_client_id<F, Fut>(
+        platform: &enums::Platform,
+        client_id: i64,
+        get_frontend_client: F,
+    ) -> Result<i64, KgsStatus>
+    where
+        F: FnOnce(i64) -> Fut,
+        Fut: std::future::Future<Output = Result<i64, KgsStatus>>,
+    {
+        match platform {
+            enums::Platform::Frontend => get_frontend_client(client_id).await,
+            _ => Ok(client_id),
+        }
+    }
 }
 
 impl BackstageNotifyServer {
+    /// 依發送對象條件取得前台用戶帳號
+    #[tracing::instrument]
+    pub async fn get_receivers(
+        frontend_client_id: i64,
+        is_all: bool,
+        receiver_ids: Vec<i64>,
+        vip_levels: Vec<i64>,
+    ) -> Result<Vec<protos::player::UserAccount>, KgsStatus> {
+        let receivers = if is_all {
+            application::user_rpc::get_accounts_by_client_id(frontend_client_id)
+                .await?
+                .user_accounts
+        } else if receiver_ids.len() > 0 {
+            application::user_rpc::get_accounts_by_user_ids(frontend_client_id, receiver_ids)
+                .await?
+                .user_accounts
+        } else if vip_levels.len() > 0 {
+            application::user_rpc::get_accounts_by_vip_level(frontend_client_id, vip_levels)
+                .await?
+                .user_accounts
+        } else {
+            return Err(KgsStatus::InvalidArgument);
+        };
+
+        Ok(receivers)
+    }
+
+    /// 新增後台發送任務與任務詳情 回傳推送至notify_queue的內容
+    /// 需在交易commit之後再以publish_send_task推送 避免rollback後仍發送
+    pub async fn create_send_task<C>(
+        txn: &C,
+        frontend_client_id: i64,
+        client_id: i64,
+        client_event_id: i64,
+        sender_id: i64,
+        sender_account: String,
+        sender_ip: Option<String>,
+        task_name: String,
+        notify_level: enums::NotifyLevel,
+        receivers: &Vec<protos::player::UserAccount>,
+        templates: Vec<mq_manager::TemplateModel>,
+        recurring_campaign_id: Option<i64>,
+    ) -> Result<(i64, mq_manager::BatchNotifyModel), KgsStatus>
+    where
+        C: sea_orm::ConnectionTrait,
+    {
+        // insert a new backstage_send_task
+        let task_id = helper::generate_snowflake_id().await;
+        let task_entity = entity::backstage_send_task::ActiveModel {
+            id: Set(task_id),
+            client_event_id: Set(client_event_id),
+            client_id: Set(client_id),
+            sender_id: Set(sender_id),
+            sender_ip: Set(sender_ip),
+            sender_account: Set(sender_account),
+            task_name: Set(task_name),
+            notify_level: Set(notify_level),
+            task_status: Set(enums::TaskStatus::Pending),
+            receiver_count: Set(receivers.len() as i32),
+            receiver_account: Set(receivers
+                .iter()
+                .map(|account| account.account.to_owned())
+                .collect()),
+            receiver_id: Set(receivers.iter().map(|account| account.user_id).collect()),
+            recurring_campaign_id: Set(recurring_campaign_id),
+            create_at: NotSet,
+            update_at: NotSet,
+            error_message: NotSet,
+        }
+        .insert(txn)
+        .await
+        .map_err(|e| {
+            warn!("insert backstage_send_task failed: {:?}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+        // insert backstage_send_task_details
+        let mut templates_models = vec![];
+        for template in templates {
+            let entity = repository::backstage_send_task_detail::insert(
+                txn,
+                task_id,
+                template.notify_type,
+                template.notify_level,
+                template.title,
+                template.content,
+            )
+            .await?;
+            templates_models.push(entity.to_publish_model());
+        }
+
+        let mq_task = task_entity.to_publish_model(frontend_client_id, templates_models);
+
+        Ok((task_id, mq_task))
+    }
+
+    /// 推送後台發送任務至notify_queue
+    pub async fn publish_send_task(
+        mq_task: &mq_manager::BatchNotifyModel,
+    ) -> Result<(), KgsStatus> {
+        mq_manager::publish_batch_notify(mq_task)
+            .await
+            .map_err(|e| {
+                warn!("publish batch notify failed: {:?}", e);
+                KgsStatus::InternalServerError
+            })
+    }
+
+    async fn insert_campaign_details<C>(
+        txn: &C,
+        campaign_id: i64,
+        templates: Vec<backstage_notify::Template>,
+    ) -> Result<(), KgsStatus>
+    where
+        C: sea_orm::ConnectionTrait,
+    {
+        let mut count = 0;
+        for template in templates {
+            if template.title.len() > 0 && template.content.len() > 0 {
+                repository::recurring_campaign_detail::insert(
+                    txn,
+                    campaign_id,
+                    enums::NotifyType::try_from(template.notify_type)?,
+                    template.title,
+                    template.content,
+                )
+                .await?;
+                count += 1;
+            }
+        }
+
+        // 至少需要一個有效模板
+        if count == 0 {
+            return Err(KgsStatus::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
+    fn check_campaign_window(
+        start_at: Option<chrono::NaiveDateTime>,
+        end_at: Option<chrono::NaiveDateTime>,
+    ) -> Result<(), KgsStatus> {
+        if let (Some(start_at), Some(end_at)) = (start_at, end_at) {
+            if start_at >= end_at {
+                return Err(KgsStatus::InvalidArgument);
+            }
+        }
+        Ok(())
+    }
+
+    fn to_naive_date_time(timestamp_millis: i64) -> Result<chrono::NaiveDateTime, KgsStatus> {
+        chrono::DateTime::from_timestamp_millis(timestamp_millis)
+            .map(|date_time| date_time.naive_utc())
+            .ok_or(KgsStatus::InvalidArgument)
+    }
+
     fn get_task_name(templates: &Vec<backstage_notify::Template>) -> String {
         let mut task_name = String::new();
         for template in templates {
@@ -895,3 +1659,32 @@ impl BackstageNotifyServer {
         task_name
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn frontend_presence_uses_frontend_client() {
+        let client_id = BackstageNotifyServer::get_presence_client_id(
+            &enums::Platform::Frontend,
+            1,
+            |backstage_client_id| async move { Ok(backstage_client_id + 100) },
+        )
+        .await
+        .unwrap();
+        assert_eq!(client_id, 101);
+    }
+
+    #[tokio::test]
+    async fn backstage_presence_uses_backstage_client() {
+        let client_id = BackstageNotifyServer::get_presence_client_id(
+            &enums::Platform::Backstage,
+            1,
+            |_| async { Err(KgsStatus::InternalServerError) },
+        )
+        .await
+        .unwrap();
+        assert_eq!(client_id, 1);
+    }
+}
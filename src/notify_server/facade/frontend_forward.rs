@@ -0,0 +1,162 @@
+use crate::config;
+use crate::enums;
+use crate::notify_server::application;
+use crate::notify_server::controller::frontend_notify::FrontendNotifyServer;
+use frontend_notify_service_client::FrontendNotifyServiceClient;
+use kgs_err::models::status::Status as KgsStatus;
+use kgs_tracing::{tracing, warn};
+use protos::frontend_notify::*;
+
+/// 需送到用戶所在pod處理的事件
+#[derive(Debug, Clone)]
+pub(super) enum PodEvent {
+    Notify(ForwardNotifyRequest),
+    RevokeSessions(RevokeSessionsRequest),
+    RecordsChanged(ForwardRecordsChangedRequest),
+    TopicSubscription(ForwardTopicSubscriptionRequest),
+}
+
+impl PodEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            PodEvent::Notify(_) => "notify",
+            PodEvent::RevokeSessions(_) => "revoke sessions",
+            PodEvent::RecordsChanged(_) => "records changed",
+            PodEvent::TopicSubscription(_) => "topic subscription",
+        }
+    }
+
+    fn into_bus_event(self) -> Result<application::notify_bus::BusEvent, KgsStatus> {
+        let event = match self {
+            PodEvent::Notify(request) => {
+                let notify = request.notify.ok_or(KgsStatus::MissingBodyArgument)?;
+                application::notify_bus::BusEvent::FrontendNotify {
+                    client_id: request.client_id,
+                    user_id: request.user_id,
+                    notify_id: notify.notify_id,
+                    notify_level: notify.notify_level,
+                    title: notify.title,
+                    content: notify.content,
+                }
+            }
+            PodEvent::RevokeSessions(request) => {
+                application::notify_bus::BusEvent::RevokeSessions {
+                    client_id: request.client_id,
+                    user_id: request.user_id,
+                    reason: request.reason,
+                }
+            }
+            PodEvent::RecordsChanged(request) => {
+                application::notify_bus::BusEvent::FrontendRecordsChanged {
+                    client_id: request.client_id,
+                    user_id: request.user_id,
+                    notify_ids: request.notify_ids,
+                    notify_status: request.notify_status,
+                    unread_count: request.unread_count,
+                }
+            }
+            PodEvent::TopicSubscription(request) => {
+                let subscription = request.subscription.ok_or(KgsStatus::MissingBodyArgument)?;
+                application::notify_bus::BusEvent::TopicSubscription {
+                    client_id: subscription.client_id,
+                    user_id: subscription.user_id,
+                    session_id: subscription.session_id,
+                    topics: subscription.topics,
+                    is_subscribe: request.is_subscribe,
+                }
+            }
+        };
+
+        Ok(event)
+    }
+}
+
+impl FrontendNotifyServer {
+    /// 送到用戶所在的所有pod 此pod直接處理 其他pod轉發
+    /// 單一pod失敗不影響其他pod 回傳用戶所在的pod數量
+    #[tracing::instrument]
+    pub(super) async fn dispatch_to_user_pods(
+        &self,
+        client_id: i64,
+        user_id: i64,
+        event: PodEvent,
+    ) -> Result<usize, KgsStatus> {
+        // get user location servers from redis
+        let pod_ips =
+            application::notify_handler::get_user_located_servers_from_redis(client_id, user_id)?;
+
+        for pod_ip in pod_ips.iter() {
+            // if the user located in the same server handle the event
+            // else send the event to the user located server
+            let result = if &config::config::get_kubernetes().pod_ip == pod_ip {
+                self.handle_pod_event(event.clone()).await
+            } else {
+                Self::forward_to_pod(pod_ip, event.clone()).await
+            };
+
+            if let Err(err) = result {
+                warn!("send {} to pod {} failed: {}", event.name(), pod_ip, err);
+            }
+        }
+
+        Ok(pod_ips.len())
+    }
+
+    /// 處理送到此pod的事件
+    async fn handle_pod_event(&self, event: PodEvent) -> Result<(), KgsStatus> {
+        match event {
+            PodEvent::Notify(request) => {
+                self.handle_forward_notify(request).await?;
+            }
+            PodEvent::RevokeSessions(request) => {
+                self.revoke_local_sessions(request.client_id, request.user_id, &request.reason)
+                    .await?;
+            }
+            PodEvent::RecordsChanged(request) => {
+                self.handle_records_changed(
+                    request.client_id,
+                    request.user_id,
+                    &request.notify_ids,
+                    enums::NotifyStatus::try_from(request.notify_status)?,
+                    request.unread_count,
+                )
+                .await?;
+            }
+            PodEvent::TopicSubscription(request) => {
+                let subscription = request.subscription.ok_or(KgsStatus::MissingBodyArgument)?;
+                self.handle_topic_subscription(&subscription, request.is_subscribe);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 轉發事件到用戶所在的pod
+    async fn forward_to_pod(pod_ip: &str, event: PodEvent) -> Result<(), KgsStatus> {
+        // 透過redis pub/sub轉發 不需要直接連線到該pod
+        if application::notify_bus::is_enabled() {
+            return application::notify_bus::publish_to_pod(pod_ip, event.into_bus_event()?);
+        }
+
+        let mut client =
+            FrontendNotifyServiceClient::new(application::pod_channel::get_channel(pod_ip)?);
+
+        let name = event.name();
+        let result = match event {
+            PodEvent::Notify(request) => client.forward_notify(request).await,
+            PodEvent::RevokeSessions(request) => client.forward_revoke_sessions(request).await,
+            PodEvent::RecordsChanged(request) => client.forward_records_changed(request).await,
+            PodEvent::TopicSubscription(request) => {
+                client.forward_topic_subscription(request).await
+            }
+        };
+
+        result.map_err(|err| {
+            warn!("Failed to forward {}: {:?}", name, err);
+            application::pod_channel::evict_if_unavailable(pod_ip, &err);
+            KgsStatus::InternalServerError
+        })?;
+
+        Ok(())
+    }
+}
@@ -1,17 +1,24 @@
+use super::frontend_forward::PodEvent;
 use crate::enums::{NotifyLevel, NotifyStatus};
+use crate::helper;
 use crate::notify_server::application;
-use crate::notify_server::controller::frontend_notify::FrontendNotifyServer;
+use crate::notify_server::controller::frontend_notify::{
+    FrontendNotifyServer, PendingAck, SessionInfo, FRONTEND_NOTIFY_SERVER,
+};
 use crate::notify_server::model;
 use crate::repository;
-use crate::{config, helper};
 use crate::{enums, mq_manager};
-use frontend_notify_service_client::FrontendNotifyServiceClient;
 use kgs_err::models::status::Status as KgsStatus;
 use kgs_tracing::{tracing, warn};
 use protos::frontend_notify::{self, *};
-use tokio::sync::mpsc::Receiver;
+use std::collections::{HashMap, HashSet};
+use tokio::sync::mpsc::{Receiver, Sender};
 
 const NOTIFY_PAGE_SIZE: u64 = 10;
+const REPLAY_PAGE_SIZE: u64 = 100; // 重新連線時每次查詢補發的通知數量
+const REPLAY_LIVE_BUFFER_SIZE: usize = 100; // 補發期間暫存即時通知的數量
+const ACK_TIMEOUT_MILLIS: i64 = 30 * 1000; // 推送後超過30秒未確認收到則重新推送
+const MAX_REDELIVER_TIMES: u32 = 3; // 最多重新推送的次數
 
 impl FrontendNotifyServer {
     #[tracing::instrument(skip_all)]
@@ -19,8 +26,42 @@ impl FrontendNotifyServer {
         &self,
         request: ConnectionRequest,
     ) -> Result<Receiver<Result<frontend_notify::Receiver, tonic::Status>>, KgsStatus> {
+        // 連線時可一併訂閱主題
+        Self::check_topics(&request.topics)?;
+
+        // 沒有帶session_id時由server產生
+        let session_id = match request.session_id {
+            Some(session_id) => session_id,
+            None => helper::generate_snowflake_id().await.to_string(),
+        };
+
         // create channel
-        let (tx, rx) = tokio::sync::mpsc::channel(1); // 先測試 每一個user一個channel 避免爆掉
+        let (tx, rx) = tokio::sync::mpsc::channel(application::stream_sender::get_buffer_size(
+            &enums::Platform::Frontend,
+        ));
+
+        // 第一則訊息告知session_id 用戶端關閉連線時需帶入 才能只關閉自己的連線
+        let session_created = frontend_notify::Receiver {
+            message: Some(frontend_notify::receiver::Message::SessionCreated(
+                frontend_notify::SessionCreated {
+                    session_id: session_id.clone(),
+                },
+            )),
+        };
+        tx.try_send(Ok(session_created)).map_err(|e| {
+            warn!("send session created failed: {}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+        // 帶有游標時先補發離線期間的通知 補發完成前收到的即時通知暫存在live channel
+        let cursor = (request.last_notify_id, request.last_seen_at);
+        let (tx, replay) = match cursor {
+            (None, None) => (tx, None),
+            _ => {
+                let (live_tx, live_rx) = tokio::sync::mpsc::channel(REPLAY_LIVE_BUFFER_SIZE);
+                (live_tx, Some((tx, live_rx)))
+            }
+        };
 
         // create frontend user
         let frontend_user = model::User {
@@ -28,18 +69,129 @@ impl FrontendNotifyServer {
             user_id: request.user_id,
         };
 
-        // insert connection
-        {
-            let mut connections = self.connections.write().await;
-            connections.insert(frontend_user, tx);
-        }
+        let topics: HashSet<String> = request.topics.into_iter().collect();
+
+        let session = SessionInfo {
+            session_id: session_id.clone(),
+            device_info: request.device_info.unwrap_or_default(),
+            connected_at: chrono::Utc::now().timestamp_millis(),
+            topics: topics.clone(),
+            tx,
+        };
+
+        // insert connection, 同一session重新連線時取代舊的連線
+        self.connections
+            .entry(frontend_user.clone())
+            .or_default()
+            .insert(session_id, session);
+        self.index_topics(&frontend_user, topics.iter());
 
         // save user location server to redis
-        application::notify_handler::save_user_located_server_to_redis(request.user_id)?;
+        application::notify_handler::save_user_located_server_to_redis(&frontend_user)?;
+
+        // 連線與位置都已登記後才開始補發 之後的通知都會進入live channel 不會遺漏
+        if let Some((tx, live_rx)) = replay {
+            tokio::spawn(Self::replay_missed_notify(
+                request.client_id,
+                request.user_id,
+                request.last_notify_id,
+                request.last_seen_at,
+                tx,
+                live_rx,
+            ));
+        }
 
         Ok(rx)
     }
 
+    /// 補發游標之後的未讀通知 接著送出已補發完成的標記 再轉送即時通知
+    #[tracing::instrument(skip(tx, live_rx))]
+    async fn replay_missed_notify(
+        client_id: i64,
+        user_id: i64,
+        last_notify_id: Option<i64>,
+        last_seen_at: Option<i64>,
+        tx: Sender<Result<frontend_notify::Receiver, tonic::Status>>,
+        mut live_rx: Receiver<Result<frontend_notify::Receiver, tonic::Status>>,
+    ) {
+        let db = database_manager::sea_orm::get_db();
+        let last_seen_at = last_seen_at
+            .and_then(chrono::DateTime::from_timestamp_millis)
+            .map(|date_time| date_time.naive_utc());
+
+        // 分頁補發直到沒有遺漏的通知 才送出已補發完成的標記
+        let mut replayed_notify_ids = HashSet::new();
+        let mut last_replayed_notify_id = last_notify_id;
+        loop {
+            // get the missed notify records
+            let records = match repository::notify_record::find_unread_app_records_after(
+                &*db,
+                client_id,
+                user_id,
+                last_replayed_notify_id,
+                last_seen_at,
+                REPLAY_PAGE_SIZE,
+            )
+            .await
+            {
+                Ok(records) => records,
+                Err(err) => {
+                    // 無法補發時結束串流 用戶端以相同游標重新連線
+                    warn!("get missed notify records failed: {}", err);
+                    let _ = tx.send(Err(err.to_tonic_status())).await;
+                    return;
+                }
+            };
+            let is_last_page = (records.len() as u64) < REPLAY_PAGE_SIZE;
+
+            // replay the missed notify
+            for record in records {
+                replayed_notify_ids.insert(record.id);
+                last_replayed_notify_id = Some(record.id);
+                let response = frontend_notify::Receiver {
+                    message: Some(frontend_notify::receiver::Message::Notify(
+                        record.to_proto(),
+                    )),
+                };
+                if tx.send(Ok(response)).await.is_err() {
+                    return;
+                }
+            }
+
+            if is_last_page {
+                break;
+            }
+        }
+
+        // send caught up marker
+        let caught_up = frontend_notify::Receiver {
+            message: Some(frontend_notify::receiver::Message::CaughtUp(
+                frontend_notify::CaughtUp {
+                    last_notify_id: last_replayed_notify_id,
+                },
+            )),
+        };
+        if tx.send(Ok(caught_up)).await.is_err() {
+            return;
+        }
+
+        // forward the live notify, 已補發過的通知不再重複發送
+        while let Some(message) = live_rx.recv().await {
+            if let Ok(frontend_notify::Receiver {
+                message: Some(frontend_notify::receiver::Message::Notify(notify)),
+            }) = &message
+            {
+                if replayed_notify_ids.contains(&notify.notify_id) {
+                    continue;
+                }
+            }
+
+            if tx.send(message).await.is_err() {
+                return;
+            }
+        }
+    }
+
     #[tracing::instrument(skip_all)]
     pub async fn close_connection(&self, request: ConnectionRequest) -> Result<(), KgsStatus> {
         // create frontend user
@@ -48,20 +200,218 @@ impl FrontendNotifyServer {
             user_id: request.user_id,
         };
 
-        // remove connection
-        {
-            let mut connection = self.connections.write().await;
-            if let Some(tx) = connection.remove(&frontend_user) {
-                drop(tx);
+        // 只關閉指定的session 關閉用戶所有連線需使用revoke_sessions
+        let session_id = request.session_id.ok_or(KgsStatus::InvalidArgument)?;
+
+        // remove session
+        let has_other_sessions =
+            self.remove_sessions(&frontend_user, Some(std::slice::from_ref(&session_id)));
+
+        // remove user location server from redis if no session left on this pod
+        if !has_other_sessions {
+            application::notify_handler::remove_user_located_server_from_redis(&frontend_user)?;
+        }
+
+        Ok(())
+    }
+
+    /// 強制用戶下線 關閉用戶在所有pod上的連線 用於帳號停用或重設密碼
+    #[tracing::instrument]
+    pub async fn revoke_sessions(
+        &self,
+        request: RevokeSessionsRequest,
+    ) -> Result<Empty, KgsStatus> {
+        let frontend_user = model::User {
+            client_id: request.client_id,
+            user_id: request.user_id,
+        };
+
+        // 失敗的pod上的連線仍會因位置被清除而無法收到通知
+        self.dispatch_to_user_pods(
+            request.client_id,
+            request.user_id,
+            PodEvent::RevokeSessions(request.clone()),
+        )
+        .await?;
+
+        // clear the user location servers from redis
+        application::notify_handler::clear_user_located_servers_from_redis(&frontend_user)?;
+
+        Ok(Empty {})
+    }
+
+    /// 關閉用戶在此pod上的所有連線 關閉前送出連線已被撤銷的訊息
+    #[tracing::instrument]
+    pub async fn revoke_local_sessions(
+        &self,
+        client_id: i64,
+        user_id: i64,
+        reason: &str,
+    ) -> Result<(), KgsStatus> {
+        let frontend_user = model::User { client_id, user_id };
+
+        // remove all sessions of the user
+        let sessions = match self.connections.remove(&frontend_user) {
+            Some((_, sessions)) => sessions,
+            None => return Ok(()),
+        };
+
+        // 最後一則訊息送出後釋放sender 連線在訊息送達後結束
+        let response = frontend_notify::Receiver {
+            message: Some(frontend_notify::receiver::Message::SessionRevoked(
+                frontend_notify::SessionRevoked {
+                    reason: reason.to_string(),
+                },
+            )),
+        };
+        for session in sessions.into_values() {
+            let result = application::stream_sender::try_send(
+                &enums::Platform::Frontend,
+                &session.tx,
+                response.clone(),
+            );
+            if result != application::stream_sender::SendResult::Sent {
+                warn!(
+                    "send session revoked error session_id: {} result: {:?}",
+                    session.session_id, result
+                );
             }
         }
 
-        // remove user location server from redis
-        application::notify_handler::remove_user_located_server_from_redis(request.user_id)?;
+        // 已下線的用戶不需要再重新推送
+        self.pending_acks.retain(|_, pending_ack| {
+            pending_ack.client_id != client_id || pending_ack.user_id != user_id
+        });
 
         Ok(())
     }
 
+    /// 移除用戶的連線 session_ids為None時移除全部 回傳用戶在此pod上是否仍有連線
+    pub(super) fn remove_sessions(
+        &self,
+        frontend_user: &model::User,
+        session_ids: Option<&[String]>,
+    ) -> bool {
+        let has_sessions = match self.connections.get_mut(frontend_user) {
+            Some(mut sessions) => {
+                match session_ids {
+                    Some(session_ids) => {
+                        for session_id in session_ids {
+                            sessions.remove(session_id);
+                        }
+                    }
+                    None => sessions.clear(),
+                }
+                !sessions.is_empty()
+            }
+            None => false,
+        };
+
+        // 移除時再次確認 避免移除其他請求剛建立的連線
+        if !has_sessions {
+            self.connections
+                .remove_if(frontend_user, |_, sessions| sessions.is_empty());
+        }
+
+        has_sessions
+    }
+
+    /// 推送訊息到用戶在此pod上的所有連線 不等待 並移除已關閉的連線
+    /// 暫存已滿而被丟棄的訊息由各自的補送機制處理 回傳未關閉的連線數量
+    pub(super) fn send_to_sessions(
+        &self,
+        frontend_user: &model::User,
+        messages: &[frontend_notify::Receiver],
+    ) -> Result<usize, KgsStatus> {
+        // 複製所有session的sender 避免發送時持有鎖
+        let sessions: Vec<(
+            String,
+            Sender<Result<frontend_notify::Receiver, tonic::Status>>,
+        )> = match self.connections.get(frontend_user) {
+            Some(sessions) => sessions
+                .values()
+                .map(|session| (session.session_id.clone(), session.tx.clone()))
+                .collect(),
+            None => vec![],
+        };
+
+        // send the messages to every session, collect the closed sessions
+        let mut closed_session_ids = vec![];
+        for (session_id, tx) in sessions.iter() {
+            for message in messages.iter() {
+                let result = application::stream_sender::try_send(
+                    &enums::Platform::Frontend,
+                    tx,
+                    message.clone(),
+                );
+                if result.should_remove() {
+                    warn!(
+                        "send message error session_id: {} result: {:?}",
+                        session_id, result
+                    );
+                    closed_session_ids.push(session_id.clone());
+                    break;
+                }
+            }
+        }
+
+        // remove the closed sessions
+        let has_sessions = if closed_session_ids.is_empty() {
+            !sessions.is_empty()
+        } else {
+            self.remove_sessions(frontend_user, Some(&closed_session_ids))
+        };
+
+        // if the user connection not found remove the user located server from redis
+        if !has_sessions {
+            application::notify_handler::remove_user_located_server_from_redis(frontend_user)?;
+        }
+
+        Ok(sessions.len() - closed_session_ids.len())
+    }
+
+    /// 移除此pod上已關閉的連線 回傳仍在線的用戶
+    #[tracing::instrument(skip_all)]
+    pub async fn refresh_sessions(&self) -> Vec<model::User> {
+        // 逐一鎖定每個用戶的連線 不需要鎖定整個連線表
+        let mut online_users = vec![];
+        let mut offline_users = vec![];
+        for mut sessions in self.connections.iter_mut() {
+            sessions.retain(|_, session| !session.tx.is_closed());
+            if sessions.is_empty() {
+                offline_users.push(sessions.key().clone());
+            } else {
+                online_users.push(sessions.key().clone());
+            }
+        }
+
+        // remove the offline users after the iteration
+        let mut removed_users = vec![];
+        for user in offline_users {
+            if self
+                .connections
+                .remove_if(&user, |_, sessions| sessions.is_empty())
+                .is_some()
+            {
+                removed_users.push(user);
+            }
+        }
+
+        // remove the user located server from redis
+        for user in removed_users {
+            if let Err(err) =
+                application::notify_handler::remove_user_located_server_from_redis(&user)
+            {
+                warn!(
+                    "remove user {} located server failed: {}",
+                    user.user_id, err
+                );
+            }
+        }
+
+        online_users
+    }
+
     #[tracing::instrument]
     pub async fn system_to_frontend_user(&self, request: SendRequest) -> Result<(), KgsStatus> {
         let db = database_manager::sea_orm::get_db();
@@ -79,15 +429,76 @@ impl FrontendNotifyServer {
             return Err(KgsStatus::InvalidArgument);
         }
 
+        // 延遲發送的通知先存入delay_notify 到時間後由排程發送
+        if let Some(send_at) = Self::get_send_at(request.send_at, request.delay_secs)? {
+            let txn = database_manager::sea_orm::get_trans().await.map_err(|e| {
+                warn!("get db failed: {:?}", e);
+                KgsStatus::InternalServerError
+            })?;
+
+            // 相同key尚未發送的通知會被新的通知取代
+            if let Some(delay_key) = &request.delay_key {
+                repository::delay_notify::cancel_pending_by_key(
+                    &txn,
+                    request.client_id,
+                    request.user_id,
+                    delay_key,
+                )
+                .await?;
+            }
+
+            repository::delay_notify::insert(
+                &txn,
+                request.client_id,
+                request.user_id,
+                notify_event,
+                request.delay_key.clone(),
+                &request.key_map,
+                send_at,
+                request
+                    .expires_at
+                    .and_then(chrono::DateTime::from_timestamp_millis)
+                    .map(|date_time| date_time.naive_utc()),
+            )
+            .await?;
+
+            txn.commit().await.map_err(|e| {
+                warn!("commit failed: {:?}", e);
+                KgsStatus::InternalServerError
+            })?;
+
+            return Ok(());
+        }
+
+        Self::publish_event_notify(
+            request.client_id,
+            request.user_id,
+            &notify_event,
+            &request.key_map,
+            request.expires_at,
+        )
+        .await
+    }
+
+    /// 依事件模板發送通知給前台用戶
+    #[tracing::instrument]
+    pub async fn publish_event_notify(
+        client_id: i64,
+        user_id: i64,
+        notify_event: &enums::NotifyEvent,
+        key_map: &HashMap<String, String>,
+        expires_at: Option<i64>,
+    ) -> Result<(), KgsStatus> {
+        let db = database_manager::sea_orm::get_db();
+
         // get user_profile
-        let user_profile =
-            application::user_rpc::get_user_profile(request.client_id, request.user_id).await?;
+        let user_profile = application::user_rpc::get_user_profile(client_id, user_id).await?;
 
         // get client_notify_template_entity
         let client_notify_template_entities =
             repository::client_notify_template::find_list_by_client_id_and_notify_event_is_on(
                 &*db,
-                request.client_id,
+                client_id,
                 notify_event.to_id(),
                 &enums::Language::Jp, // TODO: 目前只有日文,未來要從user_profile取得
             )
@@ -108,8 +519,8 @@ impl FrontendNotifyServer {
             mq_manager::publish_single_notify(&mq_manager::SingleNotifyModel {
                 notify_id,
                 client_event_id: template.client_notify_event,
-                client_id: request.client_id,
-                user_id: request.user_id,
+                client_id,
+                user_id,
                 sender_id: 0, // system 發送為0
                 sender_account: "System".to_string(),
                 sender_ip: None,
@@ -118,7 +529,8 @@ impl FrontendNotifyServer {
                 title: template.title.clone(),
                 content: template.content.clone(),
                 receive_address: receive_address.to_string(),
-                key_map: request.key_map.clone(),
+                key_map: key_map.clone(),
+                expires_at,
             })
             .await
             .map_err(|err| {
@@ -130,6 +542,49 @@ impl FrontendNotifyServer {
         Ok(())
     }
 
+    #[tracing::instrument]
+    pub async fn cancel_delay_notify(
+        request: CancelDelayNotifyRequest,
+    ) -> Result<Empty, KgsStatus> {
+        let db = database_manager::sea_orm::get_db();
+
+        let count = repository::delay_notify::cancel_pending_by_key(
+            &*db,
+            request.client_id,
+            request.user_id,
+            &request.delay_key,
+        )
+        .await?;
+
+        // 找不到尚未發送的通知
+        if count == 0 {
+            return Err(KgsStatus::DataNotFound);
+        }
+
+        Ok(Empty {})
+    }
+
+    /// 計算延遲通知的發送時間 send_at(毫秒)優先於delay_secs 不需延遲則回傳None
+    fn get_send_at(
+        send_at: Option<i64>,
+        delay_secs: Option<i64>,
+    ) -> Result<Option<chrono::NaiveDateTime>, KgsStatus> {
+        let now = chrono::Utc::now().naive_utc();
+        let send_at = match (send_at, delay_secs) {
+            (Some(send_at), _) => chrono::DateTime::from_timestamp_millis(send_at)
+                .map(|date_time| date_time.naive_utc())
+                .ok_or(KgsStatus::InvalidArgument)?,
+            (None, Some(delay_secs)) if delay_secs >= 0 => {
+                now + chrono::Duration::seconds(delay_secs)
+            }
+            (None, Some(_)) => return Err(KgsStatus::InvalidArgument),
+            (None, None) => return Ok(None),
+        };
+
+        // 發送時間已過則直接發送
+        Ok(Some(send_at).filter(|send_at| *send_at > now))
+    }
+
     #[tracing::instrument]
     pub async fn send_message_in_app(
         &self,
@@ -140,28 +595,26 @@ impl FrontendNotifyServer {
         title: &str,
         content: &str,
     ) -> Result<(), KgsStatus> {
-        // get user location server from redis
-        if let Some(pod_ip) =
-            application::notify_handler::get_user_located_server_from_redis(user_id)?
-        {
-            // if the user located in the same server handle the notify
-            // else send the notify to the user located server
-            if config::config::get_kubernetes().pod_ip == pod_ip {
-                self.handle_notify(client_id, user_id, notify_id, notify_level, title, content)
-                    .await?;
-            } else {
-                self.forward_notify(
-                    &pod_ip,
-                    client_id,
-                    user_id,
-                    notify_id,
-                    notify_level,
-                    title,
-                    content,
-                )
-                .await?;
-            }
-        }
+        let notify = frontend_notify::Notify {
+            notify_id,
+            notify_level: notify_level.to_id(),
+            title: title.to_string(),
+            content: content.to_string(),
+            create_at: chrono::Utc::now().timestamp_millis(),
+            notify_status: NotifyStatus::Unread as i32,
+        };
+
+        // 用戶可能同時連線在多個pod
+        self.dispatch_to_user_pods(
+            client_id,
+            user_id,
+            PodEvent::Notify(ForwardNotifyRequest {
+                client_id,
+                user_id,
+                notify: Some(notify),
+            }),
+        )
+        .await?;
 
         Ok(())
     }
@@ -176,20 +629,11 @@ impl FrontendNotifyServer {
         title: &str,
         content: &str,
     ) -> Result<(), KgsStatus> {
-        let result = (|| async {
-            let tx: tokio::sync::mpsc::Sender<Result<frontend_notify::Receiver, tonic::Status>> =
-                self.connections
-                    .read()
-                    .await
-                    .get(&model::User {
-                        client_id: client_id,
-                        user_id: user_id,
-                    })
-                    .ok_or(KgsStatus::UserConnectionNotFound)?
-                    .clone();
-
-            // create_notify_proto
-            let response = protos::frontend_notify::receiver::Message::Notify(
+        let frontend_user = model::User { client_id, user_id };
+
+        // create_notify_proto
+        let response = protos::frontend_notify::Receiver {
+            message: Some(protos::frontend_notify::receiver::Message::Notify(
                 protos::frontend_notify::Notify {
                     notify_id: notify_id,
                     notify_level: notify_level.to_id(),
@@ -198,26 +642,113 @@ impl FrontendNotifyServer {
                     create_at: chrono::Utc::now().timestamp_millis(),
                     notify_status: enums::NotifyStatus::Unread.to_id(),
                 },
-            );
+            )),
+        };
 
-            // send notify
-            tx.send(Ok(protos::frontend_notify::Receiver {
-                message: Some(response),
-            }))
-            .await
-            .map_err(|e| {
-                warn!("send notify error: {:?}", e);
-                KgsStatus::UserConnectionNotFound
-            })?;
+        // 暫存已滿而被丟棄的通知 由重新推送排程補送
+        let sent_count = self.send_to_sessions(&frontend_user, std::slice::from_ref(&response))?;
+        if sent_count == 0 {
+            return Err(KgsStatus::UserConnectionNotFound);
+        }
 
-            Ok::<(), KgsStatus>(())
-        })()
-        .await;
+        // 至少推送到一個裝置 等待裝置確認收到
+        self.pending_acks
+            .entry(notify_id)
+            .or_insert(PendingAck {
+                client_id,
+                user_id,
+                sent_at: 0,
+                redeliver_count: 0,
+            })
+            .sent_at = chrono::Utc::now().timestamp_millis();
 
-        // if the user connection not found remove the user located server from redis
-        if let Err(err) = result {
-            application::notify_handler::remove_user_located_server_from_redis(user_id)?;
-            return Err(err);
+        Ok(())
+    }
+
+    /// 裝置確認收到通知
+    #[tracing::instrument]
+    pub async fn ack_notify(&self, request: AckNotifyRequest) -> Result<Empty, KgsStatus> {
+        if request.notify_ids.is_empty() {
+            return Err(KgsStatus::InvalidArgument);
+        }
+
+        let db = database_manager::sea_orm::get_db();
+
+        // update notify_records to delivered
+        repository::notify_record::update_delivered(
+            &*db,
+            request.client_id,
+            request.user_id,
+            request.notify_ids.clone(),
+        )
+        .await?;
+
+        // remove pending acks, 推送通知的pod可能不是此pod 由重新推送時檢查狀態
+        for notify_id in request.notify_ids.iter() {
+            self.pending_acks.remove(notify_id);
+        }
+
+        Ok(Empty {})
+    }
+
+    /// 重新推送超時未確認收到的通知
+    #[tracing::instrument(skip_all)]
+    pub async fn redeliver_unacked_notify(&self) -> Result<(), KgsStatus> {
+        let now = chrono::Utc::now().timestamp_millis();
+
+        // get the timeout pending acks, 超過重新推送次數的通知不再推送
+        self.pending_acks.retain(|notify_id, pending_ack| {
+            let is_exhausted = pending_ack.sent_at + ACK_TIMEOUT_MILLIS <= now
+                && pending_ack.redeliver_count >= MAX_REDELIVER_TIMES;
+            if is_exhausted {
+                warn!("notify {} not acked after redeliver", notify_id);
+            }
+            !is_exhausted
+        });
+        let timeout_notify_ids: Vec<i64> = self
+            .pending_acks
+            .iter()
+            .filter(|pending_ack| pending_ack.sent_at + ACK_TIMEOUT_MILLIS <= now)
+            .map(|pending_ack| *pending_ack.key())
+            .collect();
+        if timeout_notify_ids.is_empty() {
+            return Ok(());
+        }
+
+        // 只重新推送仍未送達的通知 已確認 已讀或刪除的通知不再推送
+        let db = database_manager::sea_orm::get_db();
+        let records =
+            repository::notify_record::find_undelivered_by_ids(&*db, timeout_notify_ids.clone())
+                .await?;
+        for notify_id in timeout_notify_ids.iter() {
+            if !records.iter().any(|record| record.id == *notify_id) {
+                self.pending_acks.remove(notify_id);
+            }
+        }
+
+        // redeliver the notify
+        for record in records {
+            let notify_id = record.id;
+            match self.pending_acks.get_mut(&notify_id) {
+                Some(mut pending_ack) => pending_ack.redeliver_count += 1,
+                None => continue,
+            }
+
+            if let Err(err) = self
+                .handle_notify(
+                    record.client_id,
+                    record.user_id,
+                    notify_id,
+                    record.notify_level,
+                    &record.title,
+                    &record.content,
+                )
+                .await
+            {
+                // 用戶已離線 等待重新連線時補發
+                warn!("redeliver notify {} failed: {}", notify_id, err);
+                self.pending_acks.remove(&notify_id);
+            }
         }
 
         Ok(())
@@ -299,12 +830,18 @@ impl FrontendNotifyServer {
                 KgsStatus::InternalServerError
             })?;
 
+        // 用戶端只能將通知改為已讀或刪除 送達與過期由server更新
+        let notify_status = match NotifyStatus::try_from(request.notify_status)? {
+            notify_status @ (NotifyStatus::Read | NotifyStatus::Delete) => notify_status,
+            _ => return Err(KgsStatus::InvalidArgument),
+        };
+
         // update notify_records
         let result = repository::notify_record::update_notify_records(
             &txn,
             request.client_id,
             request.user_id,
-            NotifyStatus::try_from(request.notify_status)?,
+            notify_status.clone(),
             request.notify_ids,
         )
         .await?;
@@ -315,6 +852,15 @@ impl FrontendNotifyServer {
             KgsStatus::InternalServerError
         })?;
 
+        // 通知用戶其他連線更新狀態
+        Self::publish_records_changed(
+            request.client_id,
+            request.user_id,
+            result.iter().map(|record| record.id).collect(),
+            notify_status,
+        )
+        .await;
+
         Ok(UpdateNotifyRecordResponse {
             list: result.into_iter().map(|record| record.to_proto()).collect(),
         })
@@ -353,7 +899,7 @@ impl FrontendNotifyServer {
             .transpose()?;
 
         // update notify_records
-        let _all_entities = repository::notify_record::update_all_with_notify_level(
+        let all_entities = repository::notify_record::update_all_with_notify_level(
             &txn,
             request.client_id,
             request.user_id,
@@ -368,6 +914,15 @@ impl FrontendNotifyServer {
             KgsStatus::InternalServerError
         })?;
 
+        // 通知用戶其他連線更新狀態
+        Self::publish_records_changed(
+            request.client_id,
+            request.user_id,
+            all_entities.iter().map(|record| record.id).collect(),
+            enums::NotifyStatus::Read,
+        )
+        .await;
+
         Ok(Empty {})
     }
 
@@ -386,51 +941,6 @@ impl FrontendNotifyServer {
         Ok(entity.to_proto())
     }
 
-    /// 轉發通知
-    #[tracing::instrument]
-    pub async fn forward_notify(
-        &self,
-        pod_ip: &str,
-        client_id: i64,
-        user_id: i64,
-        notify_id: i64,
-        notify_level: enums::NotifyLevel,
-        title: &str,
-        content: &str,
-    ) -> Result<(), KgsStatus> {
-        let port = config::config::get_host().service_port;
-        let rpc_url = format!("http://{}:{}", pod_ip, port);
-
-        let mut client = FrontendNotifyServiceClient::connect(rpc_url)
-            .await
-            .map_err(|err| {
-                warn!("Failed to connect to notify server: {:?}", err);
-                KgsStatus::InternalServerError
-            })?;
-
-        let notify = frontend_notify::Notify {
-            notify_id,
-            notify_level: notify_level.to_id(),
-            title: title.to_string(),
-            content: content.to_string(),
-            create_at: chrono::Utc::now().timestamp_millis(),
-            notify_status: NotifyStatus::Unread as i32,
-        };
-
-        let request = tonic::Request::new(ForwardNotifyRequest {
-            client_id,
-            user_id,
-            notify: Some(notify),
-        });
-
-        client.forward_notify(request).await.map_err(|err| {
-            warn!("Failed to send notify: {:?}", err);
-            KgsStatus::InternalServerError
-        })?;
-
-        Ok(())
-    }
-
     /// 處理收到的轉發通知
     #[tracing::instrument]
     pub async fn handle_forward_notify(
@@ -462,4 +972,86 @@ impl FrontendNotifyServer {
 
         Ok(frontend_notify::Empty {})
     }
+
+    /// 通知狀態變更後 推送最新的未讀數量與變更的通知到用戶所有pod上的連線
+    /// 推送失敗不影響狀態的更新 用戶仍可透過get_unread_notify_count取得
+    #[tracing::instrument(skip(notify_ids))]
+    async fn publish_records_changed(
+        client_id: i64,
+        user_id: i64,
+        notify_ids: Vec<i64>,
+        notify_status: enums::NotifyStatus,
+    ) {
+        // define internal function
+        async fn publish(
+            client_id: i64,
+            user_id: i64,
+            notify_ids: Vec<i64>,
+            notify_status: enums::NotifyStatus,
+        ) -> Result<(), KgsStatus> {
+            // get the latest unread count
+            let db = database_manager::sea_orm::get_db();
+            let unread_count = repository::notify_record::get_all_unread_app_records_count(
+                &*db, client_id, user_id,
+            )
+            .await?;
+
+            FRONTEND_NOTIFY_SERVER
+                .dispatch_to_user_pods(
+                    client_id,
+                    user_id,
+                    PodEvent::RecordsChanged(ForwardRecordsChangedRequest {
+                        client_id,
+                        user_id,
+                        notify_ids,
+                        notify_status: notify_status.to_id(),
+                        unread_count,
+                    }),
+                )
+                .await?;
+
+            Ok(())
+        }
+
+        if let Err(err) = publish(client_id, user_id, notify_ids, notify_status).await {
+            warn!("publish records changed failed: {}", err);
+        }
+    }
+
+    /// 推送通知狀態變更與未讀數量到用戶在此pod上的所有連線
+    #[tracing::instrument(skip(notify_ids))]
+    pub async fn handle_records_changed(
+        &self,
+        client_id: i64,
+        user_id: i64,
+        notify_ids: &[i64],
+        notify_status: enums::NotifyStatus,
+        unread_count: u64,
+    ) -> Result<(), KgsStatus> {
+        let frontend_user = model::User { client_id, user_id };
+
+        // create the messages
+        let mut messages = vec![];
+        if !notify_ids.is_empty() {
+            messages.push(frontend_notify::Receiver {
+                message: Some(frontend_notify::receiver::Message::RecordsChanged(
+                    frontend_notify::RecordsChanged {
+                        notify_ids: notify_ids.to_vec(),
+                        notify_status: notify_status.to_id(),
+                    },
+                )),
+            });
+        }
+        messages.push(frontend_notify::Receiver {
+            message: Some(frontend_notify::receiver::Message::UnreadCountChanged(
+                frontend_notify::UnreadCountChanged {
+                    total_rows: unread_count,
+                },
+            )),
+        });
+
+        self.send_to_sessions(&frontend_user, &messages)?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,364 @@
+use super::frontend_forward::PodEvent;
+use crate::notify_server::application;
+use crate::notify_server::controller::frontend_notify::{FrontendNotifyServer, PendingAck};
+use crate::notify_server::model;
+use crate::{entity, enums, helper, repository};
+use frontend_notify_service_client::FrontendNotifyServiceClient;
+use kgs_err::models::status::Status as KgsStatus;
+use kgs_tracing::{tracing, warn};
+use protos::frontend_notify::{self, *};
+use sea_orm::ActiveValue::NotSet;
+use sea_orm::Set;
+use std::collections::HashMap;
+use tokio::sync::mpsc::Sender;
+
+const MAX_TOPIC_LENGTH: usize = 128; // 主題名稱的最大長度
+const MAX_TOPICS_PER_REQUEST: usize = 50; // 單次最多訂閱的主題數量
+
+/// for前台用戶的主題訂閱 主題發送時廣播給所有pod 由各pod發送給自己的訂閱者
+impl FrontendNotifyServer {
+    /// 檢查主題名稱 例如 match:123 或 promo:spring
+    pub fn check_topics(topics: &[String]) -> Result<(), KgsStatus> {
+        if topics.len() > MAX_TOPICS_PER_REQUEST {
+            return Err(KgsStatus::InvalidArgument);
+        }
+
+        for topic in topics {
+            if topic.is_empty() || topic.len() > MAX_TOPIC_LENGTH {
+                return Err(KgsStatus::InvalidArgument);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 將用戶加入主題索引
+    pub fn index_topics<'a>(
+        &self,
+        frontend_user: &model::User,
+        topics: impl Iterator<Item = &'a String>,
+    ) {
+        for topic in topics {
+            self.topic_index
+                .entry((frontend_user.client_id, topic.clone()))
+                .or_default()
+                .insert(frontend_user.clone());
+        }
+    }
+
+    /// 用戶是否有任一連線訂閱此主題
+    fn is_subscribed(&self, frontend_user: &model::User, topic: &str) -> bool {
+        self.connections
+            .get(frontend_user)
+            .map(|sessions| {
+                sessions
+                    .values()
+                    .any(|session| session.topics.contains(topic))
+            })
+            .unwrap_or(false)
+    }
+
+    /// 訂閱或取消訂閱主題 用戶的連線可能在其他pod上 需轉發到用戶所在的pod
+    #[tracing::instrument]
+    pub async fn update_topic_subscription(
+        &self,
+        request: TopicSubscriptionRequest,
+        is_subscribe: bool,
+    ) -> Result<Empty, KgsStatus> {
+        Self::check_topics(&request.topics)?;
+
+        let pod_count = self
+            .dispatch_to_user_pods(
+                request.client_id,
+                request.user_id,
+                PodEvent::TopicSubscription(ForwardTopicSubscriptionRequest {
+                    subscription: Some(request.clone()),
+                    is_subscribe,
+                }),
+            )
+            .await?;
+        if pod_count == 0 {
+            return Err(KgsStatus::UserConnectionNotFound);
+        }
+
+        Ok(Empty {})
+    }
+
+    /// 更新此pod上用戶連線的主題訂閱 沒有帶session_id時更新該用戶所有連線
+    #[tracing::instrument]
+    pub fn handle_topic_subscription(
+        &self,
+        request: &TopicSubscriptionRequest,
+        is_subscribe: bool,
+    ) {
+        let frontend_user = model::User {
+            client_id: request.client_id,
+            user_id: request.user_id,
+        };
+
+        match self.connections.get_mut(&frontend_user) {
+            Some(mut sessions) => {
+                let sessions = sessions.values_mut().filter(|session| {
+                    request
+                        .session_id
+                        .as_ref()
+                        .map_or(true, |session_id| &session.session_id == session_id)
+                });
+                for session in sessions {
+                    if is_subscribe {
+                        session.topics.extend(request.topics.iter().cloned());
+                    } else {
+                        for topic in request.topics.iter() {
+                            session.topics.remove(topic);
+                        }
+                    }
+                }
+            }
+            None => return,
+        }
+
+        // 取消訂閱時不需更新索引 發送時會移除已不再訂閱的用戶
+        if is_subscribe {
+            self.index_topics(&frontend_user, request.topics.iter());
+        }
+    }
+
+    /// 發送給訂閱主題的所有用戶 persist為false時不新增通知紀錄 用於即時動態
+    #[tracing::instrument]
+    pub async fn publish_to_topic(
+        &self,
+        request: PublishToTopicRequest,
+    ) -> Result<Empty, KgsStatus> {
+        Self::check_topics(std::slice::from_ref(&request.topic))?;
+        enums::NotifyLevel::try_from(request.notify_level)?;
+
+        // 此pod上的訂閱者發送失敗不影響其他pod
+        if let Err(err) = self.handle_topic_publish(&request).await {
+            warn!("publish to topic {} failed: {}", request.topic, err);
+        }
+
+        // forward to other pods
+        Self::forward_topic_publish(request).await?;
+
+        Ok(Empty {})
+    }
+
+    // 轉發主題發送到其他pod
+    async fn forward_topic_publish(request: PublishToTopicRequest) -> Result<(), KgsStatus> {
+        // define internal function
+        async fn send(ip: String, request: PublishToTopicRequest) -> Result<(), KgsStatus> {
+            // get client
+            let mut client =
+                FrontendNotifyServiceClient::new(application::pod_channel::get_channel(&ip)?);
+
+            // forward topic publish
+            let _res = client.forward_topic_publish(request).await.map_err(|e| {
+                warn!("forward topic publish failed: {:?}", e);
+                application::pod_channel::evict_if_unavailable(&ip, &e);
+                kgs_err::models::status::tonic_to_kgs(e)
+            })?;
+
+            Ok(())
+        }
+
+        // 透過redis pub/sub廣播給其他pod
+        if application::notify_bus::is_enabled() {
+            return application::notify_bus::broadcast(
+                application::notify_bus::BusEvent::TopicPublish {
+                    client_id: request.client_id,
+                    topic: request.topic,
+                    notify_level: request.notify_level,
+                    title: request.title,
+                    content: request.content,
+                    persist: request.persist,
+                    client_event_id: request.client_event_id,
+                },
+            );
+        }
+
+        // get other pod ips
+        let ip_list = application::peer_discovery::get_other_pod_ips().await;
+
+        let mut futures = vec![];
+        for ip in ip_list {
+            futures.push(send(ip, request.clone()));
+        }
+
+        // wait all futures
+        futures::future::try_join_all(futures).await?;
+
+        Ok(())
+    }
+
+    /// 發送給此pod上訂閱主題的用戶
+    #[tracing::instrument]
+    pub async fn handle_topic_publish(
+        &self,
+        request: &PublishToTopicRequest,
+    ) -> Result<(), KgsStatus> {
+        let notify_level = enums::NotifyLevel::try_from(request.notify_level)?;
+        let key = (request.client_id, request.topic.clone());
+
+        // 複製訂閱者 避免發送時持有鎖
+        let users: Vec<model::User> = match self.topic_index.get(&key) {
+            Some(users) => users.iter().cloned().collect(),
+            None => return Ok(()),
+        };
+
+        // collect the subscribed sessions of every user
+        let mut receivers = vec![];
+        let mut unsubscribed_users = vec![];
+        for user in users {
+            let sessions: Vec<(
+                String,
+                Sender<Result<frontend_notify::Receiver, tonic::Status>>,
+            )> = match self.connections.get(&user) {
+                Some(sessions) => sessions
+                    .values()
+                    .filter(|session| session.topics.contains(&request.topic))
+                    .map(|session| (session.session_id.clone(), session.tx.clone()))
+                    .collect(),
+                None => vec![],
+            };
+
+            if sessions.is_empty() {
+                unsubscribed_users.push(user);
+            } else {
+                receivers.push((user, sessions));
+            }
+        }
+
+        // remove the unsubscribed users from the index, 移除前再次確認 避免移除剛訂閱的用戶
+        if !unsubscribed_users.is_empty() {
+            if let Some(mut users) = self.topic_index.get_mut(&key) {
+                for user in unsubscribed_users {
+                    if !self.is_subscribed(&user, &request.topic) {
+                        users.remove(&user);
+                    }
+                }
+            }
+            self.topic_index
+                .remove_if(&key, |_, users| users.is_empty());
+        }
+
+        if receivers.is_empty() {
+            return Ok(());
+        }
+
+        // create notify_ids
+        let mut notify_ids = vec![];
+        for _ in receivers.iter() {
+            notify_ids.push(helper::generate_snowflake_id().await);
+        }
+
+        // 需保存時先新增通知紀錄 用戶已讀與確認收到時才能找到該通知
+        if request.persist {
+            let user_accounts: HashMap<i64, String> =
+                application::user_rpc::get_accounts_by_user_ids(
+                    request.client_id,
+                    receivers.iter().map(|(user, _)| user.user_id).collect(),
+                )
+                .await?
+                .user_accounts
+                .into_iter()
+                .map(|account| (account.user_id, account.account))
+                .collect();
+
+            let active_models = receivers
+                .iter()
+                .zip(notify_ids.iter())
+                .map(
+                    |((user, _), notify_id)| entity::notify_record::ActiveModel {
+                        id: Set(*notify_id),
+                        client_id: Set(user.client_id),
+                        user_id: Set(user.user_id),
+                        user_account: Set(user_accounts
+                            .get(&user.user_id)
+                            .cloned()
+                            .unwrap_or_default()),
+                        client_notify_event_id: Set(request.client_event_id.unwrap_or_default()),
+                        sender_id: Set(0),
+                        sender_account: Set("System".to_string()),
+                        sender_ip: Set(None),
+                        notify_type: Set(enums::NotifyType::InApp),
+                        notify_level: Set(notify_level.clone()),
+                        notify_status: Set(enums::NotifyStatus::Unread),
+                        title: Set(request.title.clone()),
+                        content: Set(request.content.clone()),
+                        expires_at: Set(None),
+                        create_at: NotSet,
+                        update_at: NotSet,
+                    },
+                )
+                .collect();
+
+            let db = database_manager::sea_orm::get_db();
+            repository::notify_record::insert_many(&*db, active_models).await?;
+        }
+
+        // send to every subscribed session without waiting
+        let create_at = chrono::Utc::now().timestamp_millis();
+        for ((user, sessions), notify_id) in receivers.into_iter().zip(notify_ids) {
+            let response = frontend_notify::Receiver {
+                message: Some(frontend_notify::receiver::Message::TopicMessage(
+                    frontend_notify::TopicMessage {
+                        topic: request.topic.clone(),
+                        notify: Some(frontend_notify::Notify {
+                            notify_id,
+                            notify_level: notify_level.to_id(),
+                            title: request.title.clone(),
+                            content: request.content.clone(),
+                            create_at,
+                            notify_status: enums::NotifyStatus::Unread.to_id(),
+                        }),
+                    },
+                )),
+            };
+
+            let mut closed_session_ids = vec![];
+            for (session_id, tx) in sessions.iter() {
+                let result = application::stream_sender::try_send(
+                    &enums::Platform::Frontend,
+                    tx,
+                    response.clone(),
+                );
+                if result.should_remove() {
+                    warn!(
+                        "send topic message error session_id: {} result: {:?}",
+                        session_id, result
+                    );
+                    closed_session_ids.push(session_id.clone());
+                }
+            }
+
+            // 已保存的通知 等待裝置確認收到
+            if request.persist && sessions.len() > closed_session_ids.len() {
+                self.pending_acks.insert(
+                    notify_id,
+                    PendingAck {
+                        client_id: user.client_id,
+                        user_id: user.user_id,
+                        sent_at: create_at,
+                        redeliver_count: 0,
+                    },
+                );
+            }
+
+            // remove the closed sessions
+            if !closed_session_ids.is_empty()
+                && !self.remove_sessions(&user, Some(&closed_session_ids))
+            {
+                if let Err(err) =
+                    application::notify_handler::remove_user_located_server_from_redis(&user)
+                {
+                    warn!(
+                        "remove user {} located server failed: {}",
+                        user.user_id, err
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,2 +1,4 @@
 pub mod backstage_notify;
+mod frontend_forward;
 pub mod frontend_notify;
+pub mod frontend_topic;
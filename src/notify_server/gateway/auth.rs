@@ -0,0 +1,137 @@
+use super::ConnectQuery;
+use crate::config::config::{self, GatewayAuthMode};
+use crate::enums;
+use crate::notify_server::application::auth::{self, IdentityKind};
+use axum::http::HeaderMap;
+use kgs_tracing::{info, warn};
+use std::fmt::Debug;
+use tonic::async_trait;
+
+const CLIENT_ID_HEADER: &str = "x-client-id";
+const USER_ID_HEADER: &str = "x-user-id";
+
+/// 通過驗證的前台用戶
+#[derive(Debug, Clone)]
+pub struct GatewayUser {
+    pub client_id: i64,
+    pub user_id: i64,
+}
+
+/// 驗證http連線的用戶 可依部署環境替換
+#[async_trait]
+pub trait GatewayAuthenticator: Send + Sync + Debug {
+    async fn authenticate(
+        &self,
+        headers: &HeaderMap,
+        query: &ConnectQuery,
+    ) -> Result<GatewayUser, tonic::Status>;
+}
+
+/// 由前方的api gateway驗證後 以header帶入用戶
+#[derive(Debug)]
+pub struct TrustedHeaderAuthenticator;
+
+#[async_trait]
+impl GatewayAuthenticator for TrustedHeaderAuthenticator {
+    async fn authenticate(
+        &self,
+        headers: &HeaderMap,
+        _query: &ConnectQuery,
+    ) -> Result<GatewayUser, tonic::Status> {
+        let get_header = |name: &str| -> Result<i64, tonic::Status> {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| tonic::Status::unauthenticated(format!("missing header {}", name)))
+        };
+
+        Ok(GatewayUser {
+            client_id: get_header(CLIENT_ID_HEADER)?,
+            user_id: get_header(USER_ID_HEADER)?,
+        })
+    }
+}
+
+/// 直接使用query string中的用戶 不做任何驗證 僅用於開發與測試
+#[derive(Debug)]
+pub struct QueryAuthenticator;
+
+#[async_trait]
+impl GatewayAuthenticator for QueryAuthenticator {
+    async fn authenticate(
+        &self,
+        _headers: &HeaderMap,
+        query: &ConnectQuery,
+    ) -> Result<GatewayUser, tonic::Status> {
+        match (query.client_id, query.user_id) {
+            (Some(client_id), Some(user_id)) => Ok(GatewayUser { client_id, user_id }),
+            _ => Err(tonic::Status::unauthenticated(
+                "missing client_id or user_id",
+            )),
+        }
+    }
+}
+
+/// 與grpc相同以bearer token驗證 websocket與EventSource無法帶header時使用access_token query
+#[derive(Debug)]
+pub struct BearerAuthenticator;
+
+#[async_trait]
+impl GatewayAuthenticator for BearerAuthenticator {
+    async fn authenticate(
+        &self,
+        headers: &HeaderMap,
+        query: &ConnectQuery,
+    ) -> Result<GatewayUser, tonic::Status> {
+        let identity = match &query.access_token {
+            Some(access_token) => auth::verify_token(access_token).await?,
+            None => {
+                let authorization = headers
+                    .get(axum::http::header::AUTHORIZATION)
+                    .and_then(|value| value.to_str().ok());
+                auth::verify_bearer(authorization).await?
+            }
+        };
+
+        if identity.kind != IdentityKind::User(enums::Platform::Frontend) {
+            return Err(tonic::Status::permission_denied("platform not allowed"));
+        }
+
+        Ok(GatewayUser {
+            client_id: identity.client_id,
+            user_id: identity.user_id,
+        })
+    }
+}
+
+/// 檢查驗證方式 trusted_header與query模式信任請求中的用戶 未明確允許時不啟動gateway
+pub fn check_auth_mode() -> Result<(), String> {
+    let gateway_config = config::get_gateway();
+    match gateway_config.gateway_auth_mode {
+        GatewayAuthMode::Bearer => Ok(()),
+        auth_mode if gateway_config.gateway_allow_insecure_auth => {
+            warn!(
+                "gateway auth mode {:?} does not verify the user token",
+                auth_mode
+            );
+            Ok(())
+        }
+        auth_mode => Err(format!(
+            "gateway auth mode {:?} requires gateway_allow_insecure_auth",
+            auth_mode
+        )),
+    }
+}
+
+/// 依設定建立驗證方式
+pub fn new_authenticator() -> Box<dyn GatewayAuthenticator> {
+    let gateway_config = config::get_gateway();
+    info!("gateway auth mode: {:?}", gateway_config.gateway_auth_mode);
+
+    match gateway_config.gateway_auth_mode {
+        GatewayAuthMode::TrustedHeader => Box::new(TrustedHeaderAuthenticator),
+        GatewayAuthMode::Query => Box::new(QueryAuthenticator),
+        GatewayAuthMode::Bearer => Box::new(BearerAuthenticator),
+    }
+}
@@ -0,0 +1,84 @@
+use protos::frontend_notify::{self, receiver::Message};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// 依proto3的json格式轉換 欄位名稱為lowerCamelCase int64轉為字串 避免瀏覽器的精度問題
+pub fn to_json(receiver: &frontend_notify::Receiver) -> Value {
+    match &receiver.message {
+        Some(Message::Notify(notify)) => json!({ "notify": notify_to_json(notify) }),
+        Some(Message::SessionCreated(session_created)) => json!({
+            "sessionCreated": {
+                "sessionId": session_created.session_id,
+            }
+        }),
+        Some(Message::CaughtUp(caught_up)) => json!({
+            "caughtUp": {
+                "lastNotifyId": caught_up.last_notify_id.map(|id| id.to_string()),
+            }
+        }),
+        Some(Message::SessionRevoked(session_revoked)) => json!({
+            "sessionRevoked": {
+                "reason": session_revoked.reason,
+            }
+        }),
+        Some(Message::RecordsChanged(records_changed)) => json!({
+            "recordsChanged": {
+                "notifyIds": records_changed
+                    .notify_ids
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<String>>(),
+                "notifyStatus": records_changed.notify_status,
+            }
+        }),
+        Some(Message::UnreadCountChanged(unread_count_changed)) => json!({
+            "unreadCountChanged": {
+                "totalRows": unread_count_changed.total_rows.to_string(),
+            }
+        }),
+        Some(Message::TopicMessage(topic_message)) => json!({
+            "topicMessage": {
+                "topic": topic_message.topic,
+                "notify": topic_message.notify.as_ref().map(notify_to_json),
+            }
+        }),
+        None => json!({}),
+    }
+}
+
+fn notify_to_json(notify: &frontend_notify::Notify) -> Value {
+    json!({
+        "notifyId": notify.notify_id.to_string(),
+        "notifyLevel": notify.notify_level,
+        "title": notify.title,
+        "content": notify.content,
+        "createAt": notify.create_at.to_string(),
+        "notifyStatus": notify.notify_status,
+    })
+}
+
+/// 串流中斷時的錯誤
+pub fn status_to_json(status: &tonic::Status) -> Value {
+    json!({
+        "error": {
+            "code": status.code() as i32,
+            "message": status.message(),
+        }
+    })
+}
+
+/// sse用戶端確認收到通知的內容
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AckRequest {
+    pub notify_ids: Vec<String>,
+}
+
+/// websocket用戶端送出的訊息
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ClientMessage {
+    // 確認收到通知 與AckNotify相同
+    #[serde(rename_all = "camelCase")]
+    Ack { notify_ids: Vec<String> },
+}
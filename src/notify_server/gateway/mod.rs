@@ -0,0 +1,158 @@
+mod auth;
+mod message;
+mod sse;
+mod websocket;
+
+use crate::config::config;
+use crate::notify_server::FRONTEND_NOTIFY_SERVER;
+use auth::GatewayAuthenticator;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use kgs_tracing::{info, warn};
+use once_cell::sync::Lazy;
+use protos::frontend_notify;
+use serde::Deserialize;
+use tokio::sync::mpsc::Receiver;
+
+pub use auth::check_auth_mode;
+
+static AUTHENTICATOR: Lazy<Box<dyn GatewayAuthenticator>> = Lazy::new(auth::new_authenticator);
+
+/// 建立連線的query string 與ConnectionRequest相同
+#[derive(Debug, Deserialize)]
+pub struct ConnectQuery {
+    pub client_id: Option<i64>, // 僅query驗證模式使用
+    pub user_id: Option<i64>,   // 僅query驗證模式使用
+    pub session_id: Option<String>,
+    pub device_info: Option<String>,
+    pub last_notify_id: Option<i64>,
+    pub last_seen_at: Option<i64>,
+    pub topics: Option<String>,       // 以逗號分隔
+    pub access_token: Option<String>, // 僅bearer驗證模式使用
+}
+
+/// http的錯誤回應
+#[derive(Debug)]
+pub struct GatewayError(tonic::Status);
+
+impl From<tonic::Status> for GatewayError {
+    fn from(status: tonic::Status) -> Self {
+        GatewayError(status)
+    }
+}
+
+impl IntoResponse for GatewayError {
+    fn into_response(self) -> Response {
+        let status_code = match self.0.code() {
+            tonic::Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+            tonic::Code::PermissionDenied => StatusCode::FORBIDDEN,
+            tonic::Code::InvalidArgument => StatusCode::BAD_REQUEST,
+            tonic::Code::NotFound => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        (status_code, axum::Json(message::status_to_json(&self.0))).into_response()
+    }
+}
+
+/// 驗證用戶後建立連線 與grpc的create_connection使用相同的連線表
+async fn create_connection(
+    headers: &HeaderMap,
+    query: ConnectQuery,
+) -> Result<
+    (
+        frontend_notify::ConnectionRequest,
+        Receiver<Result<frontend_notify::Receiver, tonic::Status>>,
+    ),
+    GatewayError,
+> {
+    let user = AUTHENTICATOR.authenticate(headers, &query).await?;
+
+    // 沒有帶session_id時由gateway產生 關閉連線時才能只移除這個session
+    let session_id = match query.session_id {
+        Some(session_id) => session_id,
+        None => crate::helper::generate_snowflake_id().await.to_string(),
+    };
+    let request = frontend_notify::ConnectionRequest {
+        client_id: user.client_id,
+        user_id: user.user_id,
+        session_id: Some(session_id),
+        device_info: query.device_info.or_else(|| {
+            headers
+                .get(axum::http::header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string())
+        }),
+        last_notify_id: query.last_notify_id,
+        last_seen_at: query.last_seen_at,
+        topics: query
+            .topics
+            .map(|topics| {
+                topics
+                    .split(',')
+                    .map(|topic| topic.trim().to_string())
+                    .filter(|topic| !topic.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default(),
+    };
+
+    let rx = FRONTEND_NOTIFY_SERVER
+        .create_connection(request.clone())
+        .await
+        .map_err(|err| err.to_tonic_status())?;
+
+    Ok((request, rx))
+}
+
+/// 確認收到通知 notify_id以字串傳入 與json中的格式相同
+async fn ack_notify(
+    client_id: i64,
+    user_id: i64,
+    notify_ids: &[String],
+) -> Result<(), tonic::Status> {
+    let request = frontend_notify::AckNotifyRequest {
+        client_id,
+        user_id,
+        notify_ids: notify_ids
+            .iter()
+            .filter_map(|notify_id| notify_id.parse().ok())
+            .collect(),
+    };
+    FRONTEND_NOTIFY_SERVER
+        .ack_notify(request)
+        .await
+        .map(|_| ())
+        .map_err(|err| err.to_tonic_status())
+}
+
+/// 啟動websocket與sse的http服務
+pub fn start() -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let gateway_config = config::get_gateway();
+        let addr = format!(
+            "{}:{}",
+            gateway_config.gateway_host, gateway_config.gateway_port
+        );
+
+        let router = Router::new()
+            .route("/notify/ws", get(websocket::connect))
+            .route("/notify/sse", get(sse::connect))
+            .route("/notify/ack", post(sse::ack));
+
+        let listener = match tokio::net::TcpListener::bind(&addr).await {
+            Ok(listener) => listener,
+            Err(err) => {
+                warn!("bind notify gateway on {} failed: {}", addr, err);
+                return;
+            }
+        };
+        info!("start notify gateway on {}", addr);
+
+        if let Err(err) = axum::serve(listener, router).await {
+            warn!("notify gateway stopped: {}", err);
+        }
+    })
+}
@@ -0,0 +1,49 @@
+use super::{message, ConnectQuery, GatewayError};
+use crate::config::config;
+use axum::extract::Query;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::Json;
+use futures::{Stream, StreamExt};
+use kgs_tracing::tracing;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio_stream::wrappers::ReceiverStream;
+
+/// 以server-sent events推送通知 用戶端斷線後由心跳排程清除連線
+#[tracing::instrument(skip(headers, query))]
+pub async fn connect(
+    headers: HeaderMap,
+    Query(query): Query<ConnectQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, GatewayError> {
+    let (_, rx) = super::create_connection(&headers, query).await?;
+
+    let stream = ReceiverStream::new(rx).map(|message| {
+        let event = match message {
+            Ok(receiver) => Event::default().data(message::to_json(&receiver).to_string()),
+            Err(status) => Event::default()
+                .event("error")
+                .data(message::status_to_json(&status).to_string()),
+        };
+        Ok(event)
+    });
+
+    Ok(
+        Sse::new(stream).keep_alive(KeepAlive::new().interval(Duration::from_secs(
+            config::get_gateway().gateway_keep_alive_secs,
+        ))),
+    )
+}
+
+/// sse為單向推送 用戶端以此確認收到通知 未確認的通知會被重新推送
+#[tracing::instrument(skip(headers, query, body))]
+pub async fn ack(
+    headers: HeaderMap,
+    Query(query): Query<ConnectQuery>,
+    Json(body): Json<message::AckRequest>,
+) -> Result<StatusCode, GatewayError> {
+    let user = super::AUTHENTICATOR.authenticate(&headers, &query).await?;
+    super::ack_notify(user.client_id, user.user_id, &body.notify_ids).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
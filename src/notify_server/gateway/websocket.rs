@@ -0,0 +1,89 @@
+use super::{message, ConnectQuery, GatewayError};
+use crate::config::config;
+use crate::notify_server::FRONTEND_NOTIFY_SERVER;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::Query;
+use axum::http::HeaderMap;
+use axum::response::Response;
+use kgs_tracing::{tracing, warn};
+use protos::frontend_notify;
+use std::time::Duration;
+use tokio::sync::mpsc::Receiver;
+
+/// 以websocket推送通知 用戶端可透過同一連線確認收到通知
+#[tracing::instrument(skip(headers, ws, query))]
+pub async fn connect(
+    ws: WebSocketUpgrade,
+    headers: HeaderMap,
+    Query(query): Query<ConnectQuery>,
+) -> Result<Response, GatewayError> {
+    let (request, rx) = super::create_connection(&headers, query).await?;
+
+    Ok(ws.on_upgrade(move |socket| handle_socket(socket, request, rx)))
+}
+
+async fn handle_socket(
+    mut socket: WebSocket,
+    request: frontend_notify::ConnectionRequest,
+    mut rx: Receiver<Result<frontend_notify::Receiver, tonic::Status>>,
+) {
+    let mut keep_alive = tokio::time::interval(Duration::from_secs(
+        config::get_gateway().gateway_keep_alive_secs,
+    ));
+
+    loop {
+        tokio::select! {
+            message = rx.recv() => {
+                // 連線已被server關閉 例如強制下線
+                let Some(message) = message else {
+                    let _ = socket.send(Message::Close(None)).await;
+                    break;
+                };
+                let json = match message {
+                    Ok(receiver) => message::to_json(&receiver),
+                    Err(status) => message::status_to_json(&status),
+                };
+                if socket.send(Message::Text(json.to_string())).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => {
+                match message {
+                    Some(Ok(Message::Text(text))) => handle_client_message(&request, &text).await,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                }
+            }
+            _ = keep_alive.tick() => {
+                if socket.send(Message::Ping(vec![])).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    // 用戶端斷線時立即移除連線 不需等待心跳排程
+    if let Err(err) = FRONTEND_NOTIFY_SERVER.close_connection(request).await {
+        warn!("close websocket connection failed: {}", err);
+    }
+}
+
+async fn handle_client_message(request: &frontend_notify::ConnectionRequest, text: &str) {
+    let client_message: message::ClientMessage = match serde_json::from_str(text) {
+        Ok(client_message) => client_message,
+        Err(err) => {
+            warn!("parse websocket message failed: {} text: {}", err, text);
+            return;
+        }
+    };
+
+    match client_message {
+        message::ClientMessage::Ack { notify_ids } => {
+            if let Err(err) =
+                super::ack_notify(request.client_id, request.user_id, &notify_ids).await
+            {
+                warn!("ack notify from websocket failed: {}", err);
+            }
+        }
+    }
+}
@@ -1,6 +1,7 @@
 pub mod application;
 pub mod controller;
 pub mod facade;
+pub mod gateway;
 pub mod model;
 
 pub use controller::backstage_notify::BACKSTAGE_NOTIFY_SERVER;
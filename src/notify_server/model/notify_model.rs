@@ -1,5 +1,5 @@
 /// 用戶
-#[derive(Eq, Hash, PartialEq, Debug)]
+#[derive(Eq, Hash, PartialEq, Debug, Clone)]
 pub struct User {
     pub client_id: i64,
     pub user_id: i64,
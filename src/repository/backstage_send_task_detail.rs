@@ -1,9 +1,10 @@
-use crate::entity::backstage_send_task_detail;
+use crate::entity::{backstage_send_task, backstage_send_task_detail};
 use crate::enums;
 use crate::helper;
 use kgs_err::models::status::Status as KgsStatus;
 use kgs_tracing::warn;
 use sea_orm::*;
+use sea_query::Query;
 
 pub async fn insert<C>(
     txn: &C,
@@ -34,15 +35,26 @@ where
     Ok(entity)
 }
 
+/// 只回傳屬於該client的任務詳情
 pub async fn find_list_by_task_id<C>(
     db: &C,
+    client_id: i64,
     task_id: i64,
 ) -> Result<Vec<backstage_send_task_detail::Model>, KgsStatus>
 where
     C: ConnectionTrait,
 {
     let result = backstage_send_task_detail::Entity::find()
-        .filter(backstage_send_task_detail::Column::BackstageSendTaskId.eq(task_id))
+        .filter(
+            backstage_send_task_detail::Column::BackstageSendTaskId.in_subquery(
+                Query::select()
+                    .column(backstage_send_task::Column::Id)
+                    .from(backstage_send_task::Entity)
+                    .and_where(backstage_send_task::Column::Id.eq(task_id))
+                    .and_where(backstage_send_task::Column::ClientId.eq(client_id))
+                    .to_owned(),
+            ),
+        )
         .all(db)
         .await
         .map_err(|_| KgsStatus::DataNotFound)?;
@@ -0,0 +1,131 @@
+use crate::entity::delay_notify;
+use crate::enums;
+use crate::helper;
+use chrono::NaiveDateTime;
+use kgs_err::models::status::Status as KgsStatus;
+use kgs_tracing::warn;
+use sea_orm::*;
+use sea_query::Expr;
+use std::collections::HashMap;
+
+pub async fn insert<C>(
+    db: &C,
+    client_id: i64,
+    user_id: i64,
+    notify_event: enums::NotifyEvent,
+    delay_key: Option<String>,
+    key_map: &HashMap<String, String>,
+    send_at: NaiveDateTime,
+    expires_at: Option<NaiveDateTime>,
+) -> Result<delay_notify::Model, KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    let key_map = serde_json::to_value(key_map).map_err(|e| {
+        warn!("serialize key_map failed: {:?}", e);
+        KgsStatus::InvalidArgument
+    })?;
+
+    let id = helper::generate_snowflake_id().await;
+    delay_notify::ActiveModel {
+        id: Set(id),
+        client_id: Set(client_id),
+        user_id: Set(user_id),
+        notify_event: Set(notify_event),
+        delay_key: Set(delay_key),
+        key_map: Set(key_map),
+        send_at: Set(send_at),
+        delay_status: Set(enums::DelayNotifyStatus::Pending),
+        error_message: Set(None),
+        expires_at: Set(expires_at),
+        create_at: NotSet,
+        update_at: NotSet,
+    }
+    .insert(db)
+    .await
+    .map_err(|e| {
+        warn!("insert delay_notify failed: {:?}", e);
+        KgsStatus::InternalServerError
+    })
+}
+
+/// 取消該用戶所有使用此key且尚未發送的通知 回傳取消筆數
+pub async fn cancel_pending_by_key<C>(
+    db: &C,
+    client_id: i64,
+    user_id: i64,
+    delay_key: &str,
+) -> Result<u64, KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    let result = delay_notify::Entity::update_many()
+        .col_expr(
+            delay_notify::Column::DelayStatus,
+            Expr::value(enums::DelayNotifyStatus::Cancel),
+        )
+        .filter(delay_notify::Column::ClientId.eq(client_id))
+        .filter(delay_notify::Column::UserId.eq(user_id))
+        .filter(delay_notify::Column::DelayKey.eq(delay_key))
+        .filter(delay_notify::Column::DelayStatus.eq(enums::DelayNotifyStatus::Pending))
+        .exec(db)
+        .await
+        .map_err(|e| {
+            warn!("cancel delay_notify failed: {:?}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+    Ok(result.rows_affected)
+}
+
+/// 獲取已到發送時間的待發送通知
+pub async fn find_due_list<C>(
+    db: &C,
+    now: NaiveDateTime,
+    limit: u64,
+) -> Result<Vec<delay_notify::Model>, KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    delay_notify::Entity::find()
+        .filter(delay_notify::Column::DelayStatus.eq(enums::DelayNotifyStatus::Pending))
+        .filter(delay_notify::Column::SendAt.lte(now))
+        .order_by_asc(delay_notify::Column::SendAt)
+        .limit(limit)
+        .all(db)
+        .await
+        .map_err(|e| {
+            warn!("find due delay_notify failed: {:?}", e);
+            KgsStatus::InternalServerError
+        })
+}
+
+/// 更新通知狀態 只有狀態仍為from_status時才會更新成功
+/// 多個pod同時掃描到同一筆通知時 只有一個pod會拿到這筆通知
+pub async fn update_status<C>(
+    db: &C,
+    id: i64,
+    from_status: enums::DelayNotifyStatus,
+    to_status: enums::DelayNotifyStatus,
+    error_message: Option<String>,
+) -> Result<bool, KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    let result = delay_notify::Entity::update_many()
+        .col_expr(delay_notify::Column::DelayStatus, Expr::value(to_status))
+        .col_expr(
+            delay_notify::Column::ErrorMessage,
+            Expr::value(error_message),
+        )
+        .filter(delay_notify::Column::Id.eq(id))
+        .filter(delay_notify::Column::DelayStatus.eq(from_status))
+        .exec(db)
+        .await
+        .map_err(|e| {
+            warn!("update delay_notify status failed: {:?}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+    Ok(result.rows_affected == 1)
+}
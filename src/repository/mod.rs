@@ -2,6 +2,10 @@ pub mod backstage_send_task;
 pub mod backstage_send_task_detail;
 pub mod client_notify_event;
 pub mod client_notify_template;
+pub mod delay_notify;
 pub mod mq_failed_record;
+pub mod mq_quarantine_record;
 pub mod mq_success_record;
 pub mod notify_record;
+pub mod recurring_campaign;
+pub mod recurring_campaign_detail;
@@ -11,6 +11,8 @@ pub async fn create(
     notify_type: Option<enums::NotifyType>,
     content: Option<String>,
     error_message: Option<String>,
+    error_class: Option<String>,
+    retry_count: i32,
 ) -> Result<mq_failed_record::Model, sea_orm::DbErr> {
     let active_model = mq_failed_record::ActiveModel {
         id: sea_orm::ActiveValue::NotSet,
@@ -23,6 +25,8 @@ pub async fn create(
         notify_type: sea_orm::Set(notify_type),
         create_at: sea_orm::ActiveValue::NotSet,
         error_message: sea_orm::Set(error_message),
+        error_class: sea_orm::Set(error_class),
+        retry_count: sea_orm::Set(retry_count),
     };
 
     active_model.insert(db).await
@@ -0,0 +1,127 @@
+use crate::entity::mq_quarantine_record;
+use crate::helper;
+use chrono::NaiveDateTime;
+use kgs_err::models::status::Status as KgsStatus;
+use kgs_tracing::warn;
+use protos::backstage_notify;
+use sea_orm::*;
+use sea_query::Expr;
+use std::collections::HashMap;
+
+pub async fn insert<C>(
+    db: &C,
+    queue_name: &str,
+    payload: Vec<u8>,
+    headers: &HashMap<String, String>,
+    error_message: String,
+) -> Result<mq_quarantine_record::Model, KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    let headers = serde_json::to_value(headers).map_err(|e| {
+        warn!("serialize headers failed: {:?}", e);
+        KgsStatus::InternalServerError
+    })?;
+
+    let id = helper::generate_snowflake_id().await;
+    mq_quarantine_record::ActiveModel {
+        id: Set(id),
+        queue_name: Set(queue_name.to_string()),
+        payload: Set(payload),
+        headers: Set(headers),
+        error_message: Set(error_message),
+        replay_count: Set(0),
+        replayed_at: Set(None),
+        create_at: NotSet,
+        update_at: NotSet,
+    }
+    .insert(db)
+    .await
+    .map_err(|e| {
+        warn!("insert mq_quarantine_record failed: {:?}", e);
+        KgsStatus::InternalServerError
+    })
+}
+
+pub async fn find_one_by_id<C>(db: &C, id: i64) -> Result<mq_quarantine_record::Model, KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    let result = mq_quarantine_record::Entity::find_by_id(id)
+        .one(db)
+        .await
+        .map_err(|_| KgsStatus::DataNotFound)?;
+
+    result.ok_or(KgsStatus::DataNotFound)
+}
+
+/// 記錄一次重新發送
+pub async fn update_replayed<C>(
+    db: &C,
+    id: i64,
+    replayed_at: NaiveDateTime,
+) -> Result<(), KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    mq_quarantine_record::Entity::update_many()
+        .col_expr(
+            mq_quarantine_record::Column::ReplayCount,
+            Expr::col(mq_quarantine_record::Column::ReplayCount).add(1),
+        )
+        .col_expr(
+            mq_quarantine_record::Column::ReplayedAt,
+            Expr::value(replayed_at),
+        )
+        .filter(mq_quarantine_record::Column::Id.eq(id))
+        .exec(db)
+        .await
+        .map_err(|e| {
+            warn!("update mq_quarantine_record failed: {:?}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+    Ok(())
+}
+
+pub async fn get_quarantine_record_list<C>(
+    db: &C,
+    page_size: u64,
+    now_page: u64,
+    request: backstage_notify::GetQuarantineRecordListRequest,
+) -> Result<(Vec<mq_quarantine_record::Model>, u64, u64), KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    let query = mq_quarantine_record::Entity::find()
+        .apply_if(request.queue_name, |query, v| {
+            query.filter(mq_quarantine_record::Column::QueueName.eq(v))
+        })
+        .apply_if(request.is_replayed, |query, v| {
+            if v {
+                query.filter(mq_quarantine_record::Column::ReplayCount.gt(0))
+            } else {
+                query.filter(mq_quarantine_record::Column::ReplayCount.eq(0))
+            }
+        })
+        .order_by_desc(mq_quarantine_record::Column::CreateAt);
+
+    // get total rows
+    let total_rows = query.clone().count(db).await.map_err(|err| {
+        warn!("get_quarantine_record_list count error: {}", err);
+        KgsStatus::DataNotFound
+    })?;
+
+    // 創建分頁器
+    let paginator = query.paginate(db, page_size);
+
+    let records = paginator.fetch_page(now_page - 1).await.map_err(|err| {
+        warn!("get_quarantine_record_list fetch_page error: {}", err);
+        KgsStatus::DataNotFound
+    })?;
+
+    // 總頁數
+    let total_pages = (total_rows as f64 / page_size as f64).ceil() as u64;
+
+    Ok((records, total_rows, total_pages))
+}
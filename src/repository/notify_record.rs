@@ -26,6 +26,20 @@ where
     result.ok_or(KgsStatus::DataNotFound)
 }
 
+/// 尚未過期的通知 沒有設定過期時間的通知永不過期
+fn not_expired_condition() -> Condition {
+    Condition::any()
+        .add(notify_record::Column::ExpiresAt.is_null())
+        .add(notify_record::Column::ExpiresAt.gt(chrono::Utc::now().naive_utc()))
+}
+
+/// 未讀的條件 已送達裝置但尚未讀取的通知也算未讀
+fn unread_condition() -> Condition {
+    Condition::any()
+        .add(notify_record::Column::NotifyStatus.eq(enums::NotifyStatus::Unread))
+        .add(notify_record::Column::NotifyStatus.eq(enums::NotifyStatus::Delivered))
+}
+
 /// 獲取所有 站內訊息
 pub async fn find_all_app_record_by_user_id_and_status<C>(
     db: &C,
@@ -44,11 +58,15 @@ where
         .filter(notify_record::Column::ClientId.eq(client_id))
         .filter(notify_record::Column::UserId.eq(user_id))
         .filter(notify_record::Column::NotifyType.eq(enums::NotifyType::InApp))
-        .filter(if let Some(notify_status) = notify_status {
-            notify_record::Column::NotifyStatus.eq(notify_status)
-        } else {
-            notify_record::Column::NotifyStatus.ne(enums::NotifyStatus::Delete)
+        .filter(match notify_status {
+            Some(enums::NotifyStatus::Unread) => unread_condition(),
+            Some(notify_status) => {
+                Condition::all().add(notify_record::Column::NotifyStatus.eq(notify_status))
+            }
+            None => Condition::all()
+                .add(notify_record::Column::NotifyStatus.ne(enums::NotifyStatus::Delete)),
         })
+        .filter(not_expired_condition())
         .apply_if(notify_level, |query, v| {
             query.filter(notify_record::Column::NotifyLevel.eq(v))
         })
@@ -112,7 +130,8 @@ where
     let query = notify_record::Entity::find()
         .filter(notify_record::Column::ClientId.eq(client_id))
         .filter(notify_record::Column::UserId.eq(user_id))
-        .filter(notify_record::Column::NotifyType.eq(enums::NotifyType::InApp));
+        .filter(notify_record::Column::NotifyType.eq(enums::NotifyType::InApp))
+        .filter(not_expired_condition());
     let query = if let Some(notify_level) = notify_level {
         query.filter(notify_record::Column::NotifyLevel.eq(notify_level))
     } else {
@@ -135,13 +154,125 @@ where
     notify_record::Entity::find()
         .filter(notify_record::Column::ClientId.eq(client_id))
         .filter(notify_record::Column::UserId.eq(user_id))
-        .filter(notify_record::Column::NotifyStatus.eq(enums::NotifyStatus::Unread))
+        .filter(unread_condition())
         .filter(notify_record::Column::NotifyType.eq(enums::NotifyType::InApp))
+        .filter(not_expired_condition())
         .count(db)
         .await
         .map_err(|_| KgsStatus::DataNotFound)
 }
 
+/// 獲取游標之後建立的站內未讀訊息 用於重新連線時補發
+/// 分頁以id為游標 排序也只依id 避免create_at與id順序不一致時遺漏
+pub async fn find_unread_app_records_after<C>(
+    db: &C,
+    client_id: i64,
+    user_id: i64,
+    after_notify_id: Option<i64>,
+    after_create_at: Option<chrono::NaiveDateTime>,
+    limit: u64,
+) -> Result<Vec<notify_record::Model>, KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    let mut query = notify_record::Entity::find()
+        .filter(notify_record::Column::ClientId.eq(client_id))
+        .filter(notify_record::Column::UserId.eq(user_id))
+        .filter(unread_condition())
+        .filter(notify_record::Column::NotifyType.eq(enums::NotifyType::InApp))
+        .filter(not_expired_condition());
+
+    if let Some(after_notify_id) = after_notify_id {
+        query = query.filter(notify_record::Column::Id.gt(after_notify_id));
+    }
+
+    if let Some(after_create_at) = after_create_at {
+        query = query.filter(notify_record::Column::CreateAt.gt(after_create_at));
+    }
+
+    query
+        .order_by_asc(notify_record::Column::Id)
+        .limit(limit)
+        .all(db)
+        .await
+        .map_err(|e| {
+            warn!("find unread app records failed: {:?}", e);
+            KgsStatus::InternalServerError
+        })
+}
+
+/// 批次新增通知
+pub async fn insert_many<C>(
+    db: &C,
+    active_models: Vec<notify_record::ActiveModel>,
+) -> Result<(), KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    if active_models.is_empty() {
+        return Ok(());
+    }
+
+    notify_record::Entity::insert_many(active_models)
+        .exec(db)
+        .await
+        .map_err(|e| {
+            warn!("insert notify_records error: {:?}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+    Ok(())
+}
+
+/// 將裝置已收到的通知標記為已送達 只更新尚未送達的通知
+pub async fn update_delivered<C>(
+    db: &C,
+    client_id: i64,
+    user_id: i64,
+    notify_record_ids: Vec<i64>,
+) -> Result<u64, KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    let result = notify_record::Entity::update_many()
+        .col_expr(
+            notify_record::Column::NotifyStatus,
+            Expr::value(enums::NotifyStatus::Delivered),
+        )
+        .filter(notify_record::Column::ClientId.eq(client_id))
+        .filter(notify_record::Column::UserId.eq(user_id))
+        .filter(notify_record::Column::NotifyStatus.eq(enums::NotifyStatus::Unread))
+        .filter(notify_record::Column::Id.is_in(notify_record_ids))
+        .exec(db)
+        .await
+        .map_err(|e| {
+            warn!("update delivered error: {:?}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+    Ok(result.rows_affected)
+}
+
+/// 獲取尚未送達裝置的通知
+pub async fn find_undelivered_by_ids<C>(
+    db: &C,
+    notify_record_ids: Vec<i64>,
+) -> Result<Vec<notify_record::Model>, KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    notify_record::Entity::find()
+        .filter(notify_record::Column::Id.is_in(notify_record_ids))
+        .filter(notify_record::Column::NotifyStatus.eq(enums::NotifyStatus::Unread))
+        .filter(not_expired_condition())
+        .all(db)
+        .await
+        .map_err(|e| {
+            warn!("find undelivered records error: {:?}", e);
+            KgsStatus::InternalServerError
+        })
+}
+
 pub async fn update_all_with_notify_level<C>(
     db: &C,
     client_id: i64,
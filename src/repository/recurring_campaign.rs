@@ -0,0 +1,142 @@
+use crate::entity::recurring_campaign;
+use chrono::NaiveDateTime;
+use kgs_err::models::status::Status as KgsStatus;
+use kgs_tracing::warn;
+use protos::backstage_notify;
+use sea_orm::*;
+use sea_query::Expr;
+
+pub async fn find_one_by_id_and_client_id<C>(
+    db: &C,
+    id: i64,
+    client_id: i64,
+) -> Result<recurring_campaign::Model, KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    let result = recurring_campaign::Entity::find()
+        .filter(recurring_campaign::Column::Id.eq(id))
+        .filter(recurring_campaign::Column::ClientId.eq(client_id))
+        .one(db)
+        .await
+        .map_err(|_| KgsStatus::DataNotFound)?;
+
+    result.ok_or(KgsStatus::DataNotFound)
+}
+
+/// 獲取已到執行時間且啟用中的活動
+pub async fn find_due_list<C>(
+    db: &C,
+    now: NaiveDateTime,
+) -> Result<Vec<recurring_campaign::Model>, KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    recurring_campaign::Entity::find()
+        .filter(recurring_campaign::Column::IsActive.eq(true))
+        .filter(recurring_campaign::Column::NextRunAt.lte(now))
+        .order_by_asc(recurring_campaign::Column::NextRunAt)
+        .all(db)
+        .await
+        .map_err(|err| {
+            warn!("find due recurring_campaign error: {}", err);
+            KgsStatus::InternalServerError
+        })
+}
+
+/// 佔用一次活動執行 只有next_run_at仍為預期值時才會更新成功
+/// 多個pod同時掃描到同一個活動時 只有一個pod會拿到這次執行
+pub async fn claim_occurrence<C>(
+    db: &C,
+    id: i64,
+    expected_next_run_at: NaiveDateTime,
+    next_run_at: Option<NaiveDateTime>,
+    last_run_at: NaiveDateTime,
+) -> Result<bool, KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    let result = recurring_campaign::Entity::update_many()
+        .col_expr(
+            recurring_campaign::Column::NextRunAt,
+            Expr::value(next_run_at),
+        )
+        .col_expr(
+            recurring_campaign::Column::LastRunAt,
+            Expr::value(last_run_at),
+        )
+        .filter(recurring_campaign::Column::Id.eq(id))
+        .filter(recurring_campaign::Column::NextRunAt.eq(expected_next_run_at))
+        .exec(db)
+        .await
+        .map_err(|err| {
+            warn!("claim recurring_campaign occurrence error: {}", err);
+            KgsStatus::InternalServerError
+        })?;
+
+    Ok(result.rows_affected == 1)
+}
+
+pub async fn delete<C>(db: &C, id: i64, client_id: i64) -> Result<(), KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    let result = recurring_campaign::Entity::delete_many()
+        .filter(recurring_campaign::Column::Id.eq(id))
+        .filter(recurring_campaign::Column::ClientId.eq(client_id))
+        .exec(db)
+        .await
+        .map_err(|err| {
+            warn!("delete recurring_campaign error: {}", err);
+            KgsStatus::InternalServerError
+        })?;
+
+    if result.rows_affected == 0 {
+        return Err(KgsStatus::DataNotFound);
+    }
+
+    Ok(())
+}
+
+pub async fn get_recurring_campaign_list<C>(
+    db: &C,
+    page_size: u64,
+    now_page: u64,
+    request: backstage_notify::GetRecurringCampaignListRequest,
+) -> Result<(Vec<recurring_campaign::Model>, u64, u64), KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    let query = recurring_campaign::Entity::find()
+        .filter(recurring_campaign::Column::ClientId.eq(request.client_id))
+        .apply_if(request.campaign_name, |query, v| {
+            if request.is_fuzzy {
+                query.filter(recurring_campaign::Column::CampaignName.like(format!("%{}%", v)))
+            } else {
+                query.filter(recurring_campaign::Column::CampaignName.eq(v))
+            }
+        })
+        .apply_if(request.is_active, |query, v| {
+            query.filter(recurring_campaign::Column::IsActive.eq(v))
+        })
+        .order_by_desc(recurring_campaign::Column::CreateAt);
+
+    // get total rows
+    let total_rows = query.clone().count(db).await.map_err(|err| {
+        warn!("get_recurring_campaign_list count error: {}", err);
+        KgsStatus::DataNotFound
+    })?;
+
+    // 創建分頁器
+    let paginator = query.paginate(db, page_size);
+
+    let records = paginator.fetch_page(now_page - 1).await.map_err(|err| {
+        warn!("get_recurring_campaign_list fetch_page error: {}", err);
+        KgsStatus::DataNotFound
+    })?;
+
+    // 總頁數
+    let total_pages = (total_rows as f64 / page_size as f64).ceil() as u64;
+
+    Ok((records, total_rows, total_pages))
+}
@@ -0,0 +1,64 @@
+use crate::entity::recurring_campaign_detail;
+use crate::enums;
+use crate::helper;
+use kgs_err::models::status::Status as KgsStatus;
+use kgs_tracing::warn;
+use sea_orm::*;
+
+pub async fn insert<C>(
+    txn: &C,
+    recurring_campaign_id: i64,
+    notify_type: enums::NotifyType,
+    title: String,
+    content: String,
+) -> Result<recurring_campaign_detail::Model, KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    let id = helper::generate_snowflake_id().await;
+    let entity = recurring_campaign_detail::ActiveModel {
+        id: Set(id),
+        recurring_campaign_id: Set(recurring_campaign_id),
+        notify_type: Set(notify_type),
+        title: Set(title),
+        content: Set(content),
+    }
+    .insert(txn)
+    .await
+    .map_err(|e| {
+        warn!("insert recurring_campaign_detail failed: {:?}", e);
+        KgsStatus::InternalServerError
+    })?;
+    Ok(entity)
+}
+
+pub async fn find_list_by_campaign_id<C>(
+    db: &C,
+    recurring_campaign_id: i64,
+) -> Result<Vec<recurring_campaign_detail::Model>, KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    let result = recurring_campaign_detail::Entity::find()
+        .filter(recurring_campaign_detail::Column::RecurringCampaignId.eq(recurring_campaign_id))
+        .all(db)
+        .await
+        .map_err(|_| KgsStatus::DataNotFound)?;
+    Ok(result)
+}
+
+pub async fn delete_by_campaign_id<C>(db: &C, recurring_campaign_id: i64) -> Result<(), KgsStatus>
+where
+    C: ConnectionTrait,
+{
+    recurring_campaign_detail::Entity::delete_many()
+        .filter(recurring_campaign_detail::Column::RecurringCampaignId.eq(recurring_campaign_id))
+        .exec(db)
+        .await
+        .map_err(|e| {
+            warn!("delete recurring_campaign_detail failed: {:?}", e);
+            KgsStatus::InternalServerError
+        })?;
+
+    Ok(())
+}